@@ -0,0 +1,799 @@
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use async_broadcast::RecvError;
+use futures::future::{Either, select};
+use gio::prelude::*;
+use gio::{FileMonitor, FileMonitorEvent, FileMonitorFlags};
+use gtk4::gdk::Monitor;
+use gtk4::glib::{self, WeakRef, clone};
+use gtk4::prelude::*;
+use gtk4::{
+    self as gtk, Align, Application, ApplicationWindow, Label, Orientation, RevealerTransitionType,
+};
+use gtk4_layer_shell::{Edge, Layer, LayerShell};
+use log::{error, trace};
+use serde_json::json;
+
+use crate::compositor::{self, CompositorEvent, ConnectionState, MonitorInfo, WorkspaceInfo};
+use crate::config::{BarConfig, BarPosition, Config, FullscreenMode, WidgetConfig};
+use crate::control::protocol::{ControlCommand, Visibility};
+use crate::widgets::auto_hide::AutoHide;
+use crate::{style, widgets};
+
+// Editors tend to write files in several steps, wait for things to settle before reloading.
+const RELOAD_DELAY: Duration = Duration::from_millis(200);
+
+// A bar window and the layout it was built with
+struct Bar {
+    bar_config: BarConfig,
+    window: WeakRef<ApplicationWindow>,
+    // Hidden with twbarctl, the bar stays hidden when a fullscreen window goes away
+    hidden_by_user: Cell<bool>,
+    // Hidden while the monitor's active workspace has a fullscreen window
+    hidden_for_fullscreen: Cell<bool>,
+}
+
+impl Bar {
+    fn close(&self) {
+        if let Some(window) = self.window.upgrade() {
+            window.close();
+        }
+    }
+
+    // Hides the bar or moves it above the fullscreen window, depending on the config.
+    fn set_fullscreen(&self, fullscreen: bool) {
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        match self.bar_config.fullscreen {
+            FullscreenMode::Hide => {
+                self.hidden_for_fullscreen.set(fullscreen);
+                window.set_visible(!fullscreen && !self.hidden_by_user.get());
+            }
+            FullscreenMode::Overlay if fullscreen => {
+                window.set_layer(Layer::Overlay);
+                window.set_exclusive_zone(0);
+            }
+            FullscreenMode::Overlay => {
+                window.set_layer(Layer::Top);
+                if !self.bar_config.auto_hide {
+                    window.auto_exclusive_zone_enable();
+                }
+            }
+            FullscreenMode::Ignore => {}
+        }
+    }
+
+    // Shows or hides the bar for twbarctl. Showing a bar hidden for a fullscreen window brings it back until the
+    // next time the workspace goes fullscreen.
+    fn set_visibility(&self, visibility: &Visibility) {
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        let visible = visibility.apply(window.is_visible());
+        self.hidden_by_user.set(!visible);
+        if visible {
+            self.hidden_for_fullscreen.set(false);
+        }
+        window.set_visible(visible);
+    }
+}
+
+// The bars shown on a monitor
+struct MonitorBars {
+    monitor: Monitor,
+    // Empty while the bar is disabled on this monitor
+    bars: Vec<Bar>,
+    // Whether the monitor's active workspace has a fullscreen window
+    fullscreen: bool,
+}
+
+// Owns the bar windows for every monitor and rebuilds them when the config changes.
+pub struct BarManager {
+    app: Application,
+    config: RefCell<Config>,
+    // Connector name -> bars
+    monitors: RefCell<HashMap<String, MonitorBars>>,
+    config_monitor: OnceCell<FileMonitor>,
+    config_reload_pending: Cell<bool>,
+    style_reload_pending: Cell<bool>,
+}
+
+impl BarManager {
+    pub fn new(app: &Application) -> Rc<Self> {
+        let config = Config::load();
+        config.apply();
+        Rc::new(Self {
+            app: app.clone(),
+            config: RefCell::new(config),
+            monitors: RefCell::new(HashMap::new()),
+            config_monitor: OnceCell::new(),
+            config_reload_pending: Cell::new(false),
+            style_reload_pending: Cell::new(false),
+        })
+    }
+
+    pub fn add_monitor(self: &Rc<Self>, monitor: &Monitor, connector: &str) {
+        if self.monitors.borrow().contains_key(connector) {
+            return;
+        }
+
+        trace!("New monitor found: {}", connector);
+        self.monitors.borrow_mut().insert(
+            connector.to_owned(),
+            MonitorBars {
+                monitor: monitor.clone(),
+                bars: Vec::new(),
+                fullscreen: false,
+            },
+        );
+        self.update_monitor(connector);
+    }
+
+    // Adds bars for new monitors and closes the bars of monitors that are no longer connected.
+    pub fn sync_monitors(self: &Rc<Self>, monitors: &[(Monitor, String)]) {
+        self.monitors
+            .borrow_mut()
+            .retain(|connector, monitor_bars| {
+                if monitors.iter().any(|(_, name)| name == connector) {
+                    trace!("Monitor {} is still connected", connector);
+                    return true;
+                }
+
+                trace!("Closing windows due to monitor removal: {}", connector);
+                monitor_bars.bars.iter().for_each(Bar::close);
+                false
+            });
+
+        for (monitor, connector) in monitors.iter() {
+            self.add_monitor(monitor, connector);
+        }
+    }
+
+    // Reloads the config file and rebuilds the bars whose layout changed. A config which fails to load is ignored so
+    // a typo while editing doesn't wipe out the bar.
+    pub fn reload_config(self: &Rc<Self>) -> Result<(), String> {
+        let config = Config::try_load()
+            .map_err(|err| format!("Failed to reload config, keeping the current one: {}", err))?;
+
+        if *self.config.borrow() == config {
+            trace!("Config unchanged");
+            return Ok(());
+        }
+        config.apply();
+        self.config.replace(config);
+
+        let connectors: Vec<String> = self.monitors.borrow().keys().cloned().collect();
+        for connector in connectors {
+            self.update_monitor(&connector);
+        }
+        Ok(())
+    }
+
+    // Runs a command received on the control socket, returning the reply body.
+    pub fn handle_command(self: &Rc<Self>, command: ControlCommand) -> Result<String, String> {
+        match command {
+            ControlCommand::Bar {
+                visibility,
+                connector,
+            } => {
+                self.for_each_bar(connector.as_deref(), |bar| bar.set_visibility(&visibility))?;
+                Ok(String::new())
+            }
+            ControlCommand::Reveal { connector } => {
+                let auto_hides: Vec<AutoHide> = self
+                    .windows(connector.as_deref())?
+                    .iter()
+                    .filter_map(|window| window.child().and_downcast::<AutoHide>())
+                    .collect();
+                if auto_hides.is_empty() {
+                    return Err("No auto hidden bars".to_owned());
+                }
+                auto_hides.iter().for_each(AutoHide::peek);
+                Ok(String::new())
+            }
+            ControlCommand::Reload => {
+                style::reload();
+                self.reload_config()?;
+                Ok(String::new())
+            }
+            ControlCommand::Widget {
+                visibility,
+                widget,
+                connector,
+            } => {
+                for widget in self.find_widgets(&widget, connector.as_deref())? {
+                    widget.set_visible(visibility.apply(widget.is_visible()));
+                }
+                Ok(String::new())
+            }
+            ControlCommand::Popover { widget, connector } => {
+                let popovers: Vec<gtk::Popover> = self
+                    .find_widgets(&widget, connector.as_deref())?
+                    .iter()
+                    .flat_map(child_popovers)
+                    .collect();
+                if popovers.is_empty() {
+                    return Err(format!("The {} widget has no popover", widget));
+                }
+                for popover in popovers {
+                    if popover.is_visible() {
+                        popover.popdown();
+                    } else {
+                        popover.popup();
+                    }
+                }
+                Ok(String::new())
+            }
+            ControlCommand::DumpState => serde_json::to_string_pretty(&self.dump_state())
+                .map_err(|err| format!("Failed to serialize state: {}", err)),
+        }
+    }
+
+    // Returns the bar windows on the monitor, or on every monitor if no connector is given.
+    fn for_each_bar(&self, connector: Option<&str>, f: impl FnMut(&Bar)) -> Result<(), String> {
+        let monitors = self.monitors.borrow();
+        if let Some(connector) = connector
+            && !monitors.contains_key(connector)
+        {
+            return Err(format!("Unknown monitor: {}", connector));
+        }
+
+        monitors
+            .iter()
+            .filter(|(name, _)| connector.is_none_or(|connector| connector == *name))
+            .flat_map(|(_, monitor_bars)| monitor_bars.bars.iter())
+            .for_each(f);
+        Ok(())
+    }
+
+    fn windows(&self, connector: Option<&str>) -> Result<Vec<ApplicationWindow>, String> {
+        let mut windows = Vec::new();
+        self.for_each_bar(connector, |bar| windows.extend(bar.window.upgrade()))?;
+        Ok(windows)
+    }
+
+    fn find_widgets(
+        &self,
+        widget_type: &str,
+        connector: Option<&str>,
+    ) -> Result<Vec<gtk::Widget>, String> {
+        let widgets: Vec<gtk::Widget> = self
+            .windows(connector)?
+            .iter()
+            .flat_map(bar_widgets)
+            .map(|(_section, widget)| widget)
+            .filter(|widget| widget.widget_name() == widget_type)
+            .collect();
+        if widgets.is_empty() {
+            return Err(format!("No {} widget found", widget_type));
+        }
+        Ok(widgets)
+    }
+
+    fn dump_state(&self) -> serde_json::Value {
+        let monitors = self.monitors.borrow();
+        let mut connectors: Vec<&String> = monitors.keys().collect();
+        connectors.sort();
+
+        let monitors: Vec<serde_json::Value> = connectors
+            .into_iter()
+            .map(|connector| {
+                let bars: Vec<serde_json::Value> = monitors[connector]
+                    .bars
+                    .iter()
+                    .map(|bar| {
+                        let window = bar.window.upgrade();
+                        let mut sections = serde_json::Map::new();
+                        for (section, widget) in window.iter().flat_map(bar_widgets) {
+                            let widgets = sections
+                                .entry(section)
+                                .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+                            if let serde_json::Value::Array(widgets) = widgets {
+                                widgets.push(json!({
+                                    "type": widget.widget_name().as_str(),
+                                    "visible": widget.is_visible(),
+                                }));
+                            }
+                        }
+
+                        json!({
+                            "position": bar.bar_config.position,
+                            "visible": window.as_ref().is_some_and(|window| window.is_visible()),
+                            "hidden_by_user": bar.hidden_by_user.get(),
+                            "hidden_for_fullscreen": bar.hidden_for_fullscreen.get(),
+                            "widgets": sections,
+                        })
+                    })
+                    .collect();
+
+                json!({
+                    "connector": connector,
+                    "bars": bars,
+                })
+            })
+            .collect();
+
+        json!({ "monitors": monitors })
+    }
+
+    // Resolves the bars for the monitor from the config and creates, rebuilds or closes windows to match.
+    fn update_monitor(self: &Rc<Self>, connector: &str) {
+        let connector = connector.to_owned();
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = me)]
+            self,
+            async move {
+                let monitor_info = find_monitor_info(&connector).await;
+                let bar_configs = me.config.borrow().bars_for(&monitor_info).to_vec();
+
+                let mut monitors = me.monitors.borrow_mut();
+                // The monitor may have been removed while we were waiting on the compositor.
+                let Some(monitor_bars) = monitors.get_mut(&connector) else {
+                    return;
+                };
+
+                let old_bars = std::mem::take(&mut monitor_bars.bars);
+                let mut old_bars = old_bars.into_iter();
+                for (index, bar_config) in bar_configs.into_iter().enumerate() {
+                    let old_bar = old_bars.next();
+                    let old_window = old_bar.as_ref().and_then(|bar| bar.window.upgrade());
+                    // Windows which are kept stay hidden if they were hidden with twbarctl.
+                    let (window, hidden_by_user) = match (old_bar, old_window) {
+                        (Some(old_bar), Some(window)) if old_bar.bar_config == bar_config => {
+                            trace!("Bar {} for {} is unchanged", index, connector);
+                            (window, old_bar.hidden_by_user.get())
+                        }
+                        // Windows can't move between edges so only the contents are rebuilt in place.
+                        (Some(old_bar), Some(window))
+                            if old_bar.bar_config.position == bar_config.position =>
+                        {
+                            trace!("Rebuilding bar {} for {}", index, connector);
+                            populate_bar(&window, &monitor_info, &bar_config);
+                            (window, old_bar.hidden_by_user.get())
+                        }
+                        (old_bar, _) => {
+                            if let Some(old_bar) = old_bar {
+                                old_bar.close();
+                            }
+                            trace!("Creating bar {} for {}", index, connector);
+                            let window = bar_window(
+                                &me.app,
+                                &monitor_bars.monitor,
+                                &monitor_info,
+                                &bar_config,
+                            );
+                            (window, false)
+                        }
+                    };
+                    monitor_bars.bars.push(Bar {
+                        bar_config,
+                        window: window.downgrade(),
+                        hidden_by_user: Cell::new(hidden_by_user),
+                        hidden_for_fullscreen: Cell::new(false),
+                    });
+                }
+
+                // Close any bars which were removed from the config.
+                for old_bar in old_bars {
+                    trace!("Closing removed bar for {}", connector);
+                    old_bar.close();
+                }
+
+                // New and rebuilt bars start out in their normal state.
+                if monitor_bars.fullscreen {
+                    monitor_bars
+                        .bars
+                        .iter()
+                        .for_each(|bar| bar.set_fullscreen(true));
+                }
+            }
+        ));
+    }
+
+    // Follows which monitors have a fullscreen window on their active workspace and updates their bars to match.
+    pub fn watch_fullscreen(self: &Rc<Self>) {
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = me)]
+            self,
+            async move {
+                let compositor = compositor::instance().await;
+                let mut monitors_emitter = compositor.monitors();
+                let mut workspaces_emitter = compositor.workspaces();
+                let mut monitors: Vec<MonitorInfo> = Vec::new();
+                let mut workspaces: Vec<WorkspaceInfo> = Vec::new();
+
+                loop {
+                    match select(
+                        Box::pin(monitors_emitter.next()),
+                        Box::pin(workspaces_emitter.next()),
+                    )
+                    .await
+                    {
+                        Either::Left((new_monitors, _)) => monitors = new_monitors,
+                        Either::Right((new_workspaces, _)) => workspaces = new_workspaces,
+                    }
+
+                    for monitor in monitors.iter() {
+                        let fullscreen = workspaces.iter().any(|workspace| {
+                            workspace.id == monitor.active_workspace_id && workspace.has_fullscreen
+                        });
+                        me.set_fullscreen(&monitor.name, fullscreen);
+                    }
+                }
+            }
+        ));
+    }
+
+    fn set_fullscreen(&self, connector: &str, fullscreen: bool) {
+        let mut monitors = self.monitors.borrow_mut();
+        let Some(monitor_bars) = monitors.get_mut(connector) else {
+            return;
+        };
+        if monitor_bars.fullscreen == fullscreen {
+            return;
+        }
+
+        trace!("Fullscreen on {}: {}", connector, fullscreen);
+        monitor_bars.fullscreen = fullscreen;
+        monitor_bars
+            .bars
+            .iter()
+            .for_each(|bar| bar.set_fullscreen(fullscreen));
+    }
+
+    // Watches the config directory and the compositor's config reloads to pick up changes to the config and stylesheet.
+    pub fn watch_config(self: &Rc<Self>) {
+        if let Some(config_dir) = Config::config_dir() {
+            match gio::File::for_path(&config_dir)
+                .monitor_directory(FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)
+            {
+                Ok(monitor) => {
+                    monitor.connect_changed(clone!(
+                        #[weak(rename_to = me)]
+                        self,
+                        move |_monitor, file, other_file, event| {
+                            if event == FileMonitorEvent::Changed {
+                                // Wait for ChangesDoneHint instead of reloading on every write.
+                                return;
+                            }
+
+                            // For moves the new name is in other_file, e.g. editors which save by renaming a temp file.
+                            for name in [Some(file), other_file].into_iter().flatten() {
+                                match name.basename().and_then(|n| n.to_str().map(str::to_owned)) {
+                                    Some(n) if n == "config.toml" || n == "config.json" => {
+                                        me.schedule_reload(true, false)
+                                    }
+                                    Some(n) if n == "style.css" => me.schedule_reload(false, true),
+                                    _ => {}
+                                }
+                            }
+                        }
+                    ));
+                    self.config_monitor.set(monitor).ok();
+                }
+                Err(err) => error!("Failed to watch {}: {}", config_dir.display(), err),
+            }
+        }
+
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = me)]
+            self,
+            async move {
+                let compositor = compositor::instance().await;
+                let mut event_stream = compositor.events();
+
+                loop {
+                    match event_stream.recv_direct().await {
+                        Ok(CompositorEvent::ConfigReloaded) => me.schedule_reload(true, true),
+                        Ok(_) | Err(RecvError::Overflowed(_)) => {}
+                        Err(RecvError::Closed) => return,
+                    };
+                }
+            }
+        ));
+    }
+
+    fn schedule_reload(self: &Rc<Self>, reload_config: bool, reload_style: bool) {
+        let already_scheduled = self.config_reload_pending.get() || self.style_reload_pending.get();
+        self.config_reload_pending
+            .set(self.config_reload_pending.get() || reload_config);
+        self.style_reload_pending
+            .set(self.style_reload_pending.get() || reload_style);
+        if already_scheduled {
+            return;
+        }
+
+        glib::timeout_add_local_once(
+            RELOAD_DELAY,
+            clone!(
+                #[weak(rename_to = me)]
+                self,
+                move || {
+                    if me.style_reload_pending.take() {
+                        style::reload();
+                    }
+                    if me.config_reload_pending.take()
+                        && let Err(err) = me.reload_config()
+                    {
+                        error!("{}", err);
+                    }
+                }
+            ),
+        );
+    }
+}
+
+fn build_widget(
+    widget_config: &WidgetConfig,
+    monitor_info: &MonitorInfo,
+    orientation: Orientation,
+) -> gtk::Widget {
+    trace!("bar_window - building widget {:?}", widget_config);
+    let widget: gtk::Widget = match widget_config {
+        WidgetConfig::Workspaces => widgets::workspaces::Workspaces::new(monitor_info.id).into(),
+        WidgetConfig::Taskbar => widgets::taskbar::Taskbar::new(monitor_info.id).into(),
+        WidgetConfig::Cpu => widgets::cpu_usage::CpuUsage::new().into(),
+        WidgetConfig::Ram => widgets::ram_usage::RamUsage::new().into(),
+        WidgetConfig::Battery => widgets::battery_info::BatteryInfo::new().into(),
+        WidgetConfig::Clock { format } => match format {
+            Some(format) => widgets::clock::Clock::with_format(format).into(),
+            None => widgets::clock::Clock::new().into(),
+        },
+        WidgetConfig::CommandButton { label, commands } => {
+            widgets::command_button::CommandButton::new(label, commands.clone()).into()
+        }
+        WidgetConfig::ActiveWindow {
+            format,
+            max_width,
+            icon,
+            per_monitor,
+        } => widgets::active_window::ActiveWindow::new(
+            monitor_info.id,
+            format
+                .as_deref()
+                .unwrap_or(widgets::active_window::DEFAULT_FORMAT),
+            max_width.unwrap_or(-1),
+            *icon,
+            *per_monitor,
+        )
+        .into(),
+    };
+
+    // Lets the control socket find widgets by type, this also allows styling them with #type in CSS.
+    widget.set_widget_name(widget_config.type_name());
+    if let Some(orientable) = widget.dynamic_cast_ref::<gtk::Orientable>() {
+        orientable.set_orientation(orientation);
+    }
+    if orientation == Orientation::Vertical {
+        stack_labels(&widget);
+    }
+
+    widget
+}
+
+// Lets labels wrap at spaces so text like "  12%" is stacked into a column instead of widening a vertical bar.
+fn stack_labels(widget: &gtk::Widget) {
+    // Popovers have room to lay their text out normally.
+    if widget.is::<gtk::Popover>() {
+        return;
+    }
+
+    if let Some(label) = widget.downcast_ref::<Label>() {
+        label.set_wrap(true);
+        label.set_max_width_chars(1);
+        label.set_justify(gtk::Justification::Center);
+    }
+
+    let mut child = widget.first_child();
+    while let Some(widget) = child {
+        stack_labels(&widget);
+        child = widget.next_sibling();
+    }
+}
+
+// Returns the section name and widget for each configured widget in the bar window.
+fn bar_widgets(window: &ApplicationWindow) -> Vec<(String, gtk::Widget)> {
+    let mut widgets = Vec::new();
+    let Some(center_box) = find_center_box(window.upcast_ref()) else {
+        return widgets;
+    };
+
+    let sections = [
+        center_box.start_widget(),
+        center_box.center_widget(),
+        center_box.end_widget(),
+    ];
+    for section in sections.into_iter().flatten() {
+        let mut child = section.first_child();
+        while let Some(widget) = child {
+            child = widget.next_sibling();
+            widgets.push((section.widget_name().to_string(), widget));
+        }
+    }
+    widgets
+}
+
+// Finds the CenterBox holding the bar's sections, it is wrapped in more widgets when the bar is auto hidden.
+fn find_center_box(widget: &gtk::Widget) -> Option<gtk::CenterBox> {
+    let mut child = widget.first_child();
+    while let Some(widget) = child {
+        if let Some(center_box) = widget
+            .downcast_ref::<gtk::CenterBox>()
+            .cloned()
+            .or_else(|| find_center_box(&widget))
+        {
+            return Some(center_box);
+        }
+        child = widget.next_sibling();
+    }
+    None
+}
+
+// Returns the popovers which are direct children of the widget.
+fn child_popovers(widget: &gtk::Widget) -> Vec<gtk::Popover> {
+    let mut popovers = Vec::new();
+    let mut child = widget.first_child();
+    while let Some(widget) = child {
+        child = widget.next_sibling();
+        if let Ok(popover) = widget.downcast::<gtk::Popover>() {
+            popovers.push(popover);
+        }
+    }
+    popovers
+}
+
+fn section_box(
+    name: &str,
+    widget_configs: &[WidgetConfig],
+    monitor_info: &MonitorInfo,
+    orientation: Orientation,
+) -> gtk::Box {
+    let section = gtk::Box::new(orientation, 8);
+    section.set_widget_name(name);
+    for widget_config in widget_configs {
+        section.append(&build_widget(widget_config, monitor_info, orientation));
+    }
+    section
+}
+
+// Waits until the compositor knows about the monitor with the given connector name. GTK can see new monitors
+// before the compositor reports them.
+async fn find_monitor_info(connector: &str) -> MonitorInfo {
+    let compositor = compositor::instance().await;
+    let mut monitors_emitter = compositor.monitors();
+    loop {
+        let monitors = monitors_emitter.next().await;
+        match monitors.into_iter().find(|m| m.name == connector) {
+            Some(monitor) => return monitor,
+            None => trace!("Waiting for the compositor to report monitor {}", connector),
+        }
+    }
+}
+
+fn bar_window(
+    app: &Application,
+    monitor: &Monitor,
+    monitor_info: &MonitorInfo,
+    bar_config: &BarConfig,
+) -> ApplicationWindow {
+    trace!("In bar_window");
+    let window = ApplicationWindow::new(app);
+
+    window.init_layer_shell();
+    window.set_layer(Layer::Top);
+    window.set_monitor(Some(monitor));
+    // We use this for the interactive UI debugger since we need ctrl+shift+I to open it.
+    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::OnDemand);
+
+    // Anchor to every edge except the opposite one so the bar stretches along its edge.
+    let opposite_edge = match bar_config.position {
+        BarPosition::Top => Edge::Bottom,
+        BarPosition::Bottom => Edge::Top,
+        BarPosition::Left => Edge::Right,
+        BarPosition::Right => Edge::Left,
+    };
+    for edge in [Edge::Left, Edge::Top, Edge::Right, Edge::Bottom] {
+        window.set_anchor(edge, edge != opposite_edge);
+    }
+    if bar_config.position.is_vertical() {
+        window.set_default_width(1);
+    } else {
+        window.set_default_height(1);
+    }
+
+    populate_bar(&window, monitor_info, bar_config);
+
+    // Lets the stylesheet show that the compositor widgets are stale while the compositor is restarting.
+    let window_ref = window.downgrade();
+    glib::spawn_future_local(async move {
+        let compositor = compositor::instance().await;
+        let mut connection_state = compositor.connection_state();
+        loop {
+            let state = connection_state.next().await;
+            // Stops once the bar is closed instead of keeping its window alive.
+            let Some(window) = window_ref.upgrade() else {
+                return;
+            };
+            if state == ConnectionState::Disconnected {
+                window.add_css_class("disconnected");
+            } else {
+                window.remove_css_class("disconnected");
+            }
+        }
+    });
+
+    trace!("bar_window - setting window visible");
+    window.set_visible(true);
+
+    window
+}
+
+// Builds the widgets for the bar and replaces the window's current contents with them.
+fn populate_bar(window: &ApplicationWindow, monitor_info: &MonitorInfo, bar_config: &BarConfig) {
+    let orientation = if bar_config.position.is_vertical() {
+        Orientation::Vertical
+    } else {
+        Orientation::Horizontal
+    };
+
+    let left_box = section_box("left", &bar_config.left, monitor_info, orientation);
+    if orientation == Orientation::Vertical {
+        left_box.set_valign(Align::Start);
+    } else {
+        left_box.set_halign(Align::Start);
+    }
+    let center_box = section_box("center", &bar_config.center, monitor_info, orientation);
+    let right_box = section_box("right", &bar_config.right, monitor_info, orientation);
+    trace!("bar_window - all widgets added");
+
+    let hbox = gtk::CenterBox::new();
+    hbox.set_orientation(orientation);
+    hbox.set_start_widget(Some(&left_box));
+    hbox.set_center_widget(Some(&center_box));
+    hbox.set_end_widget(Some(&right_box));
+
+    if bar_config.auto_hide {
+        // Auto hidden bars overlap windows instead of pushing them aside.
+        window.set_exclusive_zone(0);
+        let transition_type = match bar_config.position {
+            BarPosition::Top => RevealerTransitionType::SlideDown,
+            BarPosition::Bottom => RevealerTransitionType::SlideUp,
+            BarPosition::Left => RevealerTransitionType::SlideRight,
+            BarPosition::Right => RevealerTransitionType::SlideLeft,
+        };
+        let auto_hide = AutoHide::new(&hbox, transition_type, bar_config.auto_hide_delay);
+        window.set_child(Some(&auto_hide));
+
+        if bar_config.reveal_on_submap {
+            // Only upgraded while handling an event so the widgets can be freed when the bar is rebuilt.
+            let auto_hide_ref = auto_hide.downgrade();
+            glib::spawn_future_local(async move {
+                let compositor = compositor::instance().await;
+                let mut event_stream = compositor.events();
+
+                loop {
+                    let event = event_stream.recv_direct().await;
+                    let Some(auto_hide) = auto_hide_ref.upgrade() else {
+                        return;
+                    };
+                    match event {
+                        Ok(CompositorEvent::BindingMode(mode)) => {
+                            auto_hide.set_pinned(!mode.is_empty())
+                        }
+                        Ok(_) | Err(RecvError::Overflowed(_)) => {}
+                        Err(RecvError::Closed) => return,
+                    };
+                }
+            });
+        }
+    } else {
+        window.auto_exclusive_zone_enable();
+        window.set_child(Some(&hbox));
+    }
+}
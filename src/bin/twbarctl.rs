@@ -0,0 +1,87 @@
+// Command line client for the control socket of a running twbar, e.g. for Hyprland bind lines:
+//   bind = SUPER, B, exec, twbarctl toggle
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use twbar::control::protocol::{ControlCommand, parse_reply, socket_path};
+
+// The bar replies from its main loop, allow for it being busy for a moment.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "Usage: twbarctl <command>
+
+Commands:
+  show|hide|toggle [connector]                        Change the visibility of the bars
+  reveal [connector]                                  Briefly slide out auto hidden bars
+  reload                                              Reload the config and stylesheet
+  dump-state                                          Print the bars and widgets as JSON
+  popover <widget type> [connector]                   Toggle a widget's popover, e.g. popover battery
+  widget show|hide|toggle <widget type> [connector]   Change the visibility of a widget
+
+Commands apply to every monitor unless a connector such as DP-1 is given.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        eprintln!("{}", USAGE);
+        return if args.is_empty() {
+            ExitCode::from(2)
+        } else {
+            ExitCode::SUCCESS
+        };
+    }
+
+    // Check the command before connecting so typos are reported even when the bar isn't running.
+    let command = args.join(" ");
+    if let Err(err) = ControlCommand::parse(&command) {
+        eprintln!("twbarctl: {}\n\n{}", err, USAGE);
+        return ExitCode::from(2);
+    }
+
+    match send(&command) {
+        Ok(body) => {
+            if !body.is_empty() {
+                println!("{}", body);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("twbarctl: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn send(command: &str) -> Result<String, String> {
+    let path = socket_path().ok_or("XDG_RUNTIME_DIR is not set")?;
+    let mut stream = UnixStream::connect(&path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound | ErrorKind::ConnectionRefused => {
+            format!(
+                "twbar is not running (no control socket at {})",
+                path.display()
+            )
+        }
+        _ => format!("Failed to connect to {}: {}", path.display(), err),
+    })?;
+    stream
+        .set_read_timeout(Some(REPLY_TIMEOUT))
+        .map_err(|err| err.to_string())?;
+
+    stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .map_err(|err| format!("Failed to send command: {}", err))?;
+
+    let mut reply = String::new();
+    stream
+        .read_to_string(&mut reply)
+        .map_err(|err| match err.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => {
+                "Timed out waiting for the bar".to_owned()
+            }
+            _ => format!("Failed to read reply: {}", err),
+        })?;
+
+    parse_reply(&reply)
+}
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use super::latest_value::LatestEventValueListener;
+use super::{WindowInfo, WorkspaceInfo};
+
+// Identifies an item across updates of the list it is in.
+pub trait Keyed {
+    type Key: Clone + Eq + Hash;
+
+    fn key(&self) -> Self::Key;
+}
+
+impl Keyed for WorkspaceInfo {
+    type Key = i32;
+
+    fn key(&self) -> i32 {
+        self.id
+    }
+}
+
+impl Keyed for WindowInfo {
+    type Key = String;
+
+    fn key(&self) -> String {
+        self.address.clone()
+    }
+}
+
+// Differences between two versions of a list, items are matched up by their key. Only the first item with a key
+// counts when a list has several.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListDiff<T: Keyed> {
+    pub added: Vec<T>,
+    pub removed: Vec<T::Key>,
+    // New values of the items which are in both versions but differ
+    pub changed: Vec<T>,
+}
+
+impl<T: Keyed + Clone + PartialEq> ListDiff<T> {
+    pub fn between(old: &[T], new: &[T]) -> Self {
+        let mut old_items: HashMap<T::Key, &T> = HashMap::new();
+        for item in old {
+            old_items.entry(item.key()).or_insert(item);
+        }
+
+        let mut new_keys = HashSet::new();
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for item in new {
+            let key = item.key();
+            if !new_keys.insert(key.clone()) {
+                continue;
+            }
+            match old_items.get(&key) {
+                None => added.push(item.clone()),
+                Some(old_item) if *old_item != item => changed.push(item.clone()),
+                Some(_) => {}
+            }
+        }
+        let mut removed_keys = HashSet::new();
+        let removed = old
+            .iter()
+            .map(Keyed::key)
+            .filter(|key| !new_keys.contains(key) && removed_keys.insert(key.clone()))
+            .collect();
+
+        Self {
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+// Like LatestEventValueListener but also says what changed since the last list it returned.
+pub struct DiffListener<T: Keyed + Clone> {
+    listener: LatestEventValueListener<Vec<T>>,
+    last_value: Vec<T>,
+}
+
+impl<T: Keyed + Clone + PartialEq> DiffListener<T> {
+    pub fn new(listener: LatestEventValueListener<Vec<T>>) -> Self {
+        Self {
+            listener,
+            last_value: Vec::new(),
+        }
+    }
+
+    // Waits for the list to change and returns it with its differences to the previous list. The first list is
+    // compared to an empty one so every item starts out as added. Updates which don't change any item are skipped.
+    pub async fn next(&mut self) -> (Vec<T>, ListDiff<T>) {
+        loop {
+            let value = self.listener.next().await;
+            let diff = ListDiff::between(&self.last_value, &value);
+            if diff.is_empty() {
+                continue;
+            }
+            self.last_value.clone_from(&value);
+            return (value, diff);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_std::sync::Arc;
+    use async_std::task;
+
+    use super::*;
+    use crate::compositor::latest_value::LatestEventValue;
+
+    fn workspace(id: i32, name: &str) -> WorkspaceInfo {
+        WorkspaceInfo {
+            id,
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn between_finds_added_removed_and_changed_items() {
+        let old = [workspace(1, "1"), workspace(2, "2"), workspace(3, "3")];
+        let new = [workspace(4, "4"), workspace(3, "mail"), workspace(1, "1")];
+
+        assert_eq!(
+            ListDiff::between(&old, &new),
+            ListDiff {
+                added: vec![workspace(4, "4")],
+                removed: vec![2],
+                changed: vec![workspace(3, "mail")],
+            }
+        );
+    }
+
+    #[test]
+    fn between_unchanged_lists_is_empty() {
+        let old = [workspace(1, "1"), workspace(2, "2")];
+        // Only the items matter, not their order.
+        let new = [workspace(2, "2"), workspace(1, "1")];
+
+        let diff = ListDiff::between(&old, &new);
+        assert!(diff.is_empty(), "{:?}", diff);
+        assert!(ListDiff::<WorkspaceInfo>::between(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn between_uses_the_first_item_with_a_key() {
+        let old = [
+            workspace(1, "1"),
+            workspace(1, "stale"),
+            workspace(2, "2"),
+            workspace(2, "2"),
+        ];
+        let new = [
+            workspace(1, "1"),
+            workspace(3, "3"),
+            workspace(3, "other"),
+            workspace(1, "other"),
+        ];
+
+        assert_eq!(
+            ListDiff::between(&old, &new),
+            ListDiff {
+                added: vec![workspace(3, "3")],
+                removed: vec![2],
+                changed: vec![],
+            }
+        );
+    }
+
+    #[async_std::test]
+    async fn diff_listener_skips_updates_without_changes() {
+        let value = Arc::new(LatestEventValue::new());
+        let mut listener = DiffListener::new(LatestEventValueListener::new(value.clone()));
+
+        value
+            .update(vec![workspace(1, "1"), workspace(2, "2")])
+            .await;
+        let (workspaces, diff) = listener.next().await;
+        assert_eq!(workspaces.len(), 2);
+        assert_eq!(diff.added, workspaces);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+
+        // The same list again is skipped, next only returns once something differs.
+        value
+            .update(vec![workspace(2, "2"), workspace(1, "1")])
+            .await;
+        let updater = task::spawn({
+            let value = value.clone();
+            async move {
+                task::sleep(Duration::from_millis(20)).await;
+                value.update(vec![workspace(2, "web")]).await;
+            }
+        });
+        let (workspaces, diff) = listener.next().await;
+        updater.await;
+        assert_eq!(workspaces, [workspace(2, "web")]);
+        assert_eq!(
+            diff,
+            ListDiff {
+                added: vec![],
+                removed: vec![1],
+                changed: vec![workspace(2, "web")],
+            }
+        );
+    }
+}
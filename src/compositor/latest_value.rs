@@ -0,0 +1,92 @@
+use async_std::sync::{Arc, Condvar, Mutex};
+
+// Holds the latest value of some state, letting any number of listeners wait for it to change.
+pub struct LatestEventValue<T> {
+    pub current_value: Mutex<(i64, T)>,
+
+    trigger: Condvar,
+}
+
+impl<T: Clone + Default> LatestEventValue<T> {
+    pub fn new() -> Self {
+        Self {
+            current_value: Mutex::new((0, T::default())),
+            trigger: Condvar::new(),
+        }
+    }
+
+    pub async fn update(&self, new_value: T) {
+        let mut data_lock = self.current_value.lock().await;
+        *data_lock = (data_lock.0 + 1, new_value);
+        self.trigger.notify_all();
+    }
+
+    pub async fn update_fn<F>(&self, update_func: F)
+    where
+        F: FnOnce(&T) -> Option<T>,
+    {
+        let mut data_lock = self.current_value.lock().await;
+        let updated_data = (update_func)(&data_lock.1);
+        if updated_data.is_some() {
+            *data_lock = (data_lock.0 + 1, updated_data.unwrap());
+            self.trigger.notify_all();
+        }
+    }
+}
+
+impl<T: Clone + Default> Default for LatestEventValue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct LatestEventValueListener<T: Clone> {
+    data: Arc<LatestEventValue<T>>,
+    last_seen_iteration: i64,
+}
+
+impl<T: Clone> LatestEventValueListener<T> {
+    pub fn new(data: Arc<LatestEventValue<T>>) -> Self {
+        Self {
+            data,
+            last_seen_iteration: 0,
+        }
+    }
+
+    pub async fn next(&mut self) -> T {
+        if let Some(guard) = self.data.current_value.try_lock() {
+            if self.last_seen_iteration != guard.0 {
+                self.last_seen_iteration = guard.0;
+                return guard.1.clone();
+            }
+        }
+
+        let guard = self
+            .data
+            .trigger
+            .wait_until(
+                self.data.current_value.lock().await,
+                |(iteration, _data)| *iteration != self.last_seen_iteration,
+            )
+            .await;
+
+        self.last_seen_iteration = guard.0;
+
+        guard.1.clone()
+    }
+
+    // Waits for a value the test is after, failing the test when it doesn't show up in time.
+    #[cfg(test)]
+    pub async fn wait_for(&mut self, done: impl Fn(&T) -> bool) -> T {
+        async_std::future::timeout(std::time::Duration::from_secs(10), async {
+            loop {
+                let value = self.next().await;
+                if done(&value) {
+                    return value;
+                }
+            }
+        })
+        .await
+        .expect("Timed out waiting for the value")
+    }
+}
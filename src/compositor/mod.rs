@@ -0,0 +1,205 @@
+// Compositor agnostic view of the workspaces, windows and monitors which the widgets are built on. Each supported
+// compositor has a backend implementing Compositor which keeps these up to date.
+use std::error::Error;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use async_broadcast::Receiver;
+use async_std::sync::{Arc, Mutex};
+use futures::future::BoxFuture;
+use gtk4::glib;
+use log::info;
+
+use crate::hyprland::compositor::HyprlandCompositor;
+use crate::niri::compositor::NiriCompositor;
+use crate::sway::compositor::SwayCompositor;
+use crate::wayland::compositor::WaylandCompositor;
+use diff::DiffListener;
+use latest_value::LatestEventValueListener;
+
+pub mod diff;
+pub mod latest_value;
+
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct WorkspaceInfo {
+    pub id: i32,
+    pub name: String,
+    pub monitor_id: i32,
+    // Order of the workspace on its monitor
+    pub position: i32,
+    // Number of windows on the workspace, None when the compositor doesn't say
+    pub windows: Option<i32>,
+    pub has_fullscreen: bool,
+    // A window on the workspace wants attention
+    pub urgent: bool,
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Eq, glib::Boxed)]
+#[boxed_type(name = "TwBarWindowInfo")]
+pub struct WindowInfo {
+    // Identifies the window when dispatching actions
+    pub address: String,
+    pub title: String,
+    pub class: String,
+    pub initial_title: String,
+    pub initial_class: String,
+    // None when the compositor doesn't say which workspace the window is on
+    pub workspace_id: Option<i32>,
+    pub monitor_id: i32,
+    // Position of the window, used to order the taskbar
+    pub at: (i32, i32),
+    // 0 for the focused window, higher for windows which were focused longer ago
+    pub focus_history_id: i32,
+    // The window wants attention
+    pub urgent: bool,
+}
+
+impl WindowInfo {
+    // Windows on an unknown workspace are taken to be on every workspace.
+    pub fn is_on_workspace(&self, workspace_id: i32) -> bool {
+        self.workspace_id.is_none_or(|id| id == workspace_id)
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct MonitorInfo {
+    pub id: i32,
+    // Connector name, e.g. DP-1
+    pub name: String,
+    pub description: String,
+    pub make: String,
+    pub model: String,
+    pub serial: String,
+    pub active_workspace_id: i32,
+    pub focused: bool,
+}
+
+// Whether the backend is currently connected to the compositor
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CompositorEvent {
+    ConfigReloaded,
+    // Keybind mode such as a Hyprland submap, empty when back to the default bindings
+    BindingMode(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    // Window address
+    FocusWindow(String),
+    // Window address
+    CloseWindow(String),
+    // Window address
+    ToggleMinimizeWindow(String),
+    // Window address
+    ToggleMaximizeWindow(String),
+    // Workspace id
+    FocusWorkspace(i32),
+}
+
+pub trait Compositor: Send + Sync {
+    fn workspaces(&self) -> LatestEventValueListener<Vec<WorkspaceInfo>>;
+    fn windows(&self) -> LatestEventValueListener<Vec<WindowInfo>>;
+    // Workspaces along with what changed in them since the last update
+    fn workspace_changes(&self) -> DiffListener<WorkspaceInfo> {
+        DiffListener::new(self.workspaces())
+    }
+    // Windows along with what changed in them since the last update
+    fn window_changes(&self) -> DiffListener<WindowInfo> {
+        DiffListener::new(self.windows())
+    }
+    fn monitors(&self) -> LatestEventValueListener<Vec<MonitorInfo>>;
+    // Id of the focused workspace
+    fn active_workspace(&self) -> LatestEventValueListener<i32>;
+    // Address of the focused window, empty when no window is focused
+    fn active_window(&self) -> LatestEventValueListener<String>;
+    fn connection_state(&self) -> LatestEventValueListener<ConnectionState>;
+    fn events(&self) -> Receiver<CompositorEvent>;
+    // Whether dispatch can carry out the action, the widgets only offer the actions which are supported
+    fn supports(&self, action: &Action) -> bool {
+        let _ = action;
+        true
+    }
+    fn dispatch(&self, action: Action) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Hyprland,
+    // Also used for i3
+    Sway,
+    Niri,
+    // Any other compositor, through the Wayland protocols
+    Wayland,
+}
+
+impl Backend {
+    // TWBAR_COMPOSITOR picks the backend, otherwise it is detected from the session's environment.
+    fn detect() -> Self {
+        match std::env::var("TWBAR_COMPOSITOR").as_deref() {
+            Ok("hyprland") => return Self::Hyprland,
+            Ok("sway") | Ok("i3") => return Self::Sway,
+            Ok("niri") => return Self::Niri,
+            Ok("wayland") => return Self::Wayland,
+            Ok(other) => log::error!("Unknown compositor {}, detecting it instead", other),
+            Err(_) => {}
+        }
+
+        let is_set = |name| std::env::var_os(name).is_some();
+        if is_set("HYPRLAND_INSTANCE_SIGNATURE") {
+            Self::Hyprland
+        } else if is_set("SWAYSOCK") || is_set("I3SOCK") {
+            Self::Sway
+        } else if is_set("NIRI_SOCKET") {
+            Self::Niri
+        } else if std::env::var_os("XDG_RUNTIME_DIR")
+            .is_some_and(|dir| Path::new(&dir).join("hypr").is_dir())
+        {
+            // Hyprland can still be found through its runtime directory.
+            Self::Hyprland
+        } else {
+            Self::Wayland
+        }
+    }
+}
+
+static FORCED_BACKEND: OnceLock<Backend> = OnceLock::new();
+
+// Uses the given backend instead of detecting it, must be called before the first call to instance().
+pub fn force_backend(backend: Backend) {
+    if FORCED_BACKEND.set(backend).is_err() {
+        log::warn!("The compositor backend was already forced");
+    }
+}
+
+// Returns the backend for the running compositor. Unlike the other singletons this is kept alive for the whole run
+// since widgets are rebuilt all the time and the backend's state would be lost in between.
+pub async fn instance() -> Arc<dyn Compositor> {
+    static INSTANCE: Mutex<Option<Arc<dyn Compositor>>> = Mutex::new(None);
+
+    let mut mutex_guard = INSTANCE.lock().await;
+    if let Some(instance) = mutex_guard.as_ref() {
+        return instance.clone();
+    }
+
+    let backend = FORCED_BACKEND
+        .get()
+        .copied()
+        .unwrap_or_else(Backend::detect);
+    info!("Using the {:?} backend", backend);
+    let instance: Arc<dyn Compositor> = match backend {
+        Backend::Hyprland => HyprlandCompositor::new().await,
+        Backend::Sway => SwayCompositor::new().await,
+        Backend::Niri => NiriCompositor::new().await,
+        Backend::Wayland => WaylandCompositor::new().await,
+    };
+    *mutex_guard = Some(instance.clone());
+    instance
+}
@@ -0,0 +1,274 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{error, info};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::compositor::MonitorInfo;
+use crate::hyprland;
+use crate::widgets::command_button::ButtonCommand;
+
+// Layout used when the user has not written a config file. This also serves as the reference for the config format.
+const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    // Bars shown on every monitor, either a single [bar] table or several [[bar]] tables.
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub bar: Vec<BarConfig>,
+    // Per monitor overrides, the first one matching a monitor is used.
+    #[serde(default)]
+    pub monitor: Vec<MonitorConfig>,
+    // Milliseconds Hyprland events are gathered for before the state they changed is fetched again
+    #[serde(default = "default_refresh_delay")]
+    pub refresh_delay: u32,
+}
+
+// Overrides the bar for the monitors which match every criteria that is set.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct MonitorConfig {
+    // Connector name, e.g. DP-1
+    pub connector: Option<String>,
+    pub description: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    // Set to false to not show a bar on matching monitors
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    // Bars for matching monitors, the top level bars are used if this isn't set
+    #[serde(default, deserialize_with = "optional_one_or_many")]
+    pub bar: Option<Vec<BarConfig>>,
+}
+
+impl MonitorConfig {
+    fn matches(&self, monitor: &MonitorInfo) -> bool {
+        let matches = |criteria: &Option<String>, value: &str| {
+            criteria.as_ref().is_none_or(|criteria| criteria == value)
+        };
+
+        matches(&self.connector, &monitor.name)
+            && matches(&self.description, &monitor.description)
+            && matches(&self.make, &monitor.make)
+            && matches(&self.model, &monitor.model)
+            && matches(&self.serial, &monitor.serial)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_auto_hide_delay() -> u32 {
+    500
+}
+
+fn default_refresh_delay() -> u32 {
+    50
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> From<OneOrMany<T>> for Vec<T> {
+    fn from(value: OneOrMany<T>) -> Self {
+        match value {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+fn one_or_many<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Vec<T>, D::Error> {
+    Ok(OneOrMany::deserialize(deserializer)?.into())
+}
+
+fn optional_one_or_many<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<T>>, D::Error> {
+    Ok(Some(one_or_many(deserializer)?))
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BarConfig {
+    // Screen edge the bar is attached to, bars on the left or right edge lay their widgets out in a column.
+    #[serde(default)]
+    pub position: BarPosition,
+    // Collapse the bar to a thin strip along its edge which slides the bar out when the pointer touches it. Auto
+    // hidden bars don't reserve space so windows are laid out under them.
+    #[serde(default)]
+    pub auto_hide: bool,
+    // Milliseconds to wait after the pointer leaves an auto hidden bar before hiding it
+    #[serde(default = "default_auto_hide_delay")]
+    pub auto_hide_delay: u32,
+    // Keep an auto hidden bar out while a keybind mode such as a Hyprland submap is active
+    #[serde(default)]
+    pub reveal_on_submap: bool,
+    // What to do while the monitor's active workspace has a fullscreen window
+    #[serde(default)]
+    pub fullscreen: FullscreenMode,
+    #[serde(default)]
+    pub left: Vec<WidgetConfig>,
+    #[serde(default)]
+    pub center: Vec<WidgetConfig>,
+    #[serde(default)]
+    pub right: Vec<WidgetConfig>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BarPosition {
+    #[default]
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FullscreenMode {
+    // Hide the bar until the workspace is no longer fullscreen
+    #[default]
+    Hide,
+    // Keep showing the bar above the fullscreen window without reserving space
+    Overlay,
+    // Leave the bar as it is, fullscreen windows cover it
+    Ignore,
+}
+
+impl BarPosition {
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WidgetConfig {
+    Workspaces,
+    Taskbar,
+    Cpu,
+    Ram,
+    Battery,
+    Clock {
+        // strftime style format string, see chrono::format::strftime
+        format: Option<String>,
+    },
+    CommandButton {
+        label: String,
+        commands: Vec<ButtonCommand>,
+    },
+    ActiveWindow {
+        // Text to show, {class}, {title} and {initial_title} are replaced with the window's details
+        format: Option<String>,
+        // Longest the text can get in characters before it is cut off with an ellipsis
+        max_width: Option<i32>,
+        // Show the application's icon next to the text
+        #[serde(default = "default_true")]
+        icon: bool,
+        // Show the window last focused on the bar's monitor rather than the focused window on any monitor
+        #[serde(default)]
+        per_monitor: bool,
+    },
+}
+
+impl WidgetConfig {
+    // The widget's type as written in the config, this is also used as the widget's name.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Workspaces => "workspaces",
+            Self::Taskbar => "taskbar",
+            Self::Cpu => "cpu",
+            Self::Ram => "ram",
+            Self::Battery => "battery",
+            Self::Clock { .. } => "clock",
+            Self::CommandButton { .. } => "command_button",
+            Self::ActiveWindow { .. } => "active_window",
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        toml::from_str(DEFAULT_CONFIG).expect("Built in config must be valid")
+    }
+}
+
+impl Config {
+    // Applies the settings which aren't tied to a bar.
+    pub fn apply(&self) {
+        hyprland::refresh::set_refresh_delay(Duration::from_millis(self.refresh_delay.into()));
+    }
+
+    // Returns the bars to show on the monitor, this is empty if the bar is disabled on it.
+    pub fn bars_for(&self, monitor: &MonitorInfo) -> &[BarConfig] {
+        match self.monitor.iter().find(|m| m.matches(monitor)) {
+            Some(monitor_config) if !monitor_config.enabled => &[],
+            Some(monitor_config) => monitor_config.bar.as_ref().unwrap_or(&self.bar),
+            None => &self.bar,
+        }
+    }
+
+    // Returns the directory holding twbar's config files, usually ~/.config/twbar
+    pub fn config_dir() -> Option<PathBuf> {
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(config_home.join("twbar"))
+    }
+
+    // Returns the first config file that exists, config.toml is preferred over config.json.
+    pub fn config_path() -> Option<PathBuf> {
+        let config_dir = Self::config_dir()?;
+        ["config.toml", "config.json"]
+            .iter()
+            .map(|file_name| config_dir.join(file_name))
+            .find(|path| path.exists())
+    }
+
+    // Loads the user's config, falling back to the built in layout if there is no config or it fails to load.
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_else(|err| {
+            error!("Failed to load config, using defaults: {}", err);
+            Self::default()
+        })
+    }
+
+    // Loads the user's config or the built in layout if there is no config file.
+    pub fn try_load() -> Result<Self, Box<dyn Error>> {
+        match Self::config_path() {
+            Some(path) => {
+                let config =
+                    Self::load_from(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                info!("Loaded config from {}", path.display());
+                Ok(config)
+            }
+            None => {
+                info!("No config file found, using defaults");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Ok(serde_json::from_str(&contents)?)
+        } else {
+            Ok(toml::from_str(&contents)?)
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::os::unix::net;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+use async_channel::Sender;
+use async_std::io::{self, BufReader, prelude::BufReadExt, prelude::WriteExt};
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::stream::StreamExt;
+use async_std::task;
+use gtk4::Application;
+use gtk4::glib::{self, clone};
+use gtk4::prelude::*;
+use log::{error, info, trace};
+
+use crate::bar_manager::BarManager;
+
+use protocol::{ControlCommand, format_reply};
+
+pub use twbar::control::protocol;
+
+// How long a client has to send its command before it is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(3);
+
+// A parsed command and where to send the reply.
+type ControlRequest = (ControlCommand, Sender<Result<String, String>>);
+
+// Serves the control socket so other programs can drive the running bar. The socket is served from a background
+// task while the commands are run on the main thread by the bar manager.
+pub fn start(app: &Application, bar_manager: &Rc<BarManager>) {
+    let Some(path) = protocol::socket_path() else {
+        error!("XDG_RUNTIME_DIR is not set, not starting the control socket");
+        return;
+    };
+    let Some(listener) = bind(&path) else {
+        return;
+    };
+    info!("Listening for commands on {}", path.display());
+
+    // Only removed once it's ours, another bar may be listening on it.
+    app.connect_shutdown(move |_| {
+        if let Err(err) = std::fs::remove_file(&path) {
+            error!(
+                "Failed to remove control socket {}: {}",
+                path.display(),
+                err
+            );
+        }
+    });
+
+    let (request_sender, request_receiver) = async_channel::unbounded::<ControlRequest>();
+    task::spawn(serve(listener.into(), request_sender));
+
+    glib::spawn_future_local(clone!(
+        #[weak]
+        bar_manager,
+        async move {
+            while let Ok((command, reply_sender)) = request_receiver.recv().await {
+                trace!("Control command: {:?}", command);
+                let reply = bar_manager.handle_command(command);
+                reply_sender.send(reply).await.ok();
+            }
+        }
+    ));
+}
+
+fn bind(path: &Path) -> Option<net::UnixListener> {
+    if path.exists() {
+        if net::UnixStream::connect(path).is_ok() {
+            error!(
+                "Another bar is already listening on {}, not starting the control socket",
+                path.display()
+            );
+            return None;
+        }
+
+        // Left behind by a bar which didn't shut down cleanly.
+        if let Err(err) = std::fs::remove_file(path) {
+            error!("Failed to remove stale socket {}: {}", path.display(), err);
+            return None;
+        }
+    }
+
+    match net::UnixListener::bind(path) {
+        Ok(listener) => Some(listener),
+        Err(err) => {
+            error!("Failed to bind control socket {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+async fn serve(listener: UnixListener, request_sender: Sender<ControlRequest>) {
+    let mut incoming = listener.incoming();
+    while let Some(stream) = incoming.next().await {
+        match stream {
+            Ok(stream) => {
+                task::spawn(handle_client(stream, request_sender.clone()));
+            }
+            Err(err) => error!("Failed to accept control connection: {}", err),
+        }
+    }
+}
+
+async fn handle_client(stream: UnixStream, request_sender: Sender<ControlRequest>) {
+    let mut line = String::new();
+    let reply =
+        match io::timeout(CLIENT_TIMEOUT, BufReader::new(&stream).read_line(&mut line)).await {
+            Err(err) => Err(format!("Failed to read command: {}", err)),
+            Ok(_) => match ControlCommand::parse(line.trim()) {
+                Err(err) => Err(err),
+                Ok(command) => {
+                    let (reply_sender, reply_receiver) = async_channel::bounded(1);
+                    if request_sender.send((command, reply_sender)).await.is_err() {
+                        Err("The bar is shutting down".to_owned())
+                    } else {
+                        reply_receiver
+                            .recv()
+                            .await
+                            .unwrap_or_else(|_| Err("The bar dropped the command".to_owned()))
+                    }
+                }
+            },
+        };
+
+    if let Err(err) = (&stream).write_all(format_reply(&reply).as_bytes()).await {
+        error!("Failed to reply to control command: {}", err);
+    }
+}
@@ -0,0 +1,287 @@
+// Wire format of the control socket. Clients write a single command line, the bar replies with a status line
+// ("ok" or "error") followed by the reply body and then closes the connection.
+use std::env::var_os;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+// Returns the control socket path, $XDG_RUNTIME_DIR/twbar-$WAYLAND_DISPLAY.sock so bars in different sessions
+// don't collide.
+pub fn socket_path() -> Option<PathBuf> {
+    let runtime_dir = PathBuf::from(var_os("XDG_RUNTIME_DIR")?);
+    let file_name = match var_os("WAYLAND_DISPLAY") {
+        Some(display) => format!("twbar-{}.sock", display.to_string_lossy()),
+        None => "twbar.sock".to_owned(),
+    };
+    Some(runtime_dir.join(file_name))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    Show,
+    Hide,
+    Toggle,
+}
+
+impl Visibility {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "show" => Some(Self::Show),
+            "hide" => Some(Self::Hide),
+            "toggle" => Some(Self::Toggle),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, visible: bool) -> bool {
+        match self {
+            Self::Show => true,
+            Self::Hide => false,
+            Self::Toggle => !visible,
+        }
+    }
+}
+
+// Commands accepted on the control socket. Where a connector is optional the command applies to every monitor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlCommand {
+    // show|hide|toggle [connector]
+    Bar {
+        visibility: Visibility,
+        connector: Option<String>,
+    },
+    // reveal [connector]
+    // Slides out auto hidden bars until the pointer has been away from them for their hide delay.
+    Reveal {
+        connector: Option<String>,
+    },
+    // reload
+    Reload,
+    // widget show|hide|toggle <widget type> [connector]
+    Widget {
+        visibility: Visibility,
+        widget: String,
+        connector: Option<String>,
+    },
+    // popover <widget type> [connector]
+    Popover {
+        widget: String,
+        connector: Option<String>,
+    },
+    // dump-state
+    DumpState,
+}
+
+impl ControlCommand {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut args = line.split_ascii_whitespace();
+        let command = args.next().ok_or("Empty command")?;
+        let parsed = match command {
+            "show" | "hide" | "toggle" => Self::Bar {
+                visibility: Visibility::parse(command).unwrap(),
+                connector: args.next().map(str::to_owned),
+            },
+            "reveal" => Self::Reveal {
+                connector: args.next().map(str::to_owned),
+            },
+            "reload" => Self::Reload,
+            "widget" => {
+                let action = args.next().ok_or("Missing widget action")?;
+                Self::Widget {
+                    visibility: Visibility::parse(action)
+                        .ok_or_else(|| format!("Unknown widget action: {}", action))?,
+                    widget: args.next().ok_or("Missing widget type")?.to_owned(),
+                    connector: args.next().map(str::to_owned),
+                }
+            }
+            "popover" => Self::Popover {
+                widget: args.next().ok_or("Missing widget type")?.to_owned(),
+                connector: args.next().map(str::to_owned),
+            },
+            "dump-state" => Self::DumpState,
+            _ => return Err(format!("Unknown command: {}", command)),
+        };
+
+        match args.next() {
+            Some(extra) => Err(format!("Unexpected argument: {}", extra)),
+            None => Ok(parsed),
+        }
+    }
+}
+
+pub fn format_reply<E: Display>(reply: &Result<String, E>) -> String {
+    match reply {
+        Ok(body) => format!("ok\n{}", body),
+        Err(err) => format!("error\n{}", err),
+    }
+}
+
+// Splits a reply from the bar into the body or error message.
+pub fn parse_reply(reply: &str) -> Result<String, String> {
+    match reply.split_once('\n').unwrap_or((reply, "")) {
+        ("ok", body) => Ok(body.to_owned()),
+        ("error", message) => Err(message.to_owned()),
+        _ => Err(format!("Unexpected reply from the bar: {}", reply)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<ControlCommand, String> {
+        ControlCommand::parse(line)
+    }
+
+    #[test]
+    fn bar_commands() {
+        assert_eq!(
+            parse("show"),
+            Ok(ControlCommand::Bar {
+                visibility: Visibility::Show,
+                connector: None,
+            })
+        );
+        assert_eq!(
+            parse("hide DP-1"),
+            Ok(ControlCommand::Bar {
+                visibility: Visibility::Hide,
+                connector: Some("DP-1".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse("  toggle   HDMI-A-1 "),
+            Ok(ControlCommand::Bar {
+                visibility: Visibility::Toggle,
+                connector: Some("HDMI-A-1".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse("reveal"),
+            Ok(ControlCommand::Reveal { connector: None })
+        );
+        assert_eq!(
+            parse("reveal DP-1"),
+            Ok(ControlCommand::Reveal {
+                connector: Some("DP-1".to_owned()),
+            })
+        );
+        assert_eq!(parse("reload"), Ok(ControlCommand::Reload));
+        assert_eq!(parse("dump-state"), Ok(ControlCommand::DumpState));
+    }
+
+    #[test]
+    fn widget_commands() {
+        assert_eq!(
+            parse("widget toggle clock"),
+            Ok(ControlCommand::Widget {
+                visibility: Visibility::Toggle,
+                widget: "clock".to_owned(),
+                connector: None,
+            })
+        );
+        assert_eq!(
+            parse("widget hide tray DP-1"),
+            Ok(ControlCommand::Widget {
+                visibility: Visibility::Hide,
+                widget: "tray".to_owned(),
+                connector: Some("DP-1".to_owned()),
+            })
+        );
+        assert_eq!(
+            parse("popover clock"),
+            Ok(ControlCommand::Popover {
+                widget: "clock".to_owned(),
+                connector: None,
+            })
+        );
+        assert_eq!(
+            parse("popover clock DP-1"),
+            Ok(ControlCommand::Popover {
+                widget: "clock".to_owned(),
+                connector: Some("DP-1".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn missing_arguments() {
+        assert_eq!(parse(""), Err("Empty command".to_owned()));
+        assert_eq!(parse("   "), Err("Empty command".to_owned()));
+        assert_eq!(parse("widget"), Err("Missing widget action".to_owned()));
+        assert_eq!(parse("widget show"), Err("Missing widget type".to_owned()));
+        assert_eq!(parse("popover"), Err("Missing widget type".to_owned()));
+    }
+
+    #[test]
+    fn extra_arguments() {
+        assert_eq!(
+            parse("show DP-1 DP-2"),
+            Err("Unexpected argument: DP-2".to_owned())
+        );
+        assert_eq!(
+            parse("reload now"),
+            Err("Unexpected argument: now".to_owned())
+        );
+        assert_eq!(
+            parse("dump-state all"),
+            Err("Unexpected argument: all".to_owned())
+        );
+        assert_eq!(
+            parse("widget show clock DP-1 extra"),
+            Err("Unexpected argument: extra".to_owned())
+        );
+        assert_eq!(
+            parse("popover clock DP-1 extra"),
+            Err("Unexpected argument: extra".to_owned())
+        );
+    }
+
+    #[test]
+    fn unknown_commands() {
+        assert_eq!(parse("quit"), Err("Unknown command: quit".to_owned()));
+        assert_eq!(
+            parse("widget reveal clock"),
+            Err("Unknown widget action: reveal".to_owned())
+        );
+        assert_eq!(
+            parse("widget Show clock"),
+            Err("Unknown widget action: Show".to_owned())
+        );
+    }
+
+    #[test]
+    fn visibility_apply() {
+        assert!(Visibility::Show.apply(false));
+        assert!(!Visibility::Hide.apply(true));
+        assert!(Visibility::Toggle.apply(false));
+        assert!(!Visibility::Toggle.apply(true));
+    }
+
+    #[test]
+    fn reply_round_trip() {
+        let ok: Result<String, String> = Ok("line 1\nline 2".to_owned());
+        assert_eq!(format_reply(&ok), "ok\nline 1\nline 2");
+        assert_eq!(parse_reply(&format_reply(&ok)), ok);
+
+        let empty: Result<String, String> = Ok(String::new());
+        assert_eq!(parse_reply(&format_reply(&empty)), empty);
+
+        let error: Result<String, String> = Err("No bar on DP-3".to_owned());
+        assert_eq!(format_reply(&error), "error\nNo bar on DP-3");
+        assert_eq!(parse_reply(&format_reply(&error)), error);
+    }
+
+    #[test]
+    fn unexpected_replies() {
+        assert_eq!(parse_reply("ok"), Ok(String::new()));
+        assert_eq!(parse_reply("error"), Err(String::new()));
+        assert_eq!(
+            parse_reply("maybe\nbody"),
+            Err("Unexpected reply from the bar: maybe\nbody".to_owned())
+        );
+        assert_eq!(
+            parse_reply(""),
+            Err("Unexpected reply from the bar: ".to_owned())
+        );
+    }
+}
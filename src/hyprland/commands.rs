@@ -1,38 +1,480 @@
+use std::fmt::Display;
 use std::time::Duration;
 
-use async_std::io::{self, ReadExt, WriteExt};
+use async_std::io::{self, ErrorKind, Read, ReadExt, WriteExt};
 
+use super::error::HyprlandError;
+use super::recording;
 use super::utils::Utils;
 
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(3);
+// Far beyond any real response, j/clients with hundreds of windows is well under a megabyte
+const MAX_RESPONSE_SIZE: u64 = 16 * 1024 * 1024;
+
 pub struct HyprlandCommands {}
 
 impl HyprlandCommands {
-    pub async fn send_command(command: &str) -> String {
-        let mut socket = Utils::create_dispatch_socket().await.unwrap();
-        socket.write_all(command.as_bytes()).await.unwrap();
-        io::timeout(Duration::from_secs(3), async {
-            let mut buf = vec![0; 1024];
-            let mut final_buffer = Vec::new();
-            let mut bytes_read = 1024;
-            while bytes_read == 1024 {
-                bytes_read = socket.read(&mut buf).await?;
-                if bytes_read > 0 {
-                    final_buffer.extend_from_slice(&buf[..bytes_read]);
-                }
+    pub async fn send_command(command: &str) -> Result<String, HyprlandError> {
+        let mut socket = Utils::create_dispatch_socket().await?;
+        socket.write_all(command.as_bytes()).await?;
+        let response = Self::read_response(socket, command, RESPONSE_TIMEOUT).await?;
+        recording::record_command(command, &response);
+        Ok(response)
+    }
+
+    async fn read_response(
+        socket: impl Read + Unpin,
+        command: &str,
+        timeout: Duration,
+    ) -> Result<String, HyprlandError> {
+        // Hyprland closes the socket once the whole response is written.
+        let mut response = Vec::new();
+        let result = io::timeout(
+            timeout,
+            socket
+                .take(MAX_RESPONSE_SIZE + 1)
+                .read_to_end(&mut response),
+        )
+        .await;
+        match result {
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::TimedOut => {
+                return Err(HyprlandError::Timeout(command.to_owned()));
+            }
+            Err(err) => return Err(err.into()),
+        }
+        if response.len() as u64 > MAX_RESPONSE_SIZE {
+            return Err(HyprlandError::ResponseTooLarge(command.to_owned()));
+        }
+
+        String::from_utf8(response).map_err(|err| HyprlandError::InvalidResponse(err.to_string()))
+    }
+
+    // Runs a dispatcher, failing unless Hyprland replies ok.
+    pub async fn dispatch(dispatch: &Dispatch) -> Result<(), HyprlandError> {
+        let dispatcher = dispatch.to_string();
+        let response = Self::send_command(&format!("dispatch {}", dispatcher)).await?;
+        if response.trim() != "ok" {
+            return Err(HyprlandError::DispatchFailed(
+                dispatcher,
+                response.trim().to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Runs the dispatchers in order over a single connection. Hyprland keeps going after a dispatcher fails so the
+    // error only says that one of them failed.
+    pub async fn dispatch_batch(dispatches: &[Dispatch]) -> Result<(), HyprlandError> {
+        let dispatchers: Vec<String> = dispatches.iter().map(|d| d.to_string()).collect();
+        let batch = Self::batch_command(&dispatchers)?;
+        if dispatchers.is_empty() {
+            return Ok(());
+        }
+
+        let response = Self::send_command(&batch).await?;
+        Self::check_batch_response(&dispatchers, &response)
+    }
+
+    fn batch_command(dispatchers: &[String]) -> Result<String, HyprlandError> {
+        // Hyprland splits batches on ; without any way to escape it.
+        if let Some(dispatcher) = dispatchers.iter().find(|d| d.contains(';')) {
+            return Err(HyprlandError::InvalidBatch(dispatcher.clone()));
+        }
+
+        let batch = dispatchers
+            .iter()
+            .map(|dispatcher| format!("dispatch {}", dispatcher))
+            .collect::<Vec<_>>()
+            .join(";");
+        Ok(format!("[[BATCH]]{}", batch))
+    }
+
+    fn check_batch_response(dispatchers: &[String], response: &str) -> Result<(), HyprlandError> {
+        // Each dispatcher answers ok, separated by whitespace depending on the Hyprland version.
+        let response_words: String = response.split_whitespace().collect();
+        if response_words != "ok".repeat(dispatchers.len()) {
+            return Err(HyprlandError::DispatchFailed(
+                dispatchers.join(";"),
+                response.trim().to_owned(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorkspaceTarget {
+    Id(i32),
+    Name(String),
+    // Workspace relative to the current one on the monitor, e.g. +1
+    Relative(i32),
+    Previous,
+    // Special workspace, the name can be empty for the default special workspace
+    Special(String),
+}
+
+impl Display for WorkspaceTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{}", id),
+            Self::Name(name) => write!(f, "name:{}", name),
+            Self::Relative(offset) => write!(f, "{:+}", offset),
+            Self::Previous => write!(f, "previous"),
+            Self::Special(name) if name.is_empty() => write!(f, "special"),
+            Self::Special(name) => write!(f, "special:{}", name),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullscreenMode {
+    Fullscreen,
+    // Fills the monitor apart from the bars and gaps
+    Maximize,
+}
+
+// Hyprland dispatchers. Windows are given by address, None acts on the focused window.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Dispatch {
+    FocusWindow(String),
+    CloseWindow(String),
+    // Closes the focused window
+    KillActive,
+    Workspace(WorkspaceTarget),
+    MoveToWorkspace {
+        workspace: WorkspaceTarget,
+        window: Option<String>,
+    },
+    // Like MoveToWorkspace but keeps the current workspace focused
+    MoveToWorkspaceSilent {
+        workspace: WorkspaceTarget,
+        window: Option<String>,
+    },
+    ToggleFloating(Option<String>),
+    Pin(Option<String>),
+    // Toggles fullscreen for the focused window
+    Fullscreen(FullscreenMode),
+    // Name of the special workspace, empty for the default one
+    ToggleSpecialWorkspace(String),
+    MoveWindowPixel {
+        x: i32,
+        y: i32,
+        // Moves to x, y instead of by x, y
+        exact: bool,
+        window: String,
+    },
+    ResizeWindowPixel {
+        width: i32,
+        height: i32,
+        // Resizes to width, height instead of by width, height
+        exact: bool,
+        window: String,
+    },
+    // Submap name, reset goes back to the default bindings
+    Submap(String),
+    Exec(String),
+}
+
+impl Display for Dispatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let window = |window: &Option<String>| match window {
+            Some(address) => format!(" address:{}", address),
+            None => String::new(),
+        };
+        // Window as an extra argument after the workspace
+        let workspace_window = |window: &Option<String>| match window {
+            Some(address) => format!(",address:{}", address),
+            None => String::new(),
+        };
+        let exact = |exact: &bool| if *exact { "exact " } else { "" };
+
+        match self {
+            Self::FocusWindow(address) => write!(f, "focuswindow address:{}", address),
+            Self::CloseWindow(address) => write!(f, "closewindow address:{}", address),
+            Self::KillActive => write!(f, "killactive"),
+            Self::Workspace(workspace) => write!(f, "workspace {}", workspace),
+            Self::MoveToWorkspace { workspace, window } => {
+                write!(
+                    f,
+                    "movetoworkspace {}{}",
+                    workspace,
+                    workspace_window(window)
+                )
+            }
+            Self::MoveToWorkspaceSilent { workspace, window } => {
+                write!(
+                    f,
+                    "movetoworkspacesilent {}{}",
+                    workspace,
+                    workspace_window(window)
+                )
             }
+            Self::ToggleFloating(address) => write!(f, "togglefloating{}", window(address)),
+            Self::Pin(address) => write!(f, "pin{}", window(address)),
+            Self::Fullscreen(FullscreenMode::Fullscreen) => write!(f, "fullscreen 0"),
+            Self::Fullscreen(FullscreenMode::Maximize) => write!(f, "fullscreen 1"),
+            Self::ToggleSpecialWorkspace(name) => write!(f, "togglespecialworkspace {}", name),
+            Self::MoveWindowPixel {
+                x,
+                y,
+                exact: is_exact,
+                window,
+            } => {
+                write!(
+                    f,
+                    "movewindowpixel {}{} {},address:{}",
+                    exact(is_exact),
+                    x,
+                    y,
+                    window
+                )
+            }
+            Self::ResizeWindowPixel {
+                width,
+                height,
+                exact: is_exact,
+                window,
+            } => write!(
+                f,
+                "resizewindowpixel {}{} {},address:{}",
+                exact(is_exact),
+                width,
+                height,
+                window
+            ),
+            Self::Submap(name) => write!(f, "submap {}", name),
+            Self::Exec(command) => write!(f, "exec {}", command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::os::unix::net::UnixStream;
+    use async_std::task;
+
+    use super::*;
+
+    // Reads the response from a peer which writes it and closes the connection.
+    async fn read_written(response: Vec<u8>) -> Result<String, HyprlandError> {
+        let (socket, mut peer) = UnixStream::pair().unwrap();
+        let writer = task::spawn(async move {
+            // Fails once the reader gives up on an oversized response.
+            let _ = peer.write_all(&response).await;
+        });
+        let result = HyprlandCommands::read_response(socket, "j/clients", RESPONSE_TIMEOUT).await;
+        writer.await;
+        result
+    }
+
+    #[async_std::test]
+    async fn reads_whole_response() {
+        // Responses used to be read in 1024 byte chunks, make sure the sizes around that aren't cut short.
+        for size in [0, 1023, 1024, 1025, 2048, 64 * 1024] {
+            let response = "x".repeat(size);
+            assert_eq!(
+                read_written(response.clone().into_bytes()).await.unwrap(),
+                response
+            );
+        }
+    }
+
+    #[async_std::test]
+    async fn reads_response_at_size_limit() {
+        let response = read_written(vec![b'x'; MAX_RESPONSE_SIZE as usize])
+            .await
+            .unwrap();
+        assert_eq!(response.len() as u64, MAX_RESPONSE_SIZE);
+    }
+
+    #[async_std::test]
+    async fn rejects_response_over_size_limit() {
+        match read_written(vec![b'x'; MAX_RESPONSE_SIZE as usize + 1]).await {
+            Err(HyprlandError::ResponseTooLarge(command)) => assert_eq!(command, "j/clients"),
+            result => panic!(
+                "Expected ResponseTooLarge, got {:?}",
+                result.map(|r| r.len())
+            ),
+        }
+    }
+
+    #[async_std::test]
+    async fn times_out_when_peer_keeps_connection_open() {
+        let (socket, mut peer) = UnixStream::pair().unwrap();
+        peer.write_all(b"partial").await.unwrap();
+        let result =
+            HyprlandCommands::read_response(socket, "j/clients", Duration::from_millis(100)).await;
+        match result {
+            Err(HyprlandError::Timeout(command)) => assert_eq!(command, "j/clients"),
+            result => panic!("Expected Timeout, got {:?}", result),
+        }
+        drop(peer);
+    }
+
+    #[async_std::test]
+    async fn rejects_invalid_utf8() {
+        assert!(matches!(
+            read_written(vec![b'o', b'k', 0xff]).await,
+            Err(HyprlandError::InvalidResponse(_))
+        ));
+    }
+
+    fn dispatchers(dispatches: &[Dispatch]) -> Vec<String> {
+        dispatches.iter().map(|d| d.to_string()).collect()
+    }
 
-            let response = String::from_utf8(final_buffer).unwrap();
-            Ok(response)
-        })
-        .await
-        .unwrap_or_default()
+    #[test]
+    fn workspace_target_display() {
+        assert_eq!(WorkspaceTarget::Id(3).to_string(), "3");
+        assert_eq!(WorkspaceTarget::Id(-99).to_string(), "-99");
+        assert_eq!(WorkspaceTarget::Name("web".into()).to_string(), "name:web");
+        assert_eq!(WorkspaceTarget::Relative(1).to_string(), "+1");
+        assert_eq!(WorkspaceTarget::Relative(-2).to_string(), "-2");
+        assert_eq!(WorkspaceTarget::Previous.to_string(), "previous");
+        assert_eq!(
+            WorkspaceTarget::Special(String::new()).to_string(),
+            "special"
+        );
+        assert_eq!(
+            WorkspaceTarget::Special("scratch".into()).to_string(),
+            "special:scratch"
+        );
     }
 
-    pub async fn set_active_window(window_address: &str) {
-        Self::send_command(&format!("dispatch focuswindow address:{}", window_address)).await;
+    #[test]
+    fn dispatch_display() {
+        let cases = [
+            (
+                Dispatch::FocusWindow("0x1".into()),
+                "focuswindow address:0x1",
+            ),
+            (
+                Dispatch::CloseWindow("0x1".into()),
+                "closewindow address:0x1",
+            ),
+            (Dispatch::KillActive, "killactive"),
+            (
+                Dispatch::Workspace(WorkspaceTarget::Relative(-1)),
+                "workspace -1",
+            ),
+            (
+                Dispatch::MoveToWorkspace {
+                    workspace: WorkspaceTarget::Id(2),
+                    window: None,
+                },
+                "movetoworkspace 2",
+            ),
+            (
+                Dispatch::MoveToWorkspace {
+                    workspace: WorkspaceTarget::Name("web".into()),
+                    window: Some("0x1".into()),
+                },
+                "movetoworkspace name:web,address:0x1",
+            ),
+            (
+                Dispatch::MoveToWorkspaceSilent {
+                    workspace: WorkspaceTarget::Special(String::new()),
+                    window: Some("0x1".into()),
+                },
+                "movetoworkspacesilent special,address:0x1",
+            ),
+            (Dispatch::ToggleFloating(None), "togglefloating"),
+            (
+                Dispatch::ToggleFloating(Some("0x1".into())),
+                "togglefloating address:0x1",
+            ),
+            (Dispatch::Pin(Some("0x1".into())), "pin address:0x1"),
+            (
+                Dispatch::Fullscreen(FullscreenMode::Fullscreen),
+                "fullscreen 0",
+            ),
+            (
+                Dispatch::Fullscreen(FullscreenMode::Maximize),
+                "fullscreen 1",
+            ),
+            (
+                Dispatch::ToggleSpecialWorkspace("scratch".into()),
+                "togglespecialworkspace scratch",
+            ),
+            (
+                Dispatch::MoveWindowPixel {
+                    x: -10,
+                    y: 20,
+                    exact: false,
+                    window: "0x1".into(),
+                },
+                "movewindowpixel -10 20,address:0x1",
+            ),
+            (
+                Dispatch::MoveWindowPixel {
+                    x: 10,
+                    y: 20,
+                    exact: true,
+                    window: "0x1".into(),
+                },
+                "movewindowpixel exact 10 20,address:0x1",
+            ),
+            (
+                Dispatch::ResizeWindowPixel {
+                    width: 800,
+                    height: 600,
+                    exact: true,
+                    window: "0x1".into(),
+                },
+                "resizewindowpixel exact 800 600,address:0x1",
+            ),
+            (Dispatch::Submap("reset".into()), "submap reset"),
+            (Dispatch::Exec("kitty -e htop".into()), "exec kitty -e htop"),
+        ];
+        for (dispatch, expected) in cases {
+            assert_eq!(dispatch.to_string(), expected);
+        }
     }
 
-    pub async fn set_active_workspace(workspace_id: i32) {
-        Self::send_command(&format!("dispatch workspace {}", workspace_id)).await;
+    #[test]
+    fn batch_command_joins_dispatchers() {
+        let dispatchers = dispatchers(&[
+            Dispatch::FocusWindow("0x1".into()),
+            Dispatch::Fullscreen(FullscreenMode::Maximize),
+        ]);
+        assert_eq!(
+            HyprlandCommands::batch_command(&dispatchers).unwrap(),
+            "[[BATCH]]dispatch focuswindow address:0x1;dispatch fullscreen 1"
+        );
+    }
+
+    #[test]
+    fn batch_command_rejects_semicolons() {
+        let dispatchers = dispatchers(&[
+            Dispatch::KillActive,
+            Dispatch::Exec("sleep 1; notify-send done".into()),
+        ]);
+        match HyprlandCommands::batch_command(&dispatchers) {
+            Err(HyprlandError::InvalidBatch(dispatcher)) => {
+                assert_eq!(dispatcher, "exec sleep 1; notify-send done");
+            }
+            result => panic!("Expected InvalidBatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn batch_response_needs_ok_per_dispatcher() {
+        let dispatchers = dispatchers(&[Dispatch::KillActive, Dispatch::Submap("reset".into())]);
+        for response in ["okok", "ok\n\nok", "ok\nok\n"] {
+            assert!(HyprlandCommands::check_batch_response(&dispatchers, response).is_ok());
+        }
+        for response in ["ok", "ok\n\nNo such dispatcher", "okokok", ""] {
+            match HyprlandCommands::check_batch_response(&dispatchers, response) {
+                Err(HyprlandError::DispatchFailed(batch, reply)) => {
+                    assert_eq!(batch, "killactive;submap reset");
+                    assert_eq!(reply, response.trim());
+                }
+                result => panic!(
+                    "Expected DispatchFailed for {:?}, got {:?}",
+                    response, result
+                ),
+            }
+        }
     }
 }
@@ -0,0 +1,327 @@
+use std::error::Error;
+
+use async_broadcast::{InactiveReceiver, Receiver, RecvError, broadcast};
+use async_std::sync::Arc;
+use async_std::task;
+use futures::future::BoxFuture;
+use log::error;
+
+use super::commands::{Dispatch, FullscreenMode, HyprlandCommands, WorkspaceTarget};
+use super::events::{HyprlandEvent, HyprlandEvents};
+use super::monitors::{HyprlandMonitor, HyprlandMonitors};
+use super::windows::{HyprlandWindow, HyprlandWindows};
+use super::workspaces::{HyprlandWorkspace, HyprlandWorkspaces};
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
+use crate::compositor::{
+    Action, Compositor, CompositorEvent, ConnectionState, MonitorInfo, WindowInfo, WorkspaceInfo,
+};
+
+impl From<&HyprlandWorkspace> for WorkspaceInfo {
+    fn from(value: &HyprlandWorkspace) -> Self {
+        Self {
+            id: value.id,
+            name: value.name.clone(),
+            monitor_id: value.monitor_id,
+            position: value.id,
+            windows: Some(value.windows),
+            has_fullscreen: value.has_fullscreen,
+            // Filled in from the windows by set_urgent, j/workspaces doesn't report it.
+            urgent: false,
+        }
+    }
+}
+
+// Marks the workspaces which have an urgent window.
+fn set_urgent(workspaces: &mut [WorkspaceInfo], windows: &[WindowInfo]) {
+    for workspace in workspaces.iter_mut() {
+        workspace.urgent = windows
+            .iter()
+            .any(|w| w.urgent && w.workspace_id == Some(workspace.id));
+    }
+}
+
+impl From<&HyprlandWindow> for WindowInfo {
+    fn from(value: &HyprlandWindow) -> Self {
+        Self {
+            address: value.address.clone(),
+            title: value.title.clone(),
+            class: value.class.clone(),
+            initial_title: value.initial_title.clone(),
+            initial_class: value.initial_class.clone(),
+            workspace_id: Some(value.workspace.id),
+            monitor_id: value.monitor,
+            at: value.at,
+            focus_history_id: value.focus_history_id,
+            urgent: value.urgent,
+        }
+    }
+}
+
+impl From<&HyprlandMonitor> for MonitorInfo {
+    fn from(value: &HyprlandMonitor) -> Self {
+        Self {
+            id: value.id,
+            name: value.name.clone(),
+            description: value.description.clone(),
+            make: value.make.clone(),
+            model: value.model.clone(),
+            serial: value.serial.clone(),
+            active_workspace_id: value.active_workspace.id as i32,
+            focused: value.focused,
+        }
+    }
+}
+
+// Compositor backend built on the Hyprland models, translating their state into the compositor agnostic types.
+pub struct HyprlandCompositor {
+    events: Arc<HyprlandEvents>,
+    workspaces: Arc<LatestEventValue<Vec<WorkspaceInfo>>>,
+    windows: Arc<LatestEventValue<Vec<WindowInfo>>>,
+    monitors: Arc<LatestEventValue<Vec<MonitorInfo>>>,
+    active_workspace: Arc<LatestEventValue<i32>>,
+    active_window: Arc<LatestEventValue<String>>,
+    event_receiver: InactiveReceiver<CompositorEvent>,
+}
+
+impl HyprlandCompositor {
+    pub async fn new() -> Arc<Self> {
+        let (mut event_sender, event_receiver) = broadcast(64);
+        event_sender.set_await_active(false);
+        event_sender.set_overflow(true);
+
+        let instance = Arc::new(Self {
+            events: HyprlandEvents::instance().await,
+            workspaces: Arc::new(LatestEventValue::new()),
+            windows: Arc::new(LatestEventValue::new()),
+            monitors: Arc::new(LatestEventValue::new()),
+            active_workspace: Arc::new(LatestEventValue::new()),
+            active_window: Arc::new(LatestEventValue::new()),
+            event_receiver: event_receiver.deactivate(),
+        });
+
+        let hyprland_workspaces = HyprlandWorkspaces::instance().await;
+        {
+            let workspaces = instance.workspaces.clone();
+            let windows = instance.windows.clone();
+            let mut workspaces_state = hyprland_workspaces.get_workspaces_state_emitter();
+            task::spawn(async move {
+                loop {
+                    let hyprland_workspaces = workspaces_state.next().await;
+                    let mut new_workspaces: Vec<WorkspaceInfo> = hyprland_workspaces
+                        .iter()
+                        .map(WorkspaceInfo::from)
+                        .collect();
+                    // Holding the windows stops them changing before the workspaces are updated.
+                    let windows = windows.current_value.lock().await;
+                    set_urgent(&mut new_workspaces, &windows.1);
+                    workspaces.update(new_workspaces).await;
+                }
+            });
+        }
+        {
+            let active_workspace = instance.active_workspace.clone();
+            let mut active_workspace_state = hyprland_workspaces.get_active_workspace_id_state();
+            task::spawn(async move {
+                loop {
+                    active_workspace
+                        .update(active_workspace_state.next().await)
+                        .await;
+                }
+            });
+        }
+        {
+            let windows = instance.windows.clone();
+            let workspaces = instance.workspaces.clone();
+            let mut windows_state = HyprlandWindows::instance()
+                .await
+                .get_windows_update_emitter();
+            task::spawn(async move {
+                loop {
+                    let hyprland_windows = windows_state.next().await;
+                    let new_windows: Vec<WindowInfo> =
+                        hyprland_windows.iter().map(WindowInfo::from).collect();
+                    windows.update(new_windows.clone()).await;
+                    workspaces
+                        .update_fn(|workspaces| {
+                            let mut updated_workspaces = workspaces.clone();
+                            set_urgent(&mut updated_workspaces, &new_windows);
+                            (updated_workspaces != *workspaces).then_some(updated_workspaces)
+                        })
+                        .await;
+                }
+            });
+        }
+        {
+            let monitors = instance.monitors.clone();
+            let mut monitors_state = HyprlandMonitors::instance()
+                .await
+                .get_monitor_state_emitter();
+            task::spawn(async move {
+                loop {
+                    let hyprland_monitors = monitors_state.next().await;
+                    monitors
+                        .update(hyprland_monitors.iter().map(MonitorInfo::from).collect())
+                        .await;
+                }
+            });
+        }
+        {
+            let active_window = instance.active_window.clone();
+            let mut connection_state = instance.events.get_connection_state_emitter();
+            task::spawn(async move {
+                loop {
+                    // The focused window isn't announced when connecting so ask for it.
+                    if connection_state.next().await == ConnectionState::Connected {
+                        match HyprlandCommands::send_command("j/activewindow").await {
+                            Ok(response) => {
+                                let address = serde_json::from_str::<serde_json::Value>(&response)
+                                    .ok()
+                                    .and_then(|window| {
+                                        window.get("address")?.as_str().map(str::to_owned)
+                                    })
+                                    .unwrap_or_default();
+                                active_window.update(address).await;
+                            }
+                            Err(err) => error!("Failed to get the active window: {}", err),
+                        }
+                    }
+                }
+            });
+        }
+        {
+            let active_window = instance.active_window.clone();
+            let mut events = instance.events.get_event_stream().await;
+            task::spawn(async move {
+                loop {
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(RecvError::Overflowed(_)) => continue,
+                        Err(RecvError::Closed) => return,
+                    };
+                    match event {
+                        HyprlandEvent::ActiveWindowV2(address) => {
+                            // Hyprland sends an empty address when no window is focused.
+                            let address = if address == "0x" {
+                                String::new()
+                            } else {
+                                address
+                            };
+                            active_window.update(address).await;
+                        }
+                        // Sending fails when nobody is listening, which is fine.
+                        HyprlandEvent::ConfigReloaded() => {
+                            event_sender
+                                .broadcast_direct(CompositorEvent::ConfigReloaded)
+                                .await
+                                .ok();
+                        }
+                        HyprlandEvent::Submap(submap) => {
+                            event_sender
+                                .broadcast_direct(CompositorEvent::BindingMode(submap))
+                                .await
+                                .ok();
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        instance
+    }
+}
+
+impl Compositor for HyprlandCompositor {
+    fn workspaces(&self) -> LatestEventValueListener<Vec<WorkspaceInfo>> {
+        LatestEventValueListener::new(self.workspaces.clone())
+    }
+
+    fn windows(&self) -> LatestEventValueListener<Vec<WindowInfo>> {
+        LatestEventValueListener::new(self.windows.clone())
+    }
+
+    fn monitors(&self) -> LatestEventValueListener<Vec<MonitorInfo>> {
+        LatestEventValueListener::new(self.monitors.clone())
+    }
+
+    fn active_workspace(&self) -> LatestEventValueListener<i32> {
+        LatestEventValueListener::new(self.active_workspace.clone())
+    }
+
+    fn active_window(&self) -> LatestEventValueListener<String> {
+        LatestEventValueListener::new(self.active_window.clone())
+    }
+
+    fn connection_state(&self) -> LatestEventValueListener<ConnectionState> {
+        self.events.get_connection_state_emitter()
+    }
+
+    fn events(&self) -> Receiver<CompositorEvent> {
+        self.event_receiver.activate_cloned()
+    }
+
+    fn supports(&self, action: &Action) -> bool {
+        !matches!(action, Action::ToggleMinimizeWindow(_))
+    }
+
+    fn dispatch(&self, action: Action) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>> {
+        Box::pin(async move {
+            let dispatch = match action {
+                Action::FocusWindow(address) => Dispatch::FocusWindow(address),
+                Action::CloseWindow(address) => Dispatch::CloseWindow(address),
+                Action::FocusWorkspace(id) => Dispatch::Workspace(WorkspaceTarget::Id(id)),
+                Action::ToggleMaximizeWindow(address) => {
+                    // The fullscreen dispatcher only acts on the focused window.
+                    HyprlandCommands::dispatch_batch(&[
+                        Dispatch::FocusWindow(address),
+                        Dispatch::Fullscreen(FullscreenMode::Maximize),
+                    ])
+                    .await?;
+                    return Ok(());
+                }
+                Action::ToggleMinimizeWindow(_) => {
+                    return Err(format!("{:?} is not supported on Hyprland", action).into());
+                }
+            };
+            HyprlandCommands::dispatch(&dispatch).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyprland::windows::HyprlandPartialWorkspace;
+
+    #[test]
+    fn urgent_windows_mark_their_workspace() {
+        let window = |address: &str, workspace_id: i32, urgent: bool| {
+            WindowInfo::from(&HyprlandWindow {
+                address: address.to_owned(),
+                workspace: HyprlandPartialWorkspace {
+                    id: workspace_id,
+                    name: workspace_id.to_string(),
+                },
+                urgent,
+                ..Default::default()
+            })
+        };
+        let windows = [window("0xa", 1, false), window("0xb", 2, true)];
+        assert!(windows[1].urgent);
+
+        let mut workspaces: Vec<WorkspaceInfo> = [1, 2, 3]
+            .into_iter()
+            .map(|id| {
+                WorkspaceInfo::from(&HyprlandWorkspace {
+                    id,
+                    name: id.to_string(),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        set_urgent(&mut workspaces, &windows);
+        let urgent: Vec<_> = workspaces.iter().map(|w| w.urgent).collect();
+        assert_eq!(urgent, [false, true, false]);
+    }
+}
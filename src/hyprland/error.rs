@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use async_std::io;
+
+#[derive(Debug)]
+pub enum HyprlandError {
+    MissingRuntimeDir,
+    // No instance signature was set and no running instance was found in $XDG_RUNTIME_DIR/hypr
+    NotRunning,
+    SocketNotFound(PathBuf),
+    InvalidResponse(String),
+    // Command which Hyprland didn't answer in time
+    Timeout(String),
+    // Command whose response was over the size limit
+    ResponseTooLarge(String),
+    // Dispatcher and Hyprland's reply
+    DispatchFailed(String, String),
+    // Dispatcher which can't be sent in a batch
+    InvalidBatch(String),
+    InvalidRecording(String),
+    Io(io::Error),
+}
+
+impl Display for HyprlandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingRuntimeDir => write!(f, "XDG_RUNTIME_DIR is not set"),
+            Self::NotRunning => write!(f, "Hyprland is not running"),
+            Self::SocketNotFound(path) => {
+                write!(f, "Hyprland socket {} does not exist", path.display())
+            }
+            Self::InvalidResponse(err) => write!(f, "Invalid response from Hyprland: {}", err),
+            Self::Timeout(command) => write!(f, "Hyprland didn't answer {} in time", command),
+            Self::ResponseTooLarge(command) => {
+                write!(f, "Hyprland's response to {} is too large", command)
+            }
+            Self::DispatchFailed(dispatcher, response) => {
+                write!(f, "Hyprland dispatch {} failed: {}", dispatcher, response)
+            }
+            Self::InvalidBatch(dispatcher) => {
+                write!(
+                    f,
+                    "Hyprland dispatch {} contains ; and can't be batched",
+                    dispatcher
+                )
+            }
+            Self::InvalidRecording(err) => write!(f, "Invalid Hyprland recording: {}", err),
+            Self::Io(err) => write!(f, "Hyprland socket error: {}", err),
+        }
+    }
+}
+
+impl Error for HyprlandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HyprlandError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
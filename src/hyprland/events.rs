@@ -2,13 +2,21 @@ use async_broadcast::{broadcast, InactiveReceiver, Receiver, Sender};
 use async_std::{
     io::{prelude::BufReadExt, BufReader},
     stream::StreamExt,
-    sync::{Arc, Condvar, Mutex, Weak},
+    sync::{Arc, Mutex, Weak},
     task,
 };
 use gio::glib::clone::Downgrade;
-use log::error;
+use log::{error, info, warn};
+use std::time::Duration;
 
+use super::recording;
 use super::utils::Utils;
+use crate::compositor::ConnectionState;
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
+
+// Delays between attempts to reconnect to Hyprland, doubling after each failure
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 pub trait EventData: Clone {
     fn parse(data: &str) -> Option<Self>
@@ -21,12 +29,14 @@ pub enum HyprlandEvent {
     Workspace(String),
     WorkspaceV2(WorkspaceV2),
     FocusedMon(FocusedMon),
+    FocusedMonV2(FocusedMonV2),
     ActiveWindow(ActiveWindow),
     // Window address
     ActiveWindowV2(String),
     Fullscreen(bool),
     // Monitor name
     MonitorRemoved(String),
+    MonitorRemovedV2(MonitorRemovedV2),
     // Monitor name
     MonitorAdded(String),
     MonitorAddedV2(MonitorAddedV2),
@@ -40,6 +50,7 @@ pub enum HyprlandEvent {
     MoveWorkspaceV2(MoveWorkspaceV2),
     RenameWorkspace(RenameWorkspace),
     ActiveSpecial(ActiveSpecial),
+    ActiveSpecialV2(ActiveSpecialV2),
     ActiveLayout(ActiveLayout),
     OpenWindow(OpenWindow),
     // Window address
@@ -69,7 +80,15 @@ pub enum HyprlandEvent {
     LockGroups(bool),
     ConfigReloaded(),
     Pin(Pin),
-    //WindowTitle(
+    // Window address
+    Bell(String),
+    // Data passed to hyprctl dispatch event
+    Custom(String),
+}
+
+// Event data leaves out the 0x which the requests and j/clients use
+fn window_address(data: &str) -> String {
+    format!("0x{}", data)
 }
 
 impl EventData for HyprlandEvent {
@@ -77,45 +96,59 @@ impl EventData for HyprlandEvent {
     where
         Self: Sized,
     {
-        if let Some((command, data)) = message.split_once(">>") {
-            let parsed_command = match command {
-                "workspace" => Some(Self::Workspace(data.to_owned())),
-                "workspacev2" => WorkspaceV2::parse(data).map(Self::WorkspaceV2),
-                "focusedmon" => FocusedMon::parse(data).map(Self::FocusedMon),
-                "activewindow" => ActiveWindow::parse(data).map(Self::ActiveWindow),
-                "activewindowv2" => Some(Self::ActiveWindowV2(format!("0x{}", data.to_owned()))),
-                "fullscreen" => Some(Self::Fullscreen(data == "1")),
-                "monitorremoved" => Some(Self::MonitorRemoved(data.to_owned())),
-                "monitoradded" => Some(Self::MonitorAdded(data.to_owned())),
-                "monitoraddedv2" => MonitorAddedV2::parse(data).map(Self::MonitorAddedV2),
-                "createworkspace" => Some(Self::CreateWorkspace(data.to_owned())),
-                "createworkspacev2" => CreateWorkspaceV2::parse(data).map(Self::CreateWorkspaceV2),
-                "destroyworkspace" => Some(Self::DestroyWorkspace(data.to_owned())),
-                "destroyworkspacev2" => {
-                    DestroyWorkspaceV2::parse(data).map(Self::DestroyWorkspaceV2)
-                }
-                "moveworkspace" => MoveWorkspace::parse(data).map(Self::MoveWorkspace),
-                "moveworkspacev2" => MoveWorkspaceV2::parse(data).map(Self::MoveWorkspaceV2),
-                "renameworkspace" => RenameWorkspace::parse(data).map(Self::RenameWorkspace),
-                "openwindow" => OpenWindow::parse(data).map(Self::OpenWindow),
-                "closewindow" => Some(Self::CloseWindow(format!("0x{}", data.to_owned()))),
-                "movewindow" => MoveWindow::parse(data).map(Self::MoveWindow),
-                "movewindowv2" => MoveWindowV2::parse(data).map(Self::MoveWindowV2),
-                "openlayer" => Some(Self::OpenLayer(data.to_owned())),
-                "changefloatingmode" => {
-                    ChangeFloatingMode::parse(data).map(Self::ChangeFloatingMode)
-                }
-                "windowtitle" => Some(Self::WindowTitle(data.to_owned())),
-                "windowtitlev2" => WindowTitleV2::parse(data).map(Self::WindowTitleV2),
-                _ => None,
-            };
-            if parsed_command.is_none() {
-                error!("Failed to parse command: {}>>{}", command, data);
-            }
-            parsed_command
-        } else {
-            None
+        // Events without data such as configreloaded may be sent without the separator.
+        let (command, data) = message.split_once(">>").unwrap_or((message, ""));
+        let parsed_command = match command {
+            "workspace" => Some(Self::Workspace(data.to_owned())),
+            "workspacev2" => WorkspaceV2::parse(data).map(Self::WorkspaceV2),
+            "focusedmon" => FocusedMon::parse(data).map(Self::FocusedMon),
+            "focusedmonv2" => FocusedMonV2::parse(data).map(Self::FocusedMonV2),
+            "activewindow" => ActiveWindow::parse(data).map(Self::ActiveWindow),
+            "activewindowv2" => Some(Self::ActiveWindowV2(window_address(data))),
+            "fullscreen" => Some(Self::Fullscreen(data == "1")),
+            "monitorremoved" => Some(Self::MonitorRemoved(data.to_owned())),
+            "monitorremovedv2" => MonitorRemovedV2::parse(data).map(Self::MonitorRemovedV2),
+            "monitoradded" => Some(Self::MonitorAdded(data.to_owned())),
+            "monitoraddedv2" => MonitorAddedV2::parse(data).map(Self::MonitorAddedV2),
+            "createworkspace" => Some(Self::CreateWorkspace(data.to_owned())),
+            "createworkspacev2" => CreateWorkspaceV2::parse(data).map(Self::CreateWorkspaceV2),
+            "destroyworkspace" => Some(Self::DestroyWorkspace(data.to_owned())),
+            "destroyworkspacev2" => DestroyWorkspaceV2::parse(data).map(Self::DestroyWorkspaceV2),
+            "moveworkspace" => MoveWorkspace::parse(data).map(Self::MoveWorkspace),
+            "moveworkspacev2" => MoveWorkspaceV2::parse(data).map(Self::MoveWorkspaceV2),
+            "renameworkspace" => RenameWorkspace::parse(data).map(Self::RenameWorkspace),
+            "activespecial" => ActiveSpecial::parse(data).map(Self::ActiveSpecial),
+            "activespecialv2" => ActiveSpecialV2::parse(data).map(Self::ActiveSpecialV2),
+            "activelayout" => ActiveLayout::parse(data).map(Self::ActiveLayout),
+            "openwindow" => OpenWindow::parse(data).map(Self::OpenWindow),
+            "closewindow" => Some(Self::CloseWindow(window_address(data))),
+            "movewindow" => MoveWindow::parse(data).map(Self::MoveWindow),
+            "movewindowv2" => MoveWindowV2::parse(data).map(Self::MoveWindowV2),
+            "openlayer" => Some(Self::OpenLayer(data.to_owned())),
+            "closelayer" => Some(Self::CloseLayer(data.to_owned())),
+            "submap" => Some(Self::Submap(data.to_owned())),
+            "changefloatingmode" => ChangeFloatingMode::parse(data).map(Self::ChangeFloatingMode),
+            "urgent" => Some(Self::Urgent(window_address(data))),
+            // Older Hyprland versions send minimize
+            "minimized" | "minimize" => Minimize::parse(data).map(Self::Minimize),
+            "screencast" => Screencast::parse(data).map(Self::Screencast),
+            "windowtitle" => Some(Self::WindowTitle(window_address(data))),
+            "windowtitlev2" => WindowTitleV2::parse(data).map(Self::WindowTitleV2),
+            "togglegroup" => ToggleGroup::parse(data).map(Self::ToggleGroup),
+            "moveintogroup" => Some(Self::MoveIntoGroup(window_address(data))),
+            "moveoutofgroup" => Some(Self::MoveOutOfGroup(window_address(data))),
+            "ignoregrouplock" => Some(Self::IgnoreGroupLock(data == "1")),
+            "lockgroups" => Some(Self::LockGroups(data == "1")),
+            "configreloaded" => Some(Self::ConfigReloaded()),
+            "pin" => Pin::parse(data).map(Self::Pin),
+            "bell" => Some(Self::Bell(window_address(data))),
+            "custom" => Some(Self::Custom(data.to_owned())),
+            _ => None,
+        };
+        if parsed_command.is_none() {
+            error!("Failed to parse command: {}>>{}", command, data);
         }
+        parsed_command
     }
 }
 
@@ -140,6 +173,27 @@ impl EventData for MonitorAddedV2 {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct MonitorRemovedV2 {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+impl EventData for MonitorRemovedV2 {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let mut parts = data.splitn(3, ',');
+        Some(Self {
+            id: parts.next()?.to_owned(),
+            name: parts.next()?.to_owned(),
+            description: parts.next()?.to_owned(),
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CreateWorkspaceV2 {
     pub id: String,
@@ -189,7 +243,8 @@ impl EventData for MoveWorkspace {
     where
         Self: Sized,
     {
-        let (name, monitor_name) = data.split_once(',')?;
+        // The name can contain commas, the monitor is always last.
+        let (name, monitor_name) = data.rsplit_once(',')?;
         Some(Self {
             name: name.to_owned(),
             monitor_name: monitor_name.to_owned(),
@@ -209,11 +264,13 @@ impl EventData for MoveWorkspaceV2 {
     where
         Self: Sized,
     {
-        let mut parts = data.splitn(3, ',');
+        // The name can contain commas, the id is always first and the monitor last.
+        let (id, rest) = data.split_once(',')?;
+        let (name, monitor_name) = rest.rsplit_once(',')?;
         Some(Self {
-            id: parts.next()?.to_owned(),
-            name: parts.next()?.to_owned(),
-            monitor_name: parts.next()?.to_owned(),
+            id: id.to_owned(),
+            name: name.to_owned(),
+            monitor_name: monitor_name.to_owned(),
         })
     }
 }
@@ -237,18 +294,71 @@ impl EventData for RenameWorkspace {
     }
 }
 
+// Special workspace opened on a monitor, the name is empty when it was closed
 #[derive(Clone, Debug)]
 pub struct ActiveSpecial {
     pub name: String,
     pub monitor_name: String,
 }
 
+impl EventData for ActiveSpecial {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        // The name can contain commas, the monitor is always last.
+        let (name, monitor_name) = data.rsplit_once(',')?;
+        Some(Self {
+            name: name.to_owned(),
+            monitor_name: monitor_name.to_owned(),
+        })
+    }
+}
+
+// Special workspace opened on a monitor, the id is 0 and the name empty when it was closed
+#[derive(Clone, Debug)]
+pub struct ActiveSpecialV2 {
+    pub id: i32,
+    pub name: String,
+    pub monitor_name: String,
+}
+
+impl EventData for ActiveSpecialV2 {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        // The name can contain commas, the id is always first and the monitor last.
+        let (id, rest) = data.split_once(',')?;
+        let (name, monitor_name) = rest.rsplit_once(',')?;
+        Some(Self {
+            id: if id.is_empty() { 0 } else { id.parse().ok()? },
+            name: name.to_owned(),
+            monitor_name: monitor_name.to_owned(),
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ActiveLayout {
     pub keyboard_name: String,
     pub layout_name: String,
 }
 
+impl EventData for ActiveLayout {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        // Keyboard names can't contain commas but layout names can.
+        let (keyboard_name, layout_name) = data.split_once(',')?;
+        Some(Self {
+            keyboard_name: keyboard_name.to_owned(),
+            layout_name: layout_name.to_owned(),
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct OpenWindow {
     pub address: String,
@@ -264,7 +374,7 @@ impl EventData for OpenWindow {
     {
         let mut parts = data.splitn(4, ',');
         Some(Self {
-            address: format!("0x{}", parts.next()?.to_owned()),
+            address: window_address(parts.next()?),
             workspace_name: parts.next()?.to_owned(),
             class: parts.next()?.to_owned(),
             title: parts.next()?.to_owned(),
@@ -283,9 +393,9 @@ impl EventData for MoveWindow {
     where
         Self: Sized,
     {
-        let (window_address, workspace_name) = data.split_once(',')?;
+        let (address, workspace_name) = data.split_once(',')?;
         Some(Self {
-            window_address: window_address.to_owned(),
+            window_address: window_address(address),
             workspace_name: workspace_name.to_owned(),
         })
     }
@@ -305,18 +415,17 @@ impl EventData for MoveWindowV2 {
     {
         let mut parts = data.splitn(3, ',');
         Some(Self {
-            window_address: format!("0x{}", parts.next()?.to_owned()),
+            window_address: window_address(parts.next()?),
             workspace_id: parts.next()?.to_owned().parse::<i32>().ok()?,
             workspace_name: parts.next()?.to_owned(),
         })
     }
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct ChangeFloatingMode {
-    window_address: String,
-    is_floating: bool,
+    pub window_address: String,
+    pub is_floating: bool,
 }
 
 impl EventData for ChangeFloatingMode {
@@ -324,34 +433,67 @@ impl EventData for ChangeFloatingMode {
     where
         Self: Sized,
     {
-        let (window_address, is_floating) = data.split_once(",")?;
+        let (address, is_floating) = data.split_once(',')?;
         Some(Self {
-            window_address: window_address.to_owned(),
+            window_address: window_address(address),
             is_floating: is_floating == "1",
         })
     }
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct Minimize {
-    window_address: String,
-    is_minimized: bool,
+    pub window_address: String,
+    pub is_minimized: bool,
+}
+
+impl EventData for Minimize {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let (address, is_minimized) = data.split_once(',')?;
+        Some(Self {
+            window_address: window_address(address),
+            is_minimized: is_minimized == "1",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreencastOwner {
+    Monitor,
+    Window,
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct Screencast {
-    state: bool,
-    // Should be an enum with 0 == monitor and 1 == window sharing
-    owner: bool,
+    // Whether a screencast is running
+    pub state: bool,
+    pub owner: ScreencastOwner,
+}
+
+impl EventData for Screencast {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let (state, owner) = data.split_once(',')?;
+        Some(Self {
+            state: state == "1",
+            owner: match owner {
+                "0" => ScreencastOwner::Monitor,
+                "1" => ScreencastOwner::Window,
+                _ => return None,
+            },
+        })
+    }
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct WindowTitleV2 {
-    address: String,
-    title: String,
+    pub address: String,
+    pub title: String,
 }
 
 impl EventData for WindowTitleV2 {
@@ -359,26 +501,55 @@ impl EventData for WindowTitleV2 {
     where
         Self: Sized,
     {
-        let (address, title) = data.split_once(",")?;
+        let (address, title) = data.split_once(',')?;
         Some(Self {
-            address: address.to_owned(),
+            address: window_address(address),
             title: title.to_owned(),
         })
     }
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct ToggleGroup {
-    state: bool,
-    window_addresses: Vec<String>,
+    // Whether the group was created, false when it was destroyed
+    pub state: bool,
+    pub window_addresses: Vec<String>,
+}
+
+impl EventData for ToggleGroup {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let (state, addresses) = data.split_once(',')?;
+        Some(Self {
+            state: state == "1",
+            window_addresses: addresses
+                .split(',')
+                .filter(|address| !address.is_empty())
+                .map(window_address)
+                .collect(),
+        })
+    }
 }
 
-#[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct Pin {
-    window_address: String,
-    pin_state: String,
+    pub window_address: String,
+    pub pinned: bool,
+}
+
+impl EventData for Pin {
+    fn parse(data: &str) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        let (address, pinned) = data.split_once(',')?;
+        Some(Self {
+            window_address: window_address(address),
+            pinned: pinned == "1",
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -415,92 +586,42 @@ impl EventData for FocusedMon {
     }
 }
 
-#[derive(Clone, Default, Debug)]
-pub struct ActiveWindow {
-    pub title: String,
+#[derive(Clone, Debug)]
+pub struct FocusedMonV2 {
+    pub monitor_name: String,
+    pub workspace_id: i32,
 }
 
-impl EventData for ActiveWindow {
+impl EventData for FocusedMonV2 {
     fn parse(data: &str) -> Option<Self> {
-        let (_class_name, title) = data.split_once(',')?;
+        let (monitor_name, workspace_id) = data.split_once(',')?;
 
         Some(Self {
-            title: title.to_owned(),
+            monitor_name: monitor_name.to_owned(),
+            workspace_id: workspace_id.parse().ok()?,
         })
     }
 }
 
-pub(super) struct LatestEventValue<T> {
-    pub current_value: Mutex<(i64, T)>,
-
-    trigger: Condvar,
-}
-
-impl<T: Clone + Default> LatestEventValue<T> {
-    pub fn new() -> Self {
-        Self {
-            current_value: Mutex::new((0, T::default())),
-            trigger: Condvar::new(),
-        }
-    }
-
-    pub async fn update(&self, new_value: T) {
-        let mut data_lock = self.current_value.lock().await;
-        *data_lock = (data_lock.0 + 1, new_value);
-        self.trigger.notify_all();
-    }
-
-    pub async fn update_fn<F>(&self, update_func: F)
-    where
-        F: FnOnce(&T) -> Option<T>,
-    {
-        let mut data_lock = self.current_value.lock().await;
-        let updated_data = (update_func)(&data_lock.1);
-        if updated_data.is_some() {
-            *data_lock = (data_lock.0 + 1, updated_data.unwrap());
-            self.trigger.notify_all();
-        }
-    }
-}
-
-pub struct LatestEventValueListener<T: Clone> {
-    data: Arc<LatestEventValue<T>>,
-    last_seen_iteration: i64,
+#[derive(Clone, Default, Debug)]
+pub struct ActiveWindow {
+    pub class: String,
+    pub title: String,
 }
 
-impl<T: Clone> LatestEventValueListener<T> {
-    pub(super) fn new(data: Arc<LatestEventValue<T>>) -> Self {
-        Self {
-            data,
-            last_seen_iteration: 0,
-        }
-    }
-
-    pub async fn next(&mut self) -> T {
-        if let Some(guard) = self.data.current_value.try_lock() {
-            if self.last_seen_iteration != guard.0 {
-                self.last_seen_iteration = guard.0;
-                return guard.1.clone();
-            }
-        }
-
-        let guard = self
-            .data
-            .trigger
-            .wait_until(
-                self.data.current_value.lock().await,
-                |(iteration, _data)| *iteration != self.last_seen_iteration,
-            )
-            .await;
-
-        self.last_seen_iteration = guard.0;
+impl EventData for ActiveWindow {
+    fn parse(data: &str) -> Option<Self> {
+        let (class, title) = data.split_once(',')?;
 
-        guard.1.clone()
+        Some(Self {
+            class: class.to_owned(),
+            title: title.to_owned(),
+        })
     }
 }
 
 pub struct HyprlandEvents {
-    active_window: Arc<LatestEventValue<ActiveWindow>>,
+    connection_state: Arc<LatestEventValue<ConnectionState>>,
     event_sender: Arc<Mutex<Sender<HyprlandEvent>>>,
     event_receiver: InactiveReceiver<HyprlandEvent>,
 }
@@ -521,52 +642,317 @@ impl HyprlandEvents {
     }
 
     async fn new() -> Arc<Self> {
-        let active_window = Arc::new(LatestEventValue::new());
         let (mut sender, receiver) = broadcast(256);
         sender.set_await_active(false);
         sender.set_overflow(true);
 
         let instance = Arc::new(HyprlandEvents {
-            active_window,
+            connection_state: Arc::new(LatestEventValue::new()),
             event_sender: Arc::new(Mutex::new(sender)),
             event_receiver: receiver.deactivate(),
         });
 
         let instance_weak = instance.downgrade();
         task::spawn(async move {
-            let event_stream = Utils::create_event_socket().await.unwrap();
-            let mut lines = BufReader::new(event_stream).lines();
-
-            while let Some(Ok(line)) = lines.next().await {
-                let instance = instance_weak.upgrade();
-                if instance.is_none() {
-                    return;
-                }
-                let instance = instance.as_ref().unwrap();
-
-                if let Some(event) = HyprlandEvent::parse(&line) {
-                    if let HyprlandEvent::ActiveWindow(active_window) = &event {
-                        instance.active_window.update(active_window.clone()).await
-                    };
-
-                    let event_sender = instance.event_sender.lock().await;
-
-                    let is_sent = event_sender.broadcast_direct(event).await;
-                    if is_sent.is_err() {
-                        println!("Error on sending event");
+            let mut retry_delay = MIN_RETRY_DELAY;
+            loop {
+                match Utils::create_event_socket().await {
+                    Ok(event_stream) => {
+                        info!("Connected to Hyprland");
+                        retry_delay = MIN_RETRY_DELAY;
+                        match instance_weak.upgrade() {
+                            Some(instance) => {
+                                instance
+                                    .connection_state
+                                    .update(ConnectionState::Connected)
+                                    .await
+                            }
+                            None => return,
+                        }
+
+                        let mut lines = BufReader::new(event_stream).lines();
+                        while let Some(Ok(line)) = lines.next().await {
+                            recording::record_event(&line);
+                            let instance = instance_weak.upgrade();
+                            if instance.is_none() {
+                                return;
+                            }
+                            let instance = instance.as_ref().unwrap();
+
+                            if let Some(event) = HyprlandEvent::parse(&line) {
+                                let event_sender = instance.event_sender.lock().await;
+
+                                let is_sent = event_sender.broadcast_direct(event).await;
+                                if is_sent.is_err() {
+                                    println!("Error on sending event");
+                                }
+                            }
+                        }
+
+                        warn!("Lost connection to Hyprland, reconnecting");
+                        match instance_weak.upgrade() {
+                            Some(instance) => {
+                                instance
+                                    .connection_state
+                                    .update(ConnectionState::Disconnected)
+                                    .await
+                            }
+                            None => return,
+                        }
+                    }
+                    Err(err) => {
+                        warn!(
+                            "Failed to connect to Hyprland, retrying in {:?}: {}",
+                            retry_delay, err
+                        );
+                        match instance_weak.upgrade() {
+                            Some(instance) => {
+                                instance
+                                    .connection_state
+                                    .update_fn(|state| {
+                                        (*state != ConnectionState::Disconnected)
+                                            .then_some(ConnectionState::Disconnected)
+                                    })
+                                    .await
+                            }
+                            None => return,
+                        }
                     }
                 }
+
+                task::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
             }
         });
 
         instance
     }
 
-    pub fn get_active_window_emitter(&self) -> LatestEventValueListener<ActiveWindow> {
-        LatestEventValueListener::new(self.active_window.clone())
+    pub fn get_connection_state_emitter(&self) -> LatestEventValueListener<ConnectionState> {
+        LatestEventValueListener::new(self.connection_state.clone())
     }
 
     pub async fn get_event_stream(&self) -> Receiver<HyprlandEvent> {
         self.event_receiver.activate_cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(message: &str) -> HyprlandEvent {
+        HyprlandEvent::parse(message).unwrap_or_else(|| panic!("Failed to parse {}", message))
+    }
+
+    #[test]
+    fn workspace_events() {
+        assert!(matches!(parse("workspace>>web"), HyprlandEvent::Workspace(name) if name == "web"));
+        assert!(
+            matches!(parse("workspacev2>>3,web,mail"), HyprlandEvent::WorkspaceV2(w) if w.id == 3 && w.name == "web,mail")
+        );
+        assert!(
+            matches!(parse("createworkspace>>3"), HyprlandEvent::CreateWorkspace(name) if name == "3")
+        );
+        assert!(
+            matches!(parse("createworkspacev2>>3,web"), HyprlandEvent::CreateWorkspaceV2(w) if w.id == "3" && w.name == "web")
+        );
+        assert!(
+            matches!(parse("destroyworkspace>>3"), HyprlandEvent::DestroyWorkspace(name) if name == "3")
+        );
+        assert!(
+            matches!(parse("destroyworkspacev2>>3,web"), HyprlandEvent::DestroyWorkspaceV2(w) if w.id == "3" && w.name == "web")
+        );
+        assert!(
+            matches!(parse("moveworkspace>>web,DP-1"), HyprlandEvent::MoveWorkspace(w) if w.name == "web" && w.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("moveworkspacev2>>3,web,DP-1"), HyprlandEvent::MoveWorkspaceV2(w) if w.id == "3" && w.name == "web" && w.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("moveworkspace>>web,mail,DP-1"), HyprlandEvent::MoveWorkspace(w) if w.name == "web,mail" && w.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("moveworkspacev2>>3,web,mail,DP-1"), HyprlandEvent::MoveWorkspaceV2(w) if w.id == "3" && w.name == "web,mail" && w.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("renameworkspace>>3,web, mail"), HyprlandEvent::RenameWorkspace(w) if w.id == "3" && w.new_name == "web, mail")
+        );
+    }
+
+    #[test]
+    fn special_workspace_events() {
+        assert!(
+            matches!(parse("activespecial>>special:scratch,DP-1"), HyprlandEvent::ActiveSpecial(s) if s.name == "special:scratch" && s.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("activespecial>>,DP-1"), HyprlandEvent::ActiveSpecial(s) if s.name.is_empty() && s.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("activespecialv2>>-98,special:scratch,DP-1"), HyprlandEvent::ActiveSpecialV2(s) if s.id == -98 && s.name == "special:scratch" && s.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("activespecial>>special:a,b,DP-1"), HyprlandEvent::ActiveSpecial(s) if s.name == "special:a,b" && s.monitor_name == "DP-1")
+        );
+        assert!(
+            matches!(parse("activespecialv2>>-98,special:a,b,DP-1"), HyprlandEvent::ActiveSpecialV2(s) if s.id == -98 && s.name == "special:a,b" && s.monitor_name == "DP-1")
+        );
+        // Closing the special workspace leaves out the id and name.
+        assert!(
+            matches!(parse("activespecialv2>>,,DP-1"), HyprlandEvent::ActiveSpecialV2(s) if s.id == 0 && s.name.is_empty() && s.monitor_name == "DP-1")
+        );
+    }
+
+    #[test]
+    fn monitor_events() {
+        assert!(
+            matches!(parse("focusedmon>>DP-1,web"), HyprlandEvent::FocusedMon(m) if m.monitor_name == "DP-1" && m.workspace_name == "web")
+        );
+        assert!(
+            matches!(parse("focusedmonv2>>DP-1,3"), HyprlandEvent::FocusedMonV2(m) if m.monitor_name == "DP-1" && m.workspace_id == 3)
+        );
+        assert!(
+            matches!(parse("monitorremoved>>DP-1"), HyprlandEvent::MonitorRemoved(name) if name == "DP-1")
+        );
+        assert!(
+            matches!(parse("monitorremovedv2>>1,DP-1,Dell Inc. U2720Q, rev 2"), HyprlandEvent::MonitorRemovedV2(m) if m.id == "1" && m.name == "DP-1" && m.description == "Dell Inc. U2720Q, rev 2")
+        );
+        assert!(
+            matches!(parse("monitoradded>>DP-1"), HyprlandEvent::MonitorAdded(name) if name == "DP-1")
+        );
+        assert!(
+            matches!(parse("monitoraddedv2>>1,DP-1,Dell Inc. U2720Q, rev 2"), HyprlandEvent::MonitorAddedV2(m) if m.id == "1" && m.name == "DP-1" && m.description == "Dell Inc. U2720Q, rev 2")
+        );
+    }
+
+    #[test]
+    fn window_events() {
+        assert!(
+            matches!(parse("activewindow>>firefox,Tabs, bookmarks - Mozilla Firefox"), HyprlandEvent::ActiveWindow(w) if w.class == "firefox" && w.title == "Tabs, bookmarks - Mozilla Firefox")
+        );
+        assert!(
+            matches!(parse("activewindowv2>>55d0c0a1b2c0"), HyprlandEvent::ActiveWindowV2(address) if address == "0x55d0c0a1b2c0")
+        );
+        assert!(
+            matches!(parse("openwindow>>55d0c0a1b2c0,web,firefox,Tabs, bookmarks"), HyprlandEvent::OpenWindow(w) if w.address == "0x55d0c0a1b2c0" && w.workspace_name == "web" && w.class == "firefox" && w.title == "Tabs, bookmarks")
+        );
+        assert!(
+            matches!(parse("closewindow>>55d0c0a1b2c0"), HyprlandEvent::CloseWindow(address) if address == "0x55d0c0a1b2c0")
+        );
+        assert!(
+            matches!(parse("movewindow>>55d0c0a1b2c0,web"), HyprlandEvent::MoveWindow(w) if w.window_address == "0x55d0c0a1b2c0" && w.workspace_name == "web")
+        );
+        assert!(
+            matches!(parse("movewindowv2>>55d0c0a1b2c0,3,web,mail"), HyprlandEvent::MoveWindowV2(w) if w.window_address == "0x55d0c0a1b2c0" && w.workspace_id == 3 && w.workspace_name == "web,mail")
+        );
+        assert!(
+            matches!(parse("changefloatingmode>>55d0c0a1b2c0,1"), HyprlandEvent::ChangeFloatingMode(w) if w.window_address == "0x55d0c0a1b2c0" && w.is_floating)
+        );
+        assert!(
+            matches!(parse("urgent>>55d0c0a1b2c0"), HyprlandEvent::Urgent(address) if address == "0x55d0c0a1b2c0")
+        );
+        assert!(
+            matches!(parse("minimized>>55d0c0a1b2c0,1"), HyprlandEvent::Minimize(w) if w.window_address == "0x55d0c0a1b2c0" && w.is_minimized)
+        );
+        assert!(
+            matches!(parse("minimize>>55d0c0a1b2c0,0"), HyprlandEvent::Minimize(w) if w.window_address == "0x55d0c0a1b2c0" && !w.is_minimized)
+        );
+        assert!(
+            matches!(parse("windowtitle>>55d0c0a1b2c0"), HyprlandEvent::WindowTitle(address) if address == "0x55d0c0a1b2c0")
+        );
+        assert!(
+            matches!(parse("windowtitlev2>>55d0c0a1b2c0,Tabs, bookmarks"), HyprlandEvent::WindowTitleV2(w) if w.address == "0x55d0c0a1b2c0" && w.title == "Tabs, bookmarks")
+        );
+        assert!(
+            matches!(parse("pin>>55d0c0a1b2c0,1"), HyprlandEvent::Pin(p) if p.window_address == "0x55d0c0a1b2c0" && p.pinned)
+        );
+        assert!(matches!(
+            parse("fullscreen>>1"),
+            HyprlandEvent::Fullscreen(true)
+        ));
+        assert!(matches!(
+            parse("fullscreen>>0"),
+            HyprlandEvent::Fullscreen(false)
+        ));
+        assert!(
+            matches!(parse("bell>>55d0c0a1b2c0"), HyprlandEvent::Bell(address) if address == "0x55d0c0a1b2c0")
+        );
+    }
+
+    #[test]
+    fn group_events() {
+        assert!(
+            matches!(parse("togglegroup>>1,55d0c0a1b2c0,55d0c0a1b1a0"), HyprlandEvent::ToggleGroup(g) if g.state && g.window_addresses == ["0x55d0c0a1b2c0", "0x55d0c0a1b1a0"])
+        );
+        assert!(
+            matches!(parse("togglegroup>>0,"), HyprlandEvent::ToggleGroup(g) if !g.state && g.window_addresses.is_empty())
+        );
+        assert!(
+            matches!(parse("moveintogroup>>55d0c0a1b2c0"), HyprlandEvent::MoveIntoGroup(address) if address == "0x55d0c0a1b2c0")
+        );
+        assert!(
+            matches!(parse("moveoutofgroup>>55d0c0a1b2c0"), HyprlandEvent::MoveOutOfGroup(address) if address == "0x55d0c0a1b2c0")
+        );
+        assert!(matches!(
+            parse("ignoregrouplock>>1"),
+            HyprlandEvent::IgnoreGroupLock(true)
+        ));
+        assert!(matches!(
+            parse("lockgroups>>0"),
+            HyprlandEvent::LockGroups(false)
+        ));
+    }
+
+    #[test]
+    fn other_events() {
+        assert!(
+            matches!(parse("activelayout>>at-translated-set-2-keyboard,English (US, intl., with dead keys)"), HyprlandEvent::ActiveLayout(l) if l.keyboard_name == "at-translated-set-2-keyboard" && l.layout_name == "English (US, intl., with dead keys)")
+        );
+        assert!(
+            matches!(parse("openlayer>>waybar"), HyprlandEvent::OpenLayer(namespace) if namespace == "waybar")
+        );
+        assert!(
+            matches!(parse("closelayer>>waybar"), HyprlandEvent::CloseLayer(namespace) if namespace == "waybar")
+        );
+        assert!(matches!(parse("submap>>resize"), HyprlandEvent::Submap(name) if name == "resize"));
+        assert!(matches!(parse("submap>>"), HyprlandEvent::Submap(name) if name.is_empty()));
+        assert!(
+            matches!(parse("screencast>>1,0"), HyprlandEvent::Screencast(s) if s.state && s.owner == ScreencastOwner::Monitor)
+        );
+        assert!(
+            matches!(parse("screencast>>0,1"), HyprlandEvent::Screencast(s) if !s.state && s.owner == ScreencastOwner::Window)
+        );
+        assert!(matches!(
+            parse("configreloaded>>"),
+            HyprlandEvent::ConfigReloaded()
+        ));
+        assert!(matches!(
+            parse("configreloaded"),
+            HyprlandEvent::ConfigReloaded()
+        ));
+        assert!(
+            matches!(parse("custom>>reload-theme,dark"), HyprlandEvent::Custom(data) if data == "reload-theme,dark")
+        );
+    }
+
+    #[test]
+    fn invalid_events() {
+        for message in [
+            "",
+            "notanevent>>data",
+            "workspacev2>>3",
+            "focusedmonv2>>DP-1,web",
+            "activespecialv2>>special,DP-1",
+            "movewindowv2>>55d0c0a1b2c0,web,web",
+            "openwindow>>55d0c0a1b2c0,web,firefox",
+            "screencast>>1,2",
+            "pin>>55d0c0a1b2c0",
+        ] {
+            assert!(
+                HyprlandEvent::parse(message).is_none(),
+                "{} should not parse",
+                message
+            );
+        }
+    }
+}
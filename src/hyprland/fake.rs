@@ -0,0 +1,436 @@
+// Stand-in for a running Hyprland so the bar can be exercised without the compositor. The fake instance is created
+// under $XDG_RUNTIME_DIR/hypr and serves either a directory of fixtures or a recording made with --record.
+//
+// A fixtures directory holds:
+//   <name>.json    returned for the j/<name> request, e.g. clients.json, workspaces.json and monitors.json. The file
+//                  is read on every request so it can be edited while the bar is running.
+//   events         script played to every event socket connection. Each line is sent as an event, lines starting
+//                  with # are skipped and "sleep <ms>" pauses the script.
+// fixtures/fake-hyprland is an example, run it with `twbar --fake-hyprland fixtures/fake-hyprland`.
+//
+// A recording is replayed with its original timing. The recorded events are played to every event socket connection
+// and requests are answered with the latest response recorded for them by that point of the replay.
+//
+// Dispatches, including the ones in a [[BATCH]], are logged and answered with "ok".
+//
+// The Hyprland models talk to the fake until it is dropped, which closes its connections and points them back at the
+// instance they used before.
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::time::{Duration, Instant};
+
+use async_channel::{Receiver, Sender};
+use async_std::io::{ReadExt, WriteExt};
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::sync::Arc;
+use async_std::task;
+use futures::StreamExt;
+use log::{error, info, warn};
+
+use super::error::HyprlandError;
+use super::recording::{self, RecordedEntry};
+use super::utils::Utils;
+
+enum Source {
+    Fixtures(PathBuf),
+    Recording(Vec<RecordedEntry>),
+}
+
+// Held by tests which run the Hyprland models against a fake, since the instance they talk to is global.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+pub struct FakeHyprland {
+    instance_dir: PathBuf,
+    previous_instance_dir: Option<PathBuf>,
+    // Never sent on, dropping it tells the fake's tasks to stop
+    _stop: Sender<()>,
+}
+
+impl FakeHyprland {
+    pub async fn start(fixtures_dir: &Path) -> Result<Self, HyprlandError> {
+        Self::start_in(fixtures_dir, &Self::runtime_dir()?).await
+    }
+
+    // Like start but creates the instance under the given directory instead of $XDG_RUNTIME_DIR.
+    pub async fn start_in(fixtures_dir: &Path, runtime_dir: &Path) -> Result<Self, HyprlandError> {
+        info!("Serving fake Hyprland from {}", fixtures_dir.display());
+        Self::serve(Source::Fixtures(fixtures_dir.to_owned()), runtime_dir).await
+    }
+
+    pub async fn replay(recording: &Path) -> Result<Self, HyprlandError> {
+        let entries = recording::load(recording)?;
+        info!("Replaying {} in a fake Hyprland", recording.display());
+        Self::serve(Source::Recording(entries), &Self::runtime_dir()?).await
+    }
+
+    fn runtime_dir() -> Result<PathBuf, HyprlandError> {
+        std::env::var("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .map_err(|_| HyprlandError::MissingRuntimeDir)
+    }
+
+    async fn serve(source: Source, runtime_dir: &Path) -> Result<Self, HyprlandError> {
+        let signature = format!("twbar-fake-{}", std::process::id());
+        let instance_dir = runtime_dir.join("hypr").join(&signature);
+        // Left behind if a previous run with the same pid was killed.
+        let _ = std::fs::remove_dir_all(&instance_dir);
+        std::fs::create_dir_all(&instance_dir)?;
+
+        let commands = UnixListener::bind(instance_dir.join(".socket.sock")).await?;
+        let events = UnixListener::bind(instance_dir.join(".socket2.sock")).await?;
+        let source = Arc::new(source);
+        let started = Instant::now();
+        let (stop, stopped) = async_channel::bounded(1);
+
+        {
+            let source = source.clone();
+            let stopped = stopped.clone();
+            task::spawn(async move {
+                let mut incoming = pin!(commands.incoming().take_until(stopped.recv()));
+                while let Some(stream) = incoming.next().await {
+                    match stream {
+                        Ok(stream) => {
+                            task::spawn(Self::answer_command(stream, source.clone(), started));
+                        }
+                        Err(err) => error!(
+                            "Fake Hyprland failed to accept a command connection: {}",
+                            err
+                        ),
+                    }
+                }
+            });
+        }
+
+        task::spawn(async move {
+            let mut incoming = pin!(events.incoming().take_until(stopped.recv()));
+            while let Some(stream) = incoming.next().await {
+                match stream {
+                    Ok(stream) => {
+                        task::spawn(Self::play_events(stream, source.clone(), stopped.clone()));
+                    }
+                    Err(err) => error!(
+                        "Fake Hyprland failed to accept an event connection: {}",
+                        err
+                    ),
+                }
+            }
+        });
+
+        info!("Fake Hyprland is at {}", instance_dir.display());
+        let previous_instance_dir = Utils::use_instance_dir(&instance_dir);
+        Ok(Self {
+            instance_dir,
+            previous_instance_dir,
+            _stop: stop,
+        })
+    }
+
+    async fn answer_command(mut stream: UnixStream, source: Arc<Source>, started: Instant) {
+        // Commands are written in one go and the client waits for the reply without closing its end.
+        let mut buf = vec![0; 8192];
+        let bytes_read = match stream.read(&mut buf).await {
+            Ok(bytes_read) => bytes_read,
+            Err(err) => {
+                warn!("Fake Hyprland failed to read a command: {}", err);
+                return;
+            }
+        };
+        let command = String::from_utf8_lossy(&buf[..bytes_read]);
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+
+        let response = match command.strip_prefix("[[BATCH]]") {
+            Some(batch) => batch
+                .split(';')
+                .map(|command| Self::respond(command.trim(), &source, elapsed_ms))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            None => Self::respond(&command, &source, elapsed_ms),
+        };
+
+        // Dropping the stream afterwards tells the client the response is complete.
+        if let Err(err) = stream.write_all(response.as_bytes()).await {
+            warn!("Fake Hyprland failed to answer {}: {}", command, err);
+        }
+    }
+
+    // elapsed_ms is how long the fake has been running, a recording answers with the response recorded by then.
+    fn respond(command: &str, source: &Source, elapsed_ms: u64) -> String {
+        if let Some(dispatcher) = command.strip_prefix("dispatch ") {
+            info!("Fake Hyprland dispatch: {}", dispatcher);
+            return "ok".to_owned();
+        }
+
+        match source {
+            Source::Fixtures(fixtures_dir) => match command.strip_prefix("j/") {
+                Some(name) => {
+                    let path = fixtures_dir.join(format!("{}.json", name));
+                    std::fs::read_to_string(&path).unwrap_or_else(|err| {
+                        warn!("Fake Hyprland has no fixture {}: {}", path.display(), err);
+                        "[]".to_owned()
+                    })
+                }
+                None => {
+                    warn!("Fake Hyprland got an unknown request: {}", command);
+                    "unknown request".to_owned()
+                }
+            },
+            Source::Recording(entries) => {
+                let mut responses = entries.iter().filter_map(|entry| match entry {
+                    RecordedEntry::Command {
+                        at_ms,
+                        command: recorded_command,
+                        response,
+                    } if recorded_command == command => Some((*at_ms, response)),
+                    _ => None,
+                });
+                // The bar may ask a little earlier than it did while recording, use the first response then.
+                let first = responses.next();
+                let response = responses
+                    .take_while(|(at_ms, _)| *at_ms <= elapsed_ms)
+                    .last()
+                    .or(first);
+                match response {
+                    Some((_, response)) => response.clone(),
+                    None => {
+                        warn!("Fake Hyprland has no recorded response to {}", command);
+                        "unknown request".to_owned()
+                    }
+                }
+            }
+        }
+    }
+
+    // Lines to send on the event socket, each with how long to wait before sending it.
+    fn events(source: &Source) -> Vec<(Duration, String)> {
+        let mut events = Vec::new();
+        match source {
+            Source::Fixtures(fixtures_dir) => {
+                let script = fixtures_dir.join("events");
+                let script = match std::fs::read_to_string(&script) {
+                    Ok(script) => script,
+                    Err(err) => {
+                        warn!(
+                            "Fake Hyprland has no event script {}: {}",
+                            script.display(),
+                            err
+                        );
+                        String::new()
+                    }
+                };
+
+                let mut delay = Duration::ZERO;
+                for line in script.lines() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    if let Some(sleep) = line.strip_prefix("sleep ") {
+                        match sleep.trim().parse() {
+                            Ok(sleep) => delay += Duration::from_millis(sleep),
+                            Err(_) => warn!("Fake Hyprland got an invalid sleep: {}", line),
+                        }
+                        continue;
+                    }
+                    events.push((delay, line.to_owned()));
+                    delay = Duration::ZERO;
+                }
+            }
+            Source::Recording(entries) => {
+                let mut last_at_ms = 0;
+                for entry in entries {
+                    if let RecordedEntry::Event { at_ms, line } = entry {
+                        let delay = Duration::from_millis(at_ms.saturating_sub(last_at_ms));
+                        events.push((delay, line.clone()));
+                        last_at_ms = *at_ms;
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    async fn play_events(mut stream: UnixStream, source: Arc<Source>, stopped: Receiver<()>) {
+        for (delay, line) in Self::events(&source) {
+            task::sleep(delay).await;
+            if stopped.is_closed() {
+                return;
+            }
+            if stream
+                .write_all(format!("{}\n", line).as_bytes())
+                .await
+                .is_err()
+            {
+                // The bar disconnected.
+                return;
+            }
+        }
+
+        // Keep the connection open like Hyprland does until the fake is dropped, closing it would look like Hyprland
+        // exiting.
+        let _ = stopped.recv().await;
+    }
+}
+
+impl Drop for FakeHyprland {
+    fn drop(&mut self) {
+        Utils::restore_instance_dir(self.previous_instance_dir.take());
+        let _ = std::fs::remove_dir_all(&self.instance_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::PoisonError;
+
+    use async_std::future::timeout;
+
+    use super::*;
+    use crate::hyprland::events::{HyprlandEvent, HyprlandEvents};
+    use crate::hyprland::monitors::HyprlandMonitors;
+    use crate::hyprland::windows::{self, HyprlandWindow, HyprlandWindows};
+    use crate::hyprland::workspaces::HyprlandWorkspaces;
+
+    // Longer than the whole event script takes to play
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn focus_order(windows: &[HyprlandWindow]) -> Vec<&str> {
+        windows::focus_order(windows, |w| w.class.as_str())
+    }
+
+    fn recording() -> Source {
+        let command = |at_ms, command: &str, response: &str| RecordedEntry::Command {
+            at_ms,
+            command: command.to_owned(),
+            response: response.to_owned(),
+        };
+        Source::Recording(vec![
+            command(100, "j/clients", "first"),
+            RecordedEntry::Event {
+                at_ms: 200,
+                line: "workspacev2>>2,2".to_owned(),
+            },
+            command(300, "j/clients", "second"),
+            command(300, "j/monitors", "monitors"),
+            command(500, "j/clients", "third"),
+        ])
+    }
+
+    #[test]
+    fn recordings_answer_with_the_latest_response() {
+        let source = recording();
+        let responses: Vec<_> = [0, 100, 299, 300, 499, 500, 10_000]
+            .into_iter()
+            .map(|elapsed_ms| FakeHyprland::respond("j/clients", &source, elapsed_ms))
+            .collect();
+        // Asking before the first response was recorded still gets it.
+        assert_eq!(
+            responses,
+            [
+                "first", "first", "first", "second", "second", "third", "third"
+            ]
+        );
+        assert_eq!(FakeHyprland::respond("j/monitors", &source, 0), "monitors");
+        assert_eq!(
+            FakeHyprland::respond("j/workspaces", &source, 1000),
+            "unknown request"
+        );
+        assert_eq!(
+            FakeHyprland::respond("dispatch workspace 2", &source, 1000),
+            "ok"
+        );
+    }
+
+    #[test]
+    fn recordings_replay_their_events_in_time() {
+        let events = FakeHyprland::events(&recording());
+        assert_eq!(
+            events,
+            [(Duration::from_millis(200), "workspacev2>>2,2".to_owned())]
+        );
+    }
+
+    // The models are singletons talking to whichever instance Utils points at, so they are all checked against a
+    // single fake in one test.
+    #[async_std::test]
+    async fn models_follow_fake_hyprland() {
+        let _lock = TEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let runtime_dir = std::env::temp_dir().join(format!("twbar-test-{}", std::process::id()));
+        let fixtures_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/fake-hyprland");
+        let fake = FakeHyprland::start_in(&fixtures_dir, &runtime_dir)
+            .await
+            .unwrap();
+
+        let hyprland_events = HyprlandEvents::instance().await;
+        let mut events = hyprland_events.get_event_stream().await;
+        let hyprland_windows = HyprlandWindows::instance().await;
+        let mut windows = hyprland_windows.get_windows_update_emitter();
+        let hyprland_workspaces = HyprlandWorkspaces::instance().await;
+        let mut workspaces = hyprland_workspaces.get_workspaces_state_emitter();
+        let mut active_workspace = hyprland_workspaces.get_active_workspace_id_state();
+        let hyprland_monitors = HyprlandMonitors::instance().await;
+        let mut monitors = hyprland_monitors.get_monitor_state_emitter();
+
+        let initial_windows = windows.wait_for(|windows| !windows.is_empty()).await;
+        assert_eq!(focus_order(&initial_windows), ["code", "kitty", "firefox"]);
+        assert!(initial_windows.iter().all(|w| w.address.starts_with("0x")));
+
+        let workspaces = workspaces
+            .wait_for(|workspaces| !workspaces.is_empty())
+            .await;
+        let workspace_ids: Vec<_> = workspaces.iter().map(|w| w.id).collect();
+        assert_eq!(workspace_ids, [1, 2, 4]);
+
+        let monitors = monitors.wait_for(|monitors| !monitors.is_empty()).await;
+        let focused: Vec<_> = monitors
+            .iter()
+            .map(|m| (m.name.as_str(), m.focused, m.active_workspace.id))
+            .collect();
+        assert_eq!(focused, [("DP-1", true, 1), ("HDMI-A-1", false, 4)]);
+
+        let mut received = Vec::new();
+        timeout(TIMEOUT, async {
+            loop {
+                let event = events.recv().await.unwrap();
+                let last = matches!(&event, HyprlandEvent::ActiveWindowV2(address) if address == "0x55d0c0a1b2c0");
+                received.push(event);
+                if last {
+                    return;
+                }
+            }
+        })
+        .await
+        .expect("Timed out waiting for the fake Hyprland events");
+        assert_eq!(received.len(), 8, "{:?}", received);
+        assert!(
+            matches!(&received[0], HyprlandEvent::WorkspaceV2(w) if w.id == 2 && w.name == "2")
+        );
+        assert!(
+            matches!(&received[1], HyprlandEvent::FocusedMon(m) if m.monitor_name == "DP-1" && m.workspace_name == "2")
+        );
+        assert!(
+            matches!(&received[2], HyprlandEvent::ActiveWindowV2(address) if address == "0x55d0c0a1b3d0")
+        );
+        assert!(
+            matches!(&received[3], HyprlandEvent::WindowTitleV2(t) if t.address == "0x55d0c0a1b3d0" && t.title == "GitHub - Mozilla Firefox")
+        );
+        assert!(matches!(&received[4], HyprlandEvent::Submap(submap) if submap == "resize"));
+        assert!(matches!(&received[5], HyprlandEvent::Submap(submap) if submap.is_empty()));
+        assert!(matches!(&received[6], HyprlandEvent::WorkspaceV2(w) if w.id == 1));
+
+        // The windows are updated from the events without asking Hyprland again.
+        let final_windows = windows
+            .wait_for(|windows| {
+                windows
+                    .iter()
+                    .any(|w| w.title == "GitHub - Mozilla Firefox")
+                    && focus_order(windows) == ["code", "firefox", "kitty"]
+            })
+            .await;
+        assert_eq!(final_windows.len(), 3);
+        assert_eq!(active_workspace.wait_for(|id| *id == 1).await, 1);
+
+        drop(fake);
+        let _ = std::fs::remove_dir_all(&runtime_dir);
+    }
+}
@@ -1,7 +1,11 @@
 pub mod events;
 pub mod commands;
+pub mod compositor;
+pub mod error;
+pub mod fake;
 pub mod monitors;
+pub mod recording;
+pub mod refresh;
 pub mod windows;
 pub mod workspaces;
-mod utils;
-mod wayland_manager;
\ No newline at end of file
+mod utils;
\ No newline at end of file
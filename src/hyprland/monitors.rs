@@ -1,3 +1,4 @@
+use async_broadcast::RecvError;
 use async_std::{
     sync::{Arc, Mutex, Weak},
     task,
@@ -7,8 +8,11 @@ use serde::Deserialize;
 
 use super::{
     commands::HyprlandCommands,
-    events::{HyprlandEvent, HyprlandEvents, LatestEventValue, LatestEventValueListener},
+    events::{HyprlandEvent, HyprlandEvents},
+    refresh::RefreshScheduler,
 };
+use crate::compositor::ConnectionState;
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
 
 #[allow(dead_code)]
 #[derive(Clone, Default, Deserialize)]
@@ -82,15 +86,47 @@ impl HyprlandMonitors {
             let instance = instance.clone();
             task::spawn(async move {
                 let events = HyprlandEvents::instance().await;
+                let mut connection_state = events.get_connection_state_emitter();
                 let mut events = events.get_event_stream().await;
 
                 instance.force_refresh().await;
 
+                let refresh = {
+                    let instance = instance.clone();
+                    RefreshScheduler::new(move || {
+                        let instance = instance.clone();
+                        async move { instance.force_refresh().await }
+                    })
+                };
+
+                {
+                    let refresh = refresh.clone();
+                    task::spawn(async move {
+                        loop {
+                            // Catch up on anything that changed while Hyprland was away.
+                            if connection_state.next().await == ConnectionState::Connected {
+                                refresh.schedule();
+                            }
+                        }
+                    });
+                }
+
                 loop {
-                    let event = events.recv().await.unwrap();
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(RecvError::Overflowed(_)) => {
+                            refresh.schedule();
+                            continue;
+                        }
+                        Err(RecvError::Closed) => return,
+                    };
                     match event {
-                        HyprlandEvent::MonitorAdded(_) => instance.force_refresh().await,
-                        HyprlandEvent::MonitorRemoved(_) => instance.force_refresh().await,
+                        HyprlandEvent::MonitorAdded(_) => refresh.schedule(),
+                        HyprlandEvent::MonitorRemoved(_) => refresh.schedule(),
+                        // Keeps each monitor's active workspace up to date.
+                        HyprlandEvent::WorkspaceV2(_)
+                        | HyprlandEvent::FocusedMon(_)
+                        | HyprlandEvent::MoveWorkspaceV2(_) => refresh.schedule(),
                         _ => {}
                     }
                 }
@@ -102,26 +138,18 @@ impl HyprlandMonitors {
 
     pub async fn force_refresh(&self) {
         trace!("In monitors::force_refresh");
-        self.monitors
-            .update_fn(|_| {
-                task::block_on(async {
-                    trace!("In monitors::force_refresh - sending monitors command");
-                    let monitors = HyprlandCommands::send_command("j/monitors").await;
-                    let deserialized = serde_json::from_str::<Vec<HyprlandMonitor>>(&monitors);
-                    if deserialized.is_err() {
-                        error!(
-                            "Failed to deserialize: {}, {}",
-                            monitors,
-                            deserialized.err().unwrap()
-                        );
-                        return None;
-                    }
-                    trace!("In monitors::force_refresh - finished");
-
-                    Some(deserialized.unwrap())
-                })
-            })
-            .await;
+        let monitors = match HyprlandCommands::send_command("j/monitors").await {
+            Ok(monitors) => monitors,
+            Err(err) => {
+                error!("Failed to get monitors: {}", err);
+                return;
+            }
+        };
+        match serde_json::from_str::<Vec<HyprlandMonitor>>(&monitors) {
+            Ok(monitors) => self.monitors.update(monitors).await,
+            Err(err) => error!("Failed to deserialize: {}, {}", monitors, err),
+        }
+        trace!("In monitors::force_refresh - finished");
     }
 
     pub fn get_monitor_state_emitter(&self) -> LatestEventValueListener<Vec<HyprlandMonitor>> {
@@ -0,0 +1,150 @@
+// Records what Hyprland tells the bar so a session can be replayed later with FakeHyprland::replay. The recording
+// is a JSON object per line holding either an event socket line or a command with its response, each with the
+// milliseconds since recording started.
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+
+use super::error::HyprlandError;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedEntry {
+    Event {
+        at_ms: u64,
+        line: String,
+    },
+    Command {
+        at_ms: u64,
+        command: String,
+        response: String,
+    },
+}
+
+struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+// Records everything sent by Hyprland to the file from now on, replacing any previous recording in it.
+pub fn start(path: &Path) -> Result<(), HyprlandError> {
+    let file = File::create(path)?;
+    info!("Recording Hyprland to {}", path.display());
+    *RECORDER.lock().unwrap() = Some(Recorder {
+        file,
+        started: Instant::now(),
+    });
+    Ok(())
+}
+
+pub(super) fn record_event(line: &str) {
+    record(|at_ms| RecordedEntry::Event {
+        at_ms,
+        line: line.to_owned(),
+    });
+}
+
+pub(super) fn record_command(command: &str, response: &str) {
+    record(|at_ms| RecordedEntry::Command {
+        at_ms,
+        command: command.to_owned(),
+        response: response.to_owned(),
+    });
+}
+
+fn record(entry: impl FnOnce(u64) -> RecordedEntry) {
+    let mut recorder = RECORDER.lock().unwrap();
+    let Some(recorder) = recorder.as_mut() else {
+        return;
+    };
+
+    let entry = entry(recorder.started.elapsed().as_millis() as u64);
+    // Written a line at a time so the recording survives the bar crashing.
+    let line = serde_json::to_string(&entry).unwrap() + "\n";
+    if let Err(err) = recorder.file.write_all(line.as_bytes()) {
+        error!("Failed to record {:?}: {}", entry, err);
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<RecordedEntry>, HyprlandError> {
+    let file = File::open(path)?;
+    BufReader::new(file)
+        .lines()
+        .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|line| {
+            serde_json::from_str(&line?)
+                .map_err(|err| HyprlandError::InvalidRecording(err.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn write_recording(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "twbar-recording-{}-{}.jsonl",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_reads_back_recorded_entries() {
+        let entries = [
+            RecordedEntry::Command {
+                at_ms: 0,
+                command: "j/clients".to_owned(),
+                response: "[\n]".to_owned(),
+            },
+            RecordedEntry::Event {
+                at_ms: 1200,
+                line: "workspacev2>>2,2".to_owned(),
+            },
+        ];
+        let lines: Vec<_> = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        // Blank lines, e.g. a trailing newline or one added while editing the file, are skipped.
+        let path = write_recording("valid", &format!("{}\n\n{}\n  \n", lines[0], lines[1]));
+
+        let loaded = load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, entries);
+    }
+
+    #[test]
+    fn load_reports_invalid_lines() {
+        let path = write_recording(
+            "invalid",
+            "{\"event\":{\"at_ms\":0,\"line\":\"submap>>\"}}\nnot json\n",
+        );
+
+        let err = load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            matches!(err, HyprlandError::InvalidRecording(_)),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn load_reports_missing_files() {
+        let err = load(Path::new("/nonexistent/twbar-recording.jsonl")).unwrap_err();
+        assert!(matches!(err, HyprlandError::Io(_)), "{:?}", err);
+    }
+}
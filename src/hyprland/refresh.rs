@@ -0,0 +1,132 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_std::channel::{Sender, TryRecvError, bounded};
+use async_std::task;
+
+// Longest a refresh is held back for other requests to join it, in milliseconds. Set from the config.
+static REFRESH_DELAY_MS: AtomicU64 = AtomicU64::new(50);
+
+pub fn set_refresh_delay(delay: Duration) {
+    REFRESH_DELAY_MS.store(delay.as_millis() as u64, Ordering::Relaxed);
+}
+
+fn refresh_delay() -> Duration {
+    Duration::from_millis(REFRESH_DELAY_MS.load(Ordering::Relaxed))
+}
+
+// Collapses bursts of refresh requests, e.g. the events sent when a monitor is plugged in, into a single refresh.
+// The refresh runs once the delay has passed since the first request and serves every request made until then.
+// Requests made while it is running queue one more refresh since the query may have missed their change.
+#[derive(Clone)]
+pub struct RefreshScheduler {
+    sender: Sender<()>,
+}
+
+impl RefreshScheduler {
+    // Holds refreshes back for the delay set with set_refresh_delay at the time of the first request.
+    pub fn new<F, Fut>(refresh: F) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        Self::spawn(refresh_delay, refresh)
+    }
+
+    pub fn with_delay<F, Fut>(delay: Duration, refresh: F) -> Self
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        Self::spawn(move || delay, refresh)
+    }
+
+    fn spawn<D, F, Fut>(delay: D, refresh: F) -> Self
+    where
+        D: Fn() -> Duration + Send + 'static,
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        // A single slot is enough, a request which finds it full is already served by the pending refresh.
+        let (sender, receiver) = bounded(1);
+        task::spawn(async move {
+            // Ends once every clone of the scheduler is dropped.
+            while receiver.recv().await.is_ok() {
+                task::sleep(delay()).await;
+                if receiver.try_recv() == Err(TryRecvError::Closed) {
+                    return;
+                }
+                refresh().await;
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub fn schedule(&self) {
+        let _ = self.sender.try_send(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    const DELAY: Duration = Duration::from_millis(50);
+
+    fn counting_scheduler(refresh_time: Duration) -> (RefreshScheduler, Arc<AtomicUsize>) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let scheduler = {
+            let runs = runs.clone();
+            RefreshScheduler::with_delay(DELAY, move || {
+                let runs = runs.clone();
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    task::sleep(refresh_time).await;
+                }
+            })
+        };
+        (scheduler, runs)
+    }
+
+    #[async_std::test]
+    async fn burst_runs_refresh_once() {
+        let (scheduler, runs) = counting_scheduler(Duration::ZERO);
+        for _ in 0..10 {
+            scheduler.schedule();
+        }
+        task::sleep(DELAY / 2).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        task::sleep(DELAY * 3).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[async_std::test]
+    async fn request_while_running_refreshes_again() {
+        let (scheduler, runs) = counting_scheduler(DELAY * 2);
+        scheduler.schedule();
+        task::sleep(DELAY * 2).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        // The refresh is still running so these are gathered into a single extra refresh.
+        for _ in 0..10 {
+            scheduler.schedule();
+        }
+        task::sleep(DELAY * 6).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[async_std::test]
+    async fn stops_when_dropped() {
+        let (scheduler, runs) = counting_scheduler(Duration::ZERO);
+        scheduler.schedule();
+        drop(scheduler);
+        task::sleep(DELAY * 3).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+    }
+}
@@ -1,47 +1,110 @@
-use async_std::{io, os::unix::net::UnixStream, path::PathBuf};
+use async_std::os::unix::net::UnixStream;
+use log::{info, warn};
+use std::cmp::Reverse;
 use std::env::var;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
 
-pub(super) struct Utils {
-}
+use super::error::HyprlandError;
+
+// Signature of the Hyprland instance we talk to. This starts out as HYPRLAND_INSTANCE_SIGNATURE but is replaced
+// when Hyprland restarts under a new signature.
+static INSTANCE_SIGNATURE: LazyLock<Mutex<Option<String>>> =
+    LazyLock::new(|| Mutex::new(var("HYPRLAND_INSTANCE_SIGNATURE").ok()));
+// Directory of an instance chosen with use_instance_dir, e.g. a FakeHyprland. Takes precedence over the signature
+// and is kept when connecting fails.
+static INSTANCE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+pub(super) struct Utils {}
 
 impl Utils {
-    pub async fn create_event_socket() -> Result<UnixStream, io::Error> {
-        let path =Self::get_hyprland_instance_path()
-            .join(".socket2.sock");
-        if !path.exists().await {
-            panic!("Could not find Hyprland socket path");
+    pub async fn create_event_socket() -> Result<UnixStream, HyprlandError> {
+        Self::connect(".socket2.sock").await
+    }
+
+    pub async fn create_dispatch_socket() -> Result<UnixStream, HyprlandError> {
+        Self::connect(".socket.sock").await
+    }
+
+    async fn connect(socket_name: &str) -> Result<UnixStream, HyprlandError> {
+        let path = Self::get_hyprland_instance_path()?.join(socket_name);
+        if !path.exists() {
+            Self::forget_instance();
+            return Err(HyprlandError::SocketNotFound(path));
         }
 
-        UnixStream::connect(path).await
+        UnixStream::connect(&path).await.map_err(|err| {
+            // The socket is left behind when Hyprland crashes, look for a new instance next time.
+            Self::forget_instance();
+            err.into()
+        })
     }
 
-    pub async fn create_dispatch_socket() -> Result<UnixStream, io::Error> {
-        let path = Self::get_hyprland_instance_path()
-            .join(".socket.sock");
-        if !path.exists().await {
-            panic!("Could not find Hyprland socket path");
+    // Talks to the instance in the given directory from now on, regardless of HYPRLAND_INSTANCE_SIGNATURE. Returns the
+    // directory used before, if any, for restore_instance_dir.
+    pub fn use_instance_dir(instance_dir: &Path) -> Option<PathBuf> {
+        INSTANCE_DIR
+            .lock()
+            .unwrap()
+            .replace(instance_dir.to_owned())
+    }
+
+    pub fn restore_instance_dir(previous: Option<PathBuf>) {
+        *INSTANCE_DIR.lock().unwrap() = previous;
+    }
+
+    fn forget_instance() {
+        INSTANCE_SIGNATURE.lock().unwrap().take();
+    }
+
+    fn get_hyprland_instance_path() -> Result<PathBuf, HyprlandError> {
+        if let Some(instance_dir) = INSTANCE_DIR.lock().unwrap().as_ref() {
+            return Ok(instance_dir.clone());
         }
 
-        UnixStream::connect(path).await
+        let hypr_dir =
+            PathBuf::from(var("XDG_RUNTIME_DIR").map_err(|_| HyprlandError::MissingRuntimeDir)?)
+                .join("hypr");
+
+        let mut signature = INSTANCE_SIGNATURE.lock().unwrap();
+        if let Some(signature) = signature.as_ref() {
+            return Ok(hypr_dir.join(signature));
+        }
+
+        let new_signature =
+            Self::find_running_instance(&hypr_dir).ok_or(HyprlandError::NotRunning)?;
+        info!("Using Hyprland instance {}", new_signature);
+        let path = hypr_dir.join(&new_signature);
+        *signature = Some(new_signature);
+        Ok(path)
     }
 
-    fn get_hyprland_instance_path() -> PathBuf {
-        let instance = match var("HYPRLAND_INSTANCE_SIGNATURE") {
-            Ok(var) => var,
-            Err(_) => {
-                panic!("Could not find HYPRLAND_INSTANCE_SIGNATURE variable, is Hyprland running?")
+    // Returns the signature of the newest Hyprland instance which accepts connections.
+    fn find_running_instance(hypr_dir: &Path) -> Option<String> {
+        let entries = match std::fs::read_dir(hypr_dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!(
+                    "Failed to look for Hyprland in {}: {}",
+                    hypr_dir.display(),
+                    err
+                );
+                return None;
             }
         };
 
-        let xdg_runtime_dir = match var("XDG_RUNTIME_DIR") {
-            Ok(var) => var,
-            Err(_) => {
-                panic!("Could not find XDG_RUNTIME_DIR variable")
-            }
-        };
+        let mut instances: Vec<_> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                Some((modified, entry.path()))
+            })
+            .collect();
+        instances.sort_by_key(|(modified, _)| Reverse(*modified));
 
-        PathBuf::from(xdg_runtime_dir)
-            .join("hypr")
-            .join(instance)
+        instances.into_iter().find_map(|(_, path)| {
+            std::os::unix::net::UnixStream::connect(path.join(".socket.sock")).ok()?;
+            Some(path.file_name()?.to_str()?.to_owned())
+        })
     }
-}
\ No newline at end of file
+}
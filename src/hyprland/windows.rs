@@ -1,19 +1,24 @@
+use async_broadcast::RecvError;
 use async_std::{
     sync::{Arc, Mutex, Weak},
     task,
 };
 use gtk4::glib;
-use log::error;
+use log::{error, trace};
 use serde::{Deserialize, Deserializer};
 use serde_json::Value;
+use std::time::Duration;
 
 use super::{
-    commands::HyprlandCommands,
-    events::{
-        EventData, HyprlandEvent, HyprlandEvents, LatestEventValue, LatestEventValueListener,
-    },
-    wayland_manager::ExtForeignToplevel,
+    commands::{Dispatch, HyprlandCommands},
+    error::HyprlandError,
+    events::{EventData, HyprlandEvent, HyprlandEvents},
+    refresh::RefreshScheduler,
+    workspaces::{HyprlandWorkspace, HyprlandWorkspaces},
 };
+use crate::compositor::ConnectionState;
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
+use crate::wayland::manager::ExtForeignToplevel;
 
 #[derive(Deserialize, Clone, Default, Debug, PartialEq, Eq)]
 pub struct HyprlandPartialWorkspace {
@@ -51,6 +56,9 @@ pub struct HyprlandWindow {
     pub swallowing: String,
     #[serde(rename = "focusHistoryID")]
     pub focus_history_id: i32,
+    // Set by the urgent event until the window is focused, j/clients doesn't report it
+    #[serde(skip)]
+    pub urgent: bool,
 }
 
 fn deserialize_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
@@ -93,7 +101,21 @@ impl HyprlandWindow {
                 open_window.title.clone_into(&mut self.initial_title);
                 open_window.title.clone_into(&mut self.title);
 
-                // FIXME: Set workspace based on the workspace name.
+                open_window
+                    .workspace_name
+                    .clone_into(&mut self.workspace.name);
+            }
+            HyprlandEvent::ChangeFloatingMode(floating_mode) => {
+                self.floating = floating_mode.is_floating;
+            }
+            HyprlandEvent::Pin(pin) => {
+                self.pinned = pin.pinned;
+            }
+            HyprlandEvent::WindowTitleV2(window_title) => {
+                self.title.clone_from(&window_title.title);
+            }
+            HyprlandEvent::Urgent(_) => {
+                self.urgent = true;
             }
             _ => {
                 panic!("Unexpected message type: {:?}", event);
@@ -101,8 +123,8 @@ impl HyprlandWindow {
         }
     }
 
-    pub async fn activate(&self) {
-        HyprlandCommands::set_active_window(&self.address).await;
+    pub async fn activate(&self) -> Result<(), HyprlandError> {
+        HyprlandCommands::dispatch(&Dispatch::FocusWindow(self.address.clone())).await
     }
 }
 
@@ -139,8 +161,16 @@ impl EventData for Vec<HyprlandWindow> {
     }
 }
 
+// How often the windows are fetched again in case an event was missed or applied wrong
+const RESYNC_INTERVAL: Duration = Duration::from_secs(60);
+// Hyprland's ids for no workspace and no monitor, used until the workspace of a window is known
+const WORKSPACE_INVALID: i32 = -1;
+const MONITOR_INVALID: i32 = -1;
+
 pub struct HyprlandWindows {
     windows: Arc<LatestEventValue<Vec<HyprlandWindow>>>,
+    // Used to find the workspace ids and monitors of windows which are opened or moved
+    workspaces: Arc<HyprlandWorkspaces>,
 }
 
 impl HyprlandWindows {
@@ -161,25 +191,112 @@ impl HyprlandWindows {
     async fn new() -> Arc<Self> {
         let windows = Arc::new(LatestEventValue::new());
 
-        let instance = Arc::new(Self { windows });
+        let instance = Arc::new(Self {
+            windows,
+            workspaces: HyprlandWorkspaces::instance().await,
+        });
 
         {
             let instance = instance.clone();
             task::spawn(async move {
-                let mut events = HyprlandEvents::instance().await.get_event_stream().await;
+                let events = HyprlandEvents::instance().await;
+                let mut connection_state = events.get_connection_state_emitter();
+                let mut events = events.get_event_stream().await;
 
                 instance.force_refresh().await;
 
+                let refresh = {
+                    let instance = instance.clone();
+                    RefreshScheduler::new(move || {
+                        let instance = instance.clone();
+                        async move { instance.force_refresh().await }
+                    })
+                };
+
+                {
+                    let refresh = refresh.clone();
+                    task::spawn(async move {
+                        loop {
+                            task::sleep(RESYNC_INTERVAL).await;
+                            refresh.schedule();
+                        }
+                    });
+                }
+
+                {
+                    let instance = instance.clone();
+                    let mut workspaces_state = instance.workspaces.get_workspaces_state_emitter();
+                    task::spawn(async move {
+                        loop {
+                            let workspaces = workspaces_state.next().await;
+                            instance
+                                .windows
+                                .update_fn(|windows| {
+                                    let mut updated_windows = windows.clone();
+                                    Self::resolve_workspaces(&mut updated_windows, &workspaces)
+                                        .then_some(updated_windows)
+                                })
+                                .await;
+                        }
+                    });
+                }
+
+                {
+                    let refresh = refresh.clone();
+                    task::spawn(async move {
+                        loop {
+                            // Catch up on anything that changed while Hyprland was away.
+                            if connection_state.next().await == ConnectionState::Connected {
+                                refresh.schedule();
+                            }
+                        }
+                    });
+                }
+
                 loop {
-                    let event = events.recv().await.unwrap();
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(RecvError::Overflowed(_)) => {
+                            refresh.schedule();
+                            continue;
+                        }
+                        Err(RecvError::Closed) => return,
+                    };
                     match &event {
-                        HyprlandEvent::CloseWindow(_)
+                        HyprlandEvent::MonitorAddedV2(_) | HyprlandEvent::MonitorRemoved(_) => {
+                            refresh.schedule();
+                        }
+                        HyprlandEvent::OpenWindow(_)
+                        | HyprlandEvent::CloseWindow(_)
                         | HyprlandEvent::MoveWindowV2(_)
-                        | HyprlandEvent::OpenWindow(_)
-                        | HyprlandEvent::MonitorAddedV2(_)
-                        | HyprlandEvent::MonitorRemoved(_)
-                        | HyprlandEvent::ChangeFloatingMode(_) => {
-                            instance.force_refresh().await;
+                        | HyprlandEvent::ChangeFloatingMode(_)
+                        | HyprlandEvent::Pin(_)
+                        | HyprlandEvent::WindowTitleV2(_)
+                        | HyprlandEvent::Urgent(_)
+                        | HyprlandEvent::ActiveWindowV2(_)
+                        | HyprlandEvent::Fullscreen(_) => {
+                            let workspaces = instance.workspaces.get_workspaces().await;
+                            let mut in_sync = true;
+                            instance
+                                .windows
+                                .update_fn(|windows| {
+                                    let mut updated_windows = windows.clone();
+                                    in_sync = Self::apply_event(
+                                        &mut updated_windows,
+                                        &event,
+                                        &workspaces,
+                                    );
+                                    (in_sync && updated_windows != *windows)
+                                        .then_some(updated_windows)
+                                })
+                                .await;
+                            if !in_sync {
+                                trace!("Windows are out of sync after {:?}, refreshing", event);
+                                refresh.schedule();
+                            } else if let HyprlandEvent::OpenWindow(_) = event {
+                                // For the position of the new window.
+                                refresh.schedule();
+                            }
                         }
                         _ => {}
                     }
@@ -190,16 +307,435 @@ impl HyprlandWindows {
         instance
     }
 
+    // Applies the event to the windows instead of fetching them all again. Returns false when the event doesn't match
+    // the windows, in which case they need to be refreshed.
+    fn apply_event(
+        windows: &mut Vec<HyprlandWindow>,
+        event: &HyprlandEvent,
+        workspaces: &[HyprlandWorkspace],
+    ) -> bool {
+        let find = |windows: &[HyprlandWindow], address: &str| {
+            windows.iter().position(|w| w.address == address)
+        };
+
+        match event {
+            HyprlandEvent::OpenWindow(open_window) => {
+                if find(windows, &open_window.address).is_some() {
+                    return false;
+                }
+                let mut window = HyprlandWindow::from(event);
+                window.workspace.id = WORKSPACE_INVALID;
+                window.monitor = MONITOR_INVALID;
+                // A new workspace may not be in the workspaces yet since they are refreshed after a delay,
+                // resolve_workspaces fills it in once it is.
+                Self::resolve_workspaces(std::slice::from_mut(&mut window), workspaces);
+                // The event has no position, so the window goes after the others on its workspace until the refresh
+                // which follows it fetches the real one.
+                window.at = windows
+                    .iter()
+                    .filter(|w| w.workspace.id == window.workspace.id)
+                    .map(|w| (w.at.0 + 1, w.at.1))
+                    .max()
+                    .unwrap_or_default();
+                window.mapped = true;
+                // New windows go to the end of the focus history until they are focused.
+                window.focus_history_id = windows.len() as i32;
+                windows.push(window);
+            }
+            HyprlandEvent::CloseWindow(address) => {
+                let Some(index) = find(windows, address) else {
+                    return false;
+                };
+                let closed = windows.remove(index);
+                for window in windows.iter_mut() {
+                    if window.focus_history_id > closed.focus_history_id {
+                        window.focus_history_id -= 1;
+                    }
+                }
+            }
+            HyprlandEvent::MoveWindowV2(move_window) => {
+                let Some(index) = find(windows, &move_window.window_address) else {
+                    return false;
+                };
+                windows[index].update_from_event(event);
+                windows[index].monitor = MONITOR_INVALID;
+                Self::resolve_workspaces(&mut windows[index..=index], workspaces);
+            }
+            HyprlandEvent::ChangeFloatingMode(floating_mode) => {
+                let Some(index) = find(windows, &floating_mode.window_address) else {
+                    return false;
+                };
+                windows[index].update_from_event(event);
+            }
+            HyprlandEvent::Pin(pin) => {
+                let Some(index) = find(windows, &pin.window_address) else {
+                    return false;
+                };
+                windows[index].update_from_event(event);
+            }
+            HyprlandEvent::WindowTitleV2(window_title) => {
+                let Some(index) = find(windows, &window_title.address) else {
+                    return false;
+                };
+                windows[index].update_from_event(event);
+            }
+            HyprlandEvent::Urgent(address) => {
+                let Some(index) = find(windows, address) else {
+                    return false;
+                };
+                windows[index].update_from_event(event);
+            }
+            HyprlandEvent::ActiveWindowV2(address) => {
+                // Sent without an address when no window is focused.
+                if address == "0x" {
+                    return true;
+                }
+                let Some(index) = find(windows, address) else {
+                    return false;
+                };
+                let previous_id = windows[index].focus_history_id;
+                for window in windows.iter_mut() {
+                    if window.focus_history_id < previous_id {
+                        window.focus_history_id += 1;
+                    }
+                }
+                windows[index].focus_history_id = 0;
+                windows[index].urgent = false;
+            }
+            HyprlandEvent::Fullscreen(fullscreen) => {
+                // Fullscreen applies to the focused window.
+                let Some(window) = windows.iter_mut().find(|w| w.focus_history_id == 0) else {
+                    return false;
+                };
+                window.fullscreen = *fullscreen;
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    // Fills in the workspace id and monitor of windows whose workspace wasn't known when they were opened or moved,
+    // and moves windows to the monitor their workspace is on now. Returns whether any window changed.
+    fn resolve_workspaces(
+        windows: &mut [HyprlandWindow],
+        workspaces: &[HyprlandWorkspace],
+    ) -> bool {
+        let mut changed = false;
+        for window in windows.iter_mut() {
+            let workspace = workspaces.iter().find(|w| {
+                if window.workspace.id == WORKSPACE_INVALID {
+                    w.name == window.workspace.name
+                } else {
+                    w.id == window.workspace.id
+                }
+            });
+            if let Some(workspace) = workspace
+                && (window.workspace.id, window.monitor) != (workspace.id, workspace.monitor_id)
+            {
+                window.workspace.id = workspace.id;
+                window.monitor = workspace.monitor_id;
+                changed = true;
+            }
+        }
+        changed
+    }
+
     pub async fn force_refresh(&self) {
-        self.windows
-            .update(
-                Vec::<HyprlandWindow>::parse(&HyprlandCommands::send_command("j/clients").await)
-                    .unwrap(),
-            )
-            .await;
+        match HyprlandCommands::send_command("j/clients").await {
+            Ok(clients) => {
+                if let Some(mut windows) = Vec::<HyprlandWindow>::parse(&clients) {
+                    self.windows
+                        .update_fn(|old_windows| {
+                            // Urgency is only known from events so carry it over.
+                            for window in windows.iter_mut() {
+                                window.urgent = old_windows
+                                    .iter()
+                                    .any(|w| w.address == window.address && w.urgent);
+                            }
+                            Some(windows)
+                        })
+                        .await;
+                }
+            }
+            Err(err) => error!("Failed to get windows: {}", err),
+        }
     }
 
     pub fn get_windows_update_emitter(&self) -> LatestEventValueListener<Vec<HyprlandWindow>> {
         LatestEventValueListener::new(self.windows.clone())
     }
 }
+
+// Names of the windows, most recently focused first
+#[cfg(test)]
+pub(super) fn focus_order<'a>(
+    windows: &'a [HyprlandWindow],
+    name: impl Fn(&'a HyprlandWindow) -> &'a str,
+) -> Vec<&'a str> {
+    let mut windows: Vec<_> = windows.iter().collect();
+    windows.sort_by_key(|w| w.focus_history_id);
+    windows.into_iter().map(name).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(address: &str, workspace_id: i32, focus_history_id: i32) -> HyprlandWindow {
+        HyprlandWindow {
+            address: address.to_owned(),
+            mapped: true,
+            workspace: HyprlandPartialWorkspace {
+                id: workspace_id,
+                name: workspace_id.to_string(),
+            },
+            monitor: 0,
+            focus_history_id,
+            ..Default::default()
+        }
+    }
+
+    fn workspace(id: i32, name: &str, monitor_id: i32) -> HyprlandWorkspace {
+        HyprlandWorkspace {
+            id,
+            name: name.to_owned(),
+            monitor_id,
+            ..Default::default()
+        }
+    }
+
+    fn workspaces() -> Vec<HyprlandWorkspace> {
+        vec![workspace(1, "1", 0), workspace(2, "2", 1)]
+    }
+
+    // Windows 0xa, 0xb and 0xc on workspace 1, focused in that order
+    fn windows() -> Vec<HyprlandWindow> {
+        vec![
+            window("0xa", 1, 0),
+            window("0xb", 1, 1),
+            window("0xc", 1, 2),
+        ]
+    }
+
+    fn apply(windows: &mut Vec<HyprlandWindow>, message: &str) -> bool {
+        let event = HyprlandEvent::parse(message).unwrap();
+        HyprlandWindows::apply_event(windows, &event, &workspaces())
+    }
+
+    fn focus_order(windows: &[HyprlandWindow]) -> Vec<&str> {
+        super::focus_order(windows, |w| w.address.as_str())
+    }
+
+    #[test]
+    fn open_window() {
+        let mut windows = windows();
+        assert!(apply(&mut windows, "openwindow>>d,2,kitty,~, home"));
+        let opened = windows.last().unwrap();
+        assert_eq!(opened.address, "0xd");
+        assert_eq!(
+            (opened.workspace.id, opened.workspace.name.as_str()),
+            (2, "2")
+        );
+        assert_eq!(opened.monitor, 1);
+        assert_eq!(
+            (opened.class.as_str(), opened.title.as_str()),
+            ("kitty", "~, home")
+        );
+        assert_eq!(
+            (opened.initial_class.as_str(), opened.initial_title.as_str()),
+            ("kitty", "~, home")
+        );
+        assert!(opened.mapped);
+        // Opening doesn't focus the window, that comes as a separate event.
+        assert_eq!(focus_order(&windows), ["0xa", "0xb", "0xc", "0xd"]);
+    }
+
+    #[test]
+    fn open_window_on_unknown_workspace() {
+        let mut windows = windows();
+        assert!(apply(&mut windows, "openwindow>>d,web,firefox,Firefox"));
+        let opened = windows.last().unwrap();
+        assert_eq!(
+            (opened.workspace.id, opened.workspace.name.as_str()),
+            (WORKSPACE_INVALID, "web")
+        );
+        assert_eq!(opened.monitor, MONITOR_INVALID);
+
+        // Filled in once the workspaces are refreshed.
+        assert!(!HyprlandWindows::resolve_workspaces(
+            &mut windows,
+            &workspaces()
+        ));
+        let mut workspaces = workspaces();
+        workspaces.push(workspace(3, "web", 1));
+        assert!(HyprlandWindows::resolve_workspaces(
+            &mut windows,
+            &workspaces
+        ));
+        let opened = windows.last().unwrap();
+        assert_eq!((opened.workspace.id, opened.monitor), (3, 1));
+        assert!(!HyprlandWindows::resolve_workspaces(
+            &mut windows,
+            &workspaces
+        ));
+    }
+
+    #[test]
+    fn open_window_sorts_last_on_its_workspace() {
+        let mut windows = windows();
+        windows[0].at = (0, 0);
+        windows[1].at = (960, 0);
+        windows[2].at = (960, 540);
+        windows.push(HyprlandWindow {
+            at: (1920, 0),
+            ..window("0xe", 2, 3)
+        });
+        assert!(apply(&mut windows, "openwindow>>d,1,kitty,kitty"));
+        assert_eq!(windows.last().unwrap().at, (961, 540));
+
+        let mut order: Vec<_> = windows.iter().collect();
+        order.sort_by_key(|w| (w.workspace.id, w.at));
+        let order: Vec<_> = order.iter().map(|w| w.address.as_str()).collect();
+        assert_eq!(order, ["0xa", "0xb", "0xc", "0xd", "0xe"]);
+    }
+
+    #[test]
+    fn workspace_moved_to_another_monitor() {
+        let mut windows = windows();
+        windows[2] = window("0xc", 2, 2);
+        windows[2].monitor = 1;
+
+        // Workspace 1 moved from monitor 0 to 1.
+        let workspaces = vec![workspace(1, "1", 1), workspace(2, "2", 1)];
+        assert!(HyprlandWindows::resolve_workspaces(
+            &mut windows,
+            &workspaces
+        ));
+        let monitors: Vec<_> = windows.iter().map(|w| w.monitor).collect();
+        assert_eq!(monitors, [1, 1, 1]);
+        assert!(!HyprlandWindows::resolve_workspaces(
+            &mut windows,
+            &workspaces
+        ));
+    }
+
+    #[test]
+    fn close_window() {
+        let mut windows = windows();
+        assert!(apply(&mut windows, "closewindow>>a"));
+        assert_eq!(focus_order(&windows), ["0xb", "0xc"]);
+        assert_eq!(windows[0].focus_history_id, 0);
+        assert_eq!(windows[1].focus_history_id, 1);
+
+        assert!(apply(&mut windows, "closewindow>>c"));
+        assert_eq!(focus_order(&windows), ["0xb"]);
+    }
+
+    #[test]
+    fn move_window() {
+        let mut windows = windows();
+        assert!(apply(&mut windows, "movewindowv2>>b,2,2"));
+        assert_eq!((windows[1].workspace.id, windows[1].monitor), (2, 1));
+        assert_eq!(windows[1].workspace.name, "2");
+
+        // Moving to a workspace which isn't known yet leaves the monitor to be filled in later.
+        assert!(apply(&mut windows, "movewindowv2>>c,7,web"));
+        assert_eq!(
+            (windows[2].workspace.id, windows[2].monitor),
+            (7, MONITOR_INVALID)
+        );
+        let mut workspaces = workspaces();
+        workspaces.push(workspace(7, "web", 1));
+        assert!(HyprlandWindows::resolve_workspaces(
+            &mut windows,
+            &workspaces
+        ));
+        assert_eq!((windows[2].workspace.id, windows[2].monitor), (7, 1));
+    }
+
+    #[test]
+    fn focus_reorders_history() {
+        let mut windows = windows();
+        assert!(apply(&mut windows, "activewindowv2>>c"));
+        assert_eq!(focus_order(&windows), ["0xc", "0xa", "0xb"]);
+        assert!(apply(&mut windows, "activewindowv2>>a"));
+        assert_eq!(focus_order(&windows), ["0xa", "0xc", "0xb"]);
+        // Focusing the focused window changes nothing.
+        assert!(apply(&mut windows, "activewindowv2>>a"));
+        assert_eq!(focus_order(&windows), ["0xa", "0xc", "0xb"]);
+
+        // Sent when focusing an empty workspace.
+        let before = windows.clone();
+        assert!(apply(&mut windows, "activewindowv2>>"));
+        assert_eq!(windows, before);
+    }
+
+    #[test]
+    fn urgent_until_focused() {
+        let mut windows = windows();
+        assert!(apply(&mut windows, "urgent>>b"));
+        assert!(windows[1].urgent);
+        assert!(!windows[0].urgent && !windows[2].urgent);
+
+        assert!(apply(&mut windows, "activewindowv2>>b"));
+        assert!(!windows[1].urgent);
+    }
+
+    #[test]
+    fn window_properties() {
+        let mut windows = windows();
+        assert!(apply(&mut windows, "changefloatingmode>>b,1"));
+        assert!(windows[1].floating);
+        assert!(apply(&mut windows, "pin>>b,1"));
+        assert!(windows[1].pinned);
+        assert!(apply(
+            &mut windows,
+            "windowtitlev2>>b,GitHub, Inc. - Mozilla Firefox"
+        ));
+        assert_eq!(windows[1].title, "GitHub, Inc. - Mozilla Firefox");
+
+        // Fullscreen applies to the focused window.
+        assert!(apply(&mut windows, "fullscreen>>1"));
+        assert!(windows[0].fullscreen);
+        assert!(!windows[1].fullscreen);
+        assert!(apply(&mut windows, "fullscreen>>0"));
+        assert!(!windows[0].fullscreen);
+    }
+
+    #[test]
+    fn unknown_windows_are_out_of_sync() {
+        let mut windows = windows();
+        for message in [
+            "openwindow>>a,1,kitty,kitty",
+            "closewindow>>d",
+            "movewindowv2>>d,2,2",
+            "changefloatingmode>>d,1",
+            "pin>>d,1",
+            "windowtitlev2>>d,title",
+            "urgent>>d",
+            "activewindowv2>>d",
+        ] {
+            let before = windows.clone();
+            assert!(
+                !apply(&mut windows, message),
+                "{} should be out of sync",
+                message
+            );
+            assert_eq!(windows, before);
+        }
+
+        // Fullscreen can't be applied while no window is focused.
+        let mut windows = vec![window("0xa", 1, 1)];
+        assert!(!apply(&mut windows, "fullscreen>>1"));
+    }
+
+    #[test]
+    fn other_events_are_ignored() {
+        let mut windows = windows();
+        let before = windows.clone();
+        assert!(apply(&mut windows, "workspacev2>>2,2"));
+        assert!(apply(&mut windows, "submap>>resize"));
+        assert_eq!(windows, before);
+    }
+}
@@ -1,3 +1,4 @@
+use async_broadcast::RecvError;
 use async_std::{
     sync::{Arc, Mutex, Weak},
     task,
@@ -6,10 +7,9 @@ use gio::glib::clone::Downgrade;
 use log::error;
 use serde::Deserialize;
 
-use super::{
-    commands::HyprlandCommands,
-    events::{HyprlandEvents, LatestEventValue, LatestEventValueListener},
-};
+use super::{commands::HyprlandCommands, events::HyprlandEvents, refresh::RefreshScheduler};
+use crate::compositor::ConnectionState;
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
 
 #[derive(Clone, Default, Deserialize, Debug)]
 pub struct HyprlandWorkspace {
@@ -58,40 +58,61 @@ impl HyprlandWorkspaces {
         {
             let instance = instance.downgrade();
             task::spawn(async move {
-                let mut events = HyprlandEvents::instance().await.get_event_stream().await;
+                let events = HyprlandEvents::instance().await;
+                let mut connection_state = events.get_connection_state_emitter();
+                let mut events = events.get_event_stream().await;
 
                 instance.upgrade().unwrap().force_refresh().await;
 
-                loop {
-                    let event = events.recv().await.unwrap();
-                    match event {
-                        super::events::HyprlandEvent::MoveWindowV2(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
-                        }
-                        super::events::HyprlandEvent::MonitorAddedV2(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
+                let refresh = {
+                    let instance = instance.clone();
+                    RefreshScheduler::new(move || {
+                        let instance = instance.clone();
+                        async move {
+                            if let Some(instance) = instance.upgrade() {
+                                instance.force_refresh().await;
+                            }
                         }
-                        super::events::HyprlandEvent::MonitorRemoved(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
+                    })
+                };
+
+                {
+                    let refresh = refresh.clone();
+                    task::spawn(async move {
+                        loop {
+                            // Catch up on anything that changed while Hyprland was away.
+                            if connection_state.next().await == ConnectionState::Connected {
+                                refresh.schedule();
+                            }
                         }
-                        super::events::HyprlandEvent::CreateWorkspace(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
+                    });
+                }
+
+                loop {
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(RecvError::Overflowed(_)) => {
+                            refresh.schedule();
+                            continue;
                         }
+                        Err(RecvError::Closed) => return,
+                    };
+                    match event {
+                        super::events::HyprlandEvent::MoveWindowV2(_) => refresh.schedule(),
+                        super::events::HyprlandEvent::MonitorAddedV2(_) => refresh.schedule(),
+                        super::events::HyprlandEvent::MonitorRemoved(_) => refresh.schedule(),
+                        super::events::HyprlandEvent::CreateWorkspace(_) => refresh.schedule(),
                         super::events::HyprlandEvent::CreateWorkspaceV2(_) => {}
-                        super::events::HyprlandEvent::MoveWorkspace(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
-                        }
+                        super::events::HyprlandEvent::MoveWorkspace(_) => refresh.schedule(),
                         super::events::HyprlandEvent::MoveWorkspaceV2(_) => {}
-                        super::events::HyprlandEvent::RenameWorkspace(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
-                        }
-                        super::events::HyprlandEvent::ActiveSpecial(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
-                        }
-                        super::events::HyprlandEvent::DestroyWorkspace(_) => {
-                            instance.upgrade().unwrap().force_refresh().await
-                        }
+                        super::events::HyprlandEvent::RenameWorkspace(_) => refresh.schedule(),
+                        super::events::HyprlandEvent::ActiveSpecial(_) => refresh.schedule(),
+                        super::events::HyprlandEvent::DestroyWorkspace(_) => refresh.schedule(),
                         super::events::HyprlandEvent::DestroyWorkspaceV2(_) => {}
+                        // Keeps has_fullscreen up to date, closing a fullscreen window doesn't always send a
+                        // fullscreen event.
+                        super::events::HyprlandEvent::Fullscreen(_)
+                        | super::events::HyprlandEvent::CloseWindow(_) => refresh.schedule(),
                         super::events::HyprlandEvent::WorkspaceV2(workspace) => {
                             instance
                                 .upgrade()
@@ -115,7 +136,11 @@ impl HyprlandWorkspaces {
                             if let Some(workspace_id) = workspace_id {
                                 instance.active_workspace_id.update(workspace_id).await;
                             } else {
-                                log::warn!("Failed to find workspace for focusedmon event. Event: {:?}\n\nWorkspaces: {:?}", focused_mon, workspaces);
+                                log::warn!(
+                                    "Failed to find workspace for focusedmon event. Event: {:?}\n\nWorkspaces: {:?}",
+                                    focused_mon,
+                                    workspaces
+                                );
                             }
                         }
                         _ => {}
@@ -128,24 +153,22 @@ impl HyprlandWorkspaces {
     }
 
     pub async fn force_refresh(&self) {
-        self.workspaces
-            .update_fn(|_| {
-                task::block_on(async {
-                    let workspaces = HyprlandCommands::send_command("j/workspaces").await;
-                    let deserialized = serde_json::from_str::<Vec<HyprlandWorkspace>>(&workspaces);
-                    if deserialized.is_err() {
-                        error!(
-                            "Failed to deserialize: {}, {}",
-                            workspaces,
-                            deserialized.err().unwrap()
-                        );
-                        return None;
-                    }
+        let workspaces = match HyprlandCommands::send_command("j/workspaces").await {
+            Ok(workspaces) => workspaces,
+            Err(err) => {
+                error!("Failed to get workspaces: {}", err);
+                return;
+            }
+        };
+        match serde_json::from_str::<Vec<HyprlandWorkspace>>(&workspaces) {
+            Ok(workspaces) => self.workspaces.update(workspaces).await,
+            Err(err) => error!("Failed to deserialize: {}, {}", workspaces, err),
+        }
+    }
 
-                    Some(deserialized.unwrap())
-                })
-            })
-            .await;
+    // Workspaces as of the last refresh
+    pub async fn get_workspaces(&self) -> Vec<HyprlandWorkspace> {
+        self.workspaces.current_value.lock().await.1.clone()
     }
 
     pub fn get_workspaces_state_emitter(&self) -> LatestEventValueListener<Vec<HyprlandWorkspace>> {
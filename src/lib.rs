@@ -0,0 +1,11 @@
+// Code shared by the twbar and twbarctl binaries, along with the compositor backends which don't depend on the
+// widgets.
+
+pub mod compositor;
+pub mod control {
+    pub mod protocol;
+}
+pub mod hyprland;
+pub mod niri;
+pub mod sway;
+pub mod wayland;
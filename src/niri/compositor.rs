@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_broadcast::{InactiveReceiver, Receiver, Sender, broadcast};
+use async_std::stream::StreamExt;
+use async_std::sync::{Arc, Mutex, Weak};
+use async_std::task;
+use futures::future::BoxFuture;
+use gio::glib::clone::Downgrade;
+use log::{error, info, trace, warn};
+use serde_json::{Value, json};
+
+use super::error::NiriError;
+use super::ipc::NiriIpc;
+use super::models::{NiriEvent, NiriOutput, NiriWindow, NiriWorkspace};
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
+use crate::compositor::{
+    Action, Compositor, CompositorEvent, ConnectionState, MonitorInfo, WindowInfo, WorkspaceInfo,
+};
+
+// Delays between attempts to reconnect to niri, doubling after each failure
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// niri's view of the session, kept up to date from the event stream
+#[derive(Default)]
+struct NiriState {
+    workspaces: Vec<NiriWorkspace>,
+    windows: Vec<NiriWindow>,
+    outputs: Vec<NiriOutput>,
+    // Window ids, most recently focused first
+    focus_history: Vec<u64>,
+    // niri only names its outputs, so monitor ids are handed out the first time an output is seen
+    output_ids: HashMap<String, i32>,
+}
+
+impl NiriState {
+    fn apply(&mut self, event: NiriEvent) {
+        match event {
+            NiriEvent::WorkspacesChanged { workspaces } => self.workspaces = workspaces,
+            NiriEvent::WorkspaceActivated { id, focused } => {
+                let output = self
+                    .workspaces
+                    .iter()
+                    .find(|w| w.id == id)
+                    .and_then(|w| w.output.clone());
+                for workspace in self.workspaces.iter_mut() {
+                    if workspace.output == output {
+                        workspace.is_active = workspace.id == id;
+                    }
+                    if focused {
+                        workspace.is_focused = workspace.id == id;
+                    }
+                }
+            }
+            NiriEvent::WindowsChanged { mut windows } => {
+                // Newer versions of niri report when each window was focused, otherwise only the focused window
+                // is known.
+                windows.sort_by(|a, b| {
+                    b.is_focused
+                        .cmp(&a.is_focused)
+                        .then_with(|| b.focus_timestamp.cmp(&a.focus_timestamp))
+                });
+                self.focus_history = windows.iter().map(|w| w.id).collect();
+                self.windows = windows;
+            }
+            NiriEvent::WindowOpenedOrChanged { window } => {
+                if window.is_focused {
+                    self.focus(Some(window.id));
+                } else if !self.focus_history.contains(&window.id) {
+                    self.focus_history.push(window.id);
+                }
+                match self.windows.iter_mut().find(|w| w.id == window.id) {
+                    Some(existing) => *existing = window,
+                    None => self.windows.push(window),
+                }
+            }
+            NiriEvent::WindowClosed { id } => {
+                self.windows.retain(|w| w.id != id);
+                self.focus_history.retain(|window_id| *window_id != id);
+            }
+            // Moving windows or columns around only sends the new layouts of the windows which moved.
+            NiriEvent::WindowLayoutsChanged { changes } => {
+                for (id, layout) in changes {
+                    if let Some(window) = self.windows.iter_mut().find(|w| w.id == id) {
+                        window.layout = layout;
+                    }
+                }
+            }
+            NiriEvent::WindowFocusChanged { id } => self.focus(id),
+            NiriEvent::WindowUrgencyChanged { id, urgent } => {
+                if let Some(window) = self.windows.iter_mut().find(|w| w.id == id) {
+                    window.is_urgent = urgent;
+                }
+            }
+            NiriEvent::WorkspaceUrgencyChanged { id, urgent } => {
+                if let Some(workspace) = self.workspaces.iter_mut().find(|w| w.id == id) {
+                    workspace.is_urgent = urgent;
+                }
+            }
+            NiriEvent::ConfigLoaded { .. } => {}
+        }
+    }
+
+    fn focus(&mut self, id: Option<u64>) {
+        for window in self.windows.iter_mut() {
+            window.is_focused = Some(window.id) == id;
+        }
+        if let Some(id) = id {
+            self.focus_history.retain(|window_id| *window_id != id);
+            self.focus_history.insert(0, id);
+        }
+    }
+
+    fn output_id(&mut self, name: &str) -> i32 {
+        let next_id = self.output_ids.len() as i32;
+        *self.output_ids.entry(name.to_owned()).or_insert(next_id)
+    }
+
+    fn workspace_monitor_id(&mut self, workspace: &NiriWorkspace) -> i32 {
+        match workspace.output.as_deref() {
+            Some(output) => self.output_id(output),
+            None => -1,
+        }
+    }
+
+    fn monitors(&mut self) -> Vec<MonitorInfo> {
+        let focused_output = self
+            .workspaces
+            .iter()
+            .find(|w| w.is_focused)
+            .and_then(|w| w.output.clone());
+
+        self.outputs
+            .clone()
+            .iter()
+            .filter(|output| output.logical.is_some())
+            .map(|output| MonitorInfo {
+                id: self.output_id(&output.name),
+                name: output.name.clone(),
+                description: output.description(),
+                make: output.make.clone(),
+                model: output.model.clone(),
+                serial: output.serial.clone().unwrap_or_default(),
+                active_workspace_id: self
+                    .workspaces
+                    .iter()
+                    .find(|w| w.is_active && w.output.as_ref() == Some(&output.name))
+                    .map(|w| w.id as i32)
+                    .unwrap_or_default(),
+                focused: focused_output.as_ref() == Some(&output.name),
+            })
+            .collect()
+    }
+
+    fn workspace_infos(&mut self) -> Vec<WorkspaceInfo> {
+        self.workspaces
+            .clone()
+            .iter()
+            .map(|workspace| WorkspaceInfo {
+                id: workspace.id as i32,
+                name: workspace
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| workspace.idx.to_string()),
+                monitor_id: self.workspace_monitor_id(workspace),
+                position: workspace.idx as i32,
+                windows: Some(
+                    self.windows
+                        .iter()
+                        .filter(|w| w.workspace_id == Some(workspace.id))
+                        .count() as i32,
+                ),
+                // niri doesn't report fullscreen windows.
+                has_fullscreen: false,
+                urgent: workspace.is_urgent,
+            })
+            .collect()
+    }
+
+    fn window_infos(&mut self) -> Vec<WindowInfo> {
+        self.windows
+            .clone()
+            .iter()
+            .map(|window| {
+                let workspace = self
+                    .workspaces
+                    .iter()
+                    .find(|w| Some(w.id) == window.workspace_id)
+                    .cloned();
+                let title = window.title.clone().unwrap_or_default();
+                let class = window.app_id.clone().unwrap_or_default();
+                WindowInfo {
+                    address: window.id.to_string(),
+                    title: title.clone(),
+                    class: class.clone(),
+                    initial_title: title,
+                    initial_class: class,
+                    workspace_id: workspace.as_ref().map(|w| w.id as i32),
+                    monitor_id: workspace
+                        .as_ref()
+                        .map(|w| self.workspace_monitor_id(w))
+                        .unwrap_or(-1),
+                    // Columns of the scrolling layout keep their order in the taskbar, floating windows go last.
+                    at: window
+                        .layout
+                        .pos_in_scrolling_layout
+                        .unwrap_or((i32::MAX, 0)),
+                    focus_history_id: self
+                        .focus_history
+                        .iter()
+                        .position(|id| *id == window.id)
+                        .unwrap_or(self.focus_history.len())
+                        as i32,
+                    urgent: window.is_urgent,
+                }
+            })
+            .collect()
+    }
+}
+
+// Compositor backend for niri's JSON IPC
+pub struct NiriCompositor {
+    // Used instead of $NIRI_SOCKET when set
+    socket: Option<PathBuf>,
+    state: Mutex<NiriState>,
+    connection_state: Arc<LatestEventValue<ConnectionState>>,
+    workspaces: Arc<LatestEventValue<Vec<WorkspaceInfo>>>,
+    windows: Arc<LatestEventValue<Vec<WindowInfo>>>,
+    monitors: Arc<LatestEventValue<Vec<MonitorInfo>>>,
+    active_workspace: Arc<LatestEventValue<i32>>,
+    active_window: Arc<LatestEventValue<String>>,
+    event_sender: Sender<CompositorEvent>,
+    event_receiver: InactiveReceiver<CompositorEvent>,
+}
+
+impl NiriCompositor {
+    pub async fn new() -> Arc<Self> {
+        Self::with_socket(None).await
+    }
+
+    async fn with_socket(socket: Option<PathBuf>) -> Arc<Self> {
+        let (mut event_sender, event_receiver) = broadcast(64);
+        event_sender.set_await_active(false);
+        event_sender.set_overflow(true);
+
+        let instance = Arc::new(Self {
+            socket,
+            state: Mutex::new(NiriState::default()),
+            connection_state: Arc::new(LatestEventValue::new()),
+            workspaces: Arc::new(LatestEventValue::new()),
+            windows: Arc::new(LatestEventValue::new()),
+            monitors: Arc::new(LatestEventValue::new()),
+            active_workspace: Arc::new(LatestEventValue::new()),
+            active_window: Arc::new(LatestEventValue::new()),
+            event_sender,
+            event_receiver: event_receiver.deactivate(),
+        });
+
+        let instance_weak = instance.downgrade();
+        task::spawn(async move {
+            let mut retry_delay = MIN_RETRY_DELAY;
+            loop {
+                let socket = match instance_weak.upgrade() {
+                    Some(instance) => instance.socket(),
+                    None => return,
+                };
+                let event_stream = match socket {
+                    Ok(socket) => NiriIpc::event_stream(&socket).await,
+                    Err(err) => Err(err),
+                };
+                match event_stream {
+                    Ok(mut lines) => {
+                        info!("Connected to niri");
+                        retry_delay = MIN_RETRY_DELAY;
+                        match instance_weak.upgrade() {
+                            Some(instance) => {
+                                instance
+                                    .connection_state
+                                    .update(ConnectionState::Connected)
+                                    .await
+                            }
+                            None => return,
+                        }
+
+                        // niri starts the stream with the current state, including a ConfigLoaded which isn't a
+                        // reload.
+                        let mut config_loaded = false;
+                        while let Some(Ok(line)) = lines.next().await {
+                            let Some(instance) = instance_weak.upgrade() else {
+                                return;
+                            };
+
+                            let event = match serde_json::from_str::<NiriEvent>(&line) {
+                                Ok(event) => event,
+                                Err(_) => {
+                                    trace!("Ignoring niri event {}", line);
+                                    continue;
+                                }
+                            };
+                            match &event {
+                                NiriEvent::ConfigLoaded { failed } => {
+                                    if config_loaded && !failed {
+                                        // Sending fails when nobody is listening, which is fine.
+                                        instance
+                                            .event_sender
+                                            .broadcast_direct(CompositorEvent::ConfigReloaded)
+                                            .await
+                                            .ok();
+                                    }
+                                    config_loaded = true;
+                                    continue;
+                                }
+                                // Outputs aren't part of the event stream but adding or removing one moves
+                                // workspaces around.
+                                NiriEvent::WorkspacesChanged { .. } => {
+                                    instance.refresh_outputs().await
+                                }
+                                _ => {}
+                            }
+                            instance.state.lock().await.apply(event);
+                            instance.publish().await;
+                        }
+
+                        warn!("Lost connection to niri, reconnecting");
+                        Self::set_disconnected(&instance_weak).await;
+                    }
+                    Err(err) => {
+                        warn!(
+                            "Failed to connect to niri, retrying in {:?}: {}",
+                            retry_delay, err
+                        );
+                        Self::set_disconnected(&instance_weak).await;
+                    }
+                }
+
+                if instance_weak.upgrade().is_none() {
+                    return;
+                }
+                task::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+            }
+        });
+
+        instance
+    }
+
+    async fn set_disconnected(instance_weak: &Weak<Self>) {
+        if let Some(instance) = instance_weak.upgrade() {
+            instance
+                .connection_state
+                .update_fn(|state| {
+                    (*state != ConnectionState::Disconnected)
+                        .then_some(ConnectionState::Disconnected)
+                })
+                .await;
+        }
+    }
+
+    fn socket(&self) -> Result<PathBuf, NiriError> {
+        match &self.socket {
+            Some(socket) => Ok(socket.clone()),
+            None => NiriIpc::socket(),
+        }
+    }
+
+    async fn request(&self, request: &Value) -> Result<Value, NiriError> {
+        NiriIpc::request(&self.socket()?, request).await
+    }
+
+    async fn refresh_outputs(&self) {
+        let outputs = match self.request(&Value::from("Outputs")).await {
+            Ok(reply) => reply,
+            Err(err) => {
+                error!("Failed to get the niri outputs: {}", err);
+                return;
+            }
+        };
+        match serde_json::from_value::<HashMap<String, NiriOutput>>(outputs["Outputs"].clone()) {
+            Ok(outputs) => {
+                let mut outputs: Vec<NiriOutput> = outputs.into_values().collect();
+                outputs.sort_by(|a, b| a.name.cmp(&b.name));
+                self.state.lock().await.outputs = outputs;
+            }
+            Err(err) => error!("Failed to parse the niri outputs: {}", err),
+        }
+    }
+
+    async fn publish(&self) {
+        let mut state = self.state.lock().await;
+        let monitors = state.monitors();
+        let workspaces = state.workspace_infos();
+        let windows = state.window_infos();
+        let active_workspace = state
+            .workspaces
+            .iter()
+            .find(|w| w.is_focused)
+            .map(|w| w.id as i32);
+        let active_window = state
+            .windows
+            .iter()
+            .find(|w| w.is_focused)
+            .map(|w| w.id.to_string())
+            .unwrap_or_default();
+        drop(state);
+
+        self.monitors.update(monitors).await;
+        self.workspaces.update(workspaces).await;
+        self.windows.update(windows).await;
+        if let Some(active_workspace) = active_workspace {
+            self.active_workspace.update(active_workspace).await;
+        }
+        self.active_window.update(active_window).await;
+    }
+}
+
+impl Compositor for NiriCompositor {
+    fn workspaces(&self) -> LatestEventValueListener<Vec<WorkspaceInfo>> {
+        LatestEventValueListener::new(self.workspaces.clone())
+    }
+
+    fn windows(&self) -> LatestEventValueListener<Vec<WindowInfo>> {
+        LatestEventValueListener::new(self.windows.clone())
+    }
+
+    fn monitors(&self) -> LatestEventValueListener<Vec<MonitorInfo>> {
+        LatestEventValueListener::new(self.monitors.clone())
+    }
+
+    fn active_workspace(&self) -> LatestEventValueListener<i32> {
+        LatestEventValueListener::new(self.active_workspace.clone())
+    }
+
+    fn active_window(&self) -> LatestEventValueListener<String> {
+        LatestEventValueListener::new(self.active_window.clone())
+    }
+
+    fn connection_state(&self) -> LatestEventValueListener<ConnectionState> {
+        LatestEventValueListener::new(self.connection_state.clone())
+    }
+
+    fn events(&self) -> Receiver<CompositorEvent> {
+        self.event_receiver.activate_cloned()
+    }
+
+    fn supports(&self, action: &Action) -> bool {
+        !matches!(
+            action,
+            Action::ToggleMinimizeWindow(_) | Action::ToggleMaximizeWindow(_)
+        )
+    }
+
+    fn dispatch(&self, action: Action) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>> {
+        Box::pin(async move {
+            let action = match action {
+                Action::FocusWindow(address) => {
+                    let id = address.parse::<u64>()?;
+                    json!({ "FocusWindow": { "id": id } })
+                }
+                Action::CloseWindow(address) => {
+                    let id = address.parse::<u64>()?;
+                    json!({ "CloseWindow": { "id": id } })
+                }
+                Action::FocusWorkspace(id) => {
+                    json!({ "FocusWorkspace": { "reference": { "Id": id } } })
+                }
+                Action::ToggleMinimizeWindow(_) | Action::ToggleMaximizeWindow(_) => {
+                    return Err(format!("{:?} is not supported on niri", action).into());
+                }
+            };
+            self.request(&json!({ "Action": action })).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use async_std::io::prelude::BufReadExt;
+    use async_std::io::{BufReader, WriteExt};
+    use async_std::os::unix::net::{UnixListener, UnixStream};
+
+    use super::*;
+
+    // The start of niri's event stream with two outputs, followed by some changes.
+    const EVENTS: &str = r#"{"WorkspacesChanged":{"workspaces":[{"id":1,"idx":1,"name":null,"output":"eDP-1","is_urgent":false,"is_active":true,"is_focused":true,"active_window_id":10},{"id":2,"idx":2,"name":"web","output":"eDP-1","is_urgent":false,"is_active":false,"is_focused":false,"active_window_id":11},{"id":3,"idx":1,"name":null,"output":"HDMI-A-1","is_urgent":false,"is_active":true,"is_focused":false,"active_window_id":null}]}}
+{"KeyboardLayoutsChanged":{"keyboard_layouts":{"names":["English (US)"],"current_idx":0}}}
+{"OverviewOpenedOrClosed":{"is_open":false}}
+{"ConfigLoaded":{"failed":false}}
+{"WindowsChanged":{"windows":[{"id":11,"title":"Mozilla Firefox","app_id":"firefox","pid":11,"workspace_id":2,"is_focused":false,"is_floating":false,"is_urgent":false,"layout":{"pos_in_scrolling_layout":[1,1]},"focus_timestamp":{"secs":100,"nanos":0}},{"id":10,"title":"shell","app_id":"foot","pid":10,"workspace_id":1,"is_focused":true,"is_floating":false,"is_urgent":false,"layout":{"pos_in_scrolling_layout":[2,1]},"focus_timestamp":{"secs":200,"nanos":0}},{"id":12,"title":"calculator","app_id":"calc","pid":12,"workspace_id":1,"is_focused":false,"is_floating":true,"is_urgent":false,"layout":{"pos_in_scrolling_layout":null},"focus_timestamp":{"secs":150,"nanos":0}}]}}
+{"WindowLayoutsChanged":{"changes":[[10,{"pos_in_scrolling_layout":[2,1]}]]}}"#;
+
+    fn apply_all(state: &mut NiriState, events: &str) {
+        for line in events.lines() {
+            // Events the bar doesn't use fail to parse, like they do on the real stream.
+            if let Ok(event) = serde_json::from_str::<NiriEvent>(line) {
+                state.apply(event);
+            }
+        }
+    }
+
+    fn started() -> NiriState {
+        let mut state = NiriState::default();
+        apply_all(&mut state, EVENTS);
+        state
+    }
+
+    // Address, workspace, monitor, position, focus history and urgency of a window
+    type WindowRow = (String, Option<i32>, i32, (i32, i32), i32, bool);
+    // Id, name, monitor, position, window count and urgency of a workspace
+    type WorkspaceRow = (i32, String, i32, i32, Option<i32>, bool);
+
+    fn windows(state: &mut NiriState) -> Vec<WindowRow> {
+        state
+            .window_infos()
+            .into_iter()
+            .map(|w| {
+                (
+                    w.address,
+                    w.workspace_id,
+                    w.monitor_id,
+                    w.at,
+                    w.focus_history_id,
+                    w.urgent,
+                )
+            })
+            .collect()
+    }
+
+    fn workspaces(state: &mut NiriState) -> Vec<WorkspaceRow> {
+        state
+            .workspace_infos()
+            .into_iter()
+            .map(|w| (w.id, w.name, w.monitor_id, w.position, w.windows, w.urgent))
+            .collect()
+    }
+
+    #[test]
+    fn initial_state() {
+        let mut state = started();
+
+        assert_eq!(
+            workspaces(&mut state),
+            [
+                (1, "1".to_owned(), 0, 1, Some(2), false),
+                (2, "web".to_owned(), 0, 2, Some(1), false),
+                (3, "1".to_owned(), 1, 1, Some(0), false),
+            ]
+        );
+        assert_eq!(
+            windows(&mut state),
+            [
+                ("10".to_owned(), Some(1), 0, (2, 1), 0, false),
+                ("12".to_owned(), Some(1), 0, (i32::MAX, 0), 1, false),
+                ("11".to_owned(), Some(2), 0, (1, 1), 2, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn workspace_activated() {
+        let mut state = started();
+        apply_all(
+            &mut state,
+            r#"{"WorkspaceActivated":{"id":2,"focused":true}}
+{"WorkspaceActivated":{"id":3,"focused":false}}"#,
+        );
+
+        let active: Vec<_> = state
+            .workspaces
+            .iter()
+            .map(|w| (w.id, w.is_active, w.is_focused))
+            .collect();
+        assert_eq!(
+            active,
+            [(1, false, false), (2, true, true), (3, true, false)]
+        );
+    }
+
+    #[test]
+    fn windows_open_focus_and_close() {
+        let mut state = started();
+        apply_all(
+            &mut state,
+            r#"{"WindowOpenedOrChanged":{"window":{"id":13,"title":"notes","app_id":"editor","workspace_id":3,"is_focused":false,"is_urgent":false,"layout":{"pos_in_scrolling_layout":[1,1]}}}}
+{"WindowFocusChanged":{"id":11}}
+{"WindowClosed":{"id":10}}
+{"WindowOpenedOrChanged":{"window":{"id":12,"title":"calculator","app_id":"calc","workspace_id":2,"is_focused":true,"is_urgent":false,"layout":{"pos_in_scrolling_layout":[2,1]}}}}"#,
+        );
+
+        assert_eq!(
+            windows(&mut state),
+            [
+                ("12".to_owned(), Some(2), 0, (2, 1), 0, false),
+                ("11".to_owned(), Some(2), 0, (1, 1), 1, false),
+                ("13".to_owned(), Some(3), 1, (1, 1), 2, false),
+            ]
+        );
+        let focused: Vec<_> = state.windows.iter().map(|w| w.is_focused).collect();
+        assert_eq!(focused, [true, false, false]);
+
+        state.apply(NiriEvent::WindowFocusChanged { id: None });
+        assert!(state.windows.iter().all(|w| !w.is_focused));
+        assert_eq!(state.focus_history, [12, 11, 13]);
+    }
+
+    #[test]
+    fn window_layouts_changed() {
+        let mut state = started();
+        apply_all(
+            &mut state,
+            r#"{"WindowLayoutsChanged":{"changes":[[10,{"pos_in_scrolling_layout":[3,1],"tile_size":[960.0,1080.0]}],[12,{"pos_in_scrolling_layout":[1,2]}],[99,{"pos_in_scrolling_layout":[1,1]}]]}}"#,
+        );
+
+        let mut windows = state.window_infos();
+        windows.sort_by_key(|w| (w.workspace_id, w.at));
+        let order: Vec<_> = windows.iter().map(|w| (w.address.as_str(), w.at)).collect();
+        assert_eq!(order, [("12", (1, 2)), ("10", (3, 1)), ("11", (1, 1))]);
+    }
+
+    #[test]
+    fn urgency_changes() {
+        let mut state = started();
+        apply_all(
+            &mut state,
+            r#"{"WindowUrgencyChanged":{"id":11,"urgent":true}}
+{"WorkspaceUrgencyChanged":{"id":2,"urgent":true}}
+{"WindowUrgencyChanged":{"id":99,"urgent":true}}"#,
+        );
+        let urgent: Vec<_> = state.window_infos().iter().map(|w| w.urgent).collect();
+        assert_eq!(urgent, [false, false, true]);
+        let urgent: Vec<_> = state.workspace_infos().iter().map(|w| w.urgent).collect();
+        assert_eq!(urgent, [false, true, false]);
+
+        apply_all(
+            &mut state,
+            r#"{"WindowUrgencyChanged":{"id":11,"urgent":false}}
+{"WorkspaceUrgencyChanged":{"id":2,"urgent":false}}"#,
+        );
+        assert!(state.window_infos().iter().all(|w| !w.urgent));
+        assert!(state.workspace_infos().iter().all(|w| !w.urgent));
+    }
+
+    #[test]
+    fn monitors_follow_focus() {
+        let mut state = started();
+        let output = |name: &str| {
+            serde_json::from_value::<NiriOutput>(json!({
+                "name": name, "make": "Acme", "model": "Panel", "serial": null, "logical": {}
+            }))
+            .unwrap()
+        };
+        state.outputs = vec![output("HDMI-A-1"), output("eDP-1")];
+        apply_all(
+            &mut state,
+            r#"{"WorkspaceActivated":{"id":3,"focused":true}}"#,
+        );
+
+        let monitors: Vec<_> = state
+            .monitors()
+            .into_iter()
+            .map(|m| {
+                (
+                    m.id,
+                    m.name,
+                    m.description,
+                    m.active_workspace_id,
+                    m.focused,
+                )
+            })
+            .collect();
+        assert_eq!(
+            monitors,
+            [
+                (0, "HDMI-A-1".to_owned(), "Acme Panel".to_owned(), 3, true),
+                (1, "eDP-1".to_owned(), "Acme Panel".to_owned(), 1, false),
+            ]
+        );
+    }
+
+    const OUTPUTS: &str = r#"{"Ok":{"Outputs":{"eDP-1":{"name":"eDP-1","make":"Acme","model":"Panel","serial":null,"logical":{"x":0,"y":0}},"HDMI-A-1":{"name":"HDMI-A-1","make":"Acme","model":"Monitor","serial":null,"logical":{"x":1920,"y":0}}}}}"#;
+
+    // Stands in for niri on the socket. Each event stream gets the next script, the connection is closed afterwards
+    // like when niri exits, except for the last one which is kept open.
+    async fn fake_niri(socket: &Path, scripts: Vec<String>) {
+        let listener = UnixListener::bind(socket).await.unwrap();
+        task::spawn(async move {
+            let mut scripts = scripts.into_iter();
+            let mut incoming = listener.incoming();
+            while let Some(Ok(stream)) = incoming.next().await {
+                let request = match BufReader::new(&stream).lines().next().await {
+                    Some(Ok(request)) => request,
+                    _ => continue,
+                };
+                let reply = match request.as_str() {
+                    r#""EventStream""# => {
+                        format!("{{\"Ok\":\"Handled\"}}\n{}\n", scripts.next().unwrap())
+                    }
+                    r#""Outputs""# => format!("{}\n", OUTPUTS),
+                    _ => "{\"Ok\":\"Handled\"}\n".to_owned(),
+                };
+                (&stream).write_all(reply.as_bytes()).await.unwrap();
+                if request == r#""EventStream""# && scripts.as_slice().is_empty() {
+                    task::spawn(async move {
+                        let _stream: UnixStream = stream;
+                        futures::future::pending::<()>().await;
+                    });
+                }
+            }
+        });
+    }
+
+    fn taskbar_order(windows: &[WindowInfo]) -> Vec<&str> {
+        let mut windows: Vec<_> = windows.iter().collect();
+        windows.sort_by_key(|w| (w.workspace_id, w.at));
+        windows.iter().map(|w| w.address.as_str()).collect()
+    }
+
+    #[async_std::test]
+    async fn event_stream_over_a_socket() {
+        let dir = std::env::temp_dir().join(format!("twbar-niri-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("niri.sock");
+        let _ = std::fs::remove_file(&socket);
+        // The first stream ends after the initial state, the bar reconnects and niri reports a moved window.
+        let moved = r#"{"WindowLayoutsChanged":{"changes":[[10,{"pos_in_scrolling_layout":[3,1]}],[12,{"pos_in_scrolling_layout":[1,2]}]]}}"#;
+        fake_niri(
+            &socket,
+            vec![EVENTS.to_owned(), format!("{}\n{}", EVENTS, moved)],
+        )
+        .await;
+
+        let niri = NiriCompositor::with_socket(Some(socket)).await;
+        let windows = niri
+            .windows()
+            .wait_for(|windows| taskbar_order(windows) == ["12", "10", "11"])
+            .await;
+        assert_eq!(windows.len(), 3);
+        niri.connection_state()
+            .wait_for(|state| *state == ConnectionState::Connected)
+            .await;
+
+        let monitors: Vec<_> = niri
+            .monitors()
+            .wait_for(|monitors| monitors.len() == 2)
+            .await
+            .into_iter()
+            .map(|m| (m.name, m.description, m.active_workspace_id))
+            .collect();
+        assert_eq!(
+            monitors,
+            [
+                ("HDMI-A-1".to_owned(), "Acme Monitor".to_owned(), 3),
+                ("eDP-1".to_owned(), "Acme Panel".to_owned(), 1),
+            ]
+        );
+        assert_eq!(niri.active_workspace().wait_for(|_| true).await, 1);
+        assert_eq!(niri.active_window().wait_for(|_| true).await, "10");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use async_std::io;
+
+#[derive(Debug)]
+pub enum NiriError {
+    MissingSocket,
+    InvalidResponse(String),
+    // niri replied with an error message
+    RequestFailed(String),
+    Io(io::Error),
+}
+
+impl Display for NiriError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSocket => write!(f, "NIRI_SOCKET is not set"),
+            Self::InvalidResponse(err) => write!(f, "Invalid response from niri: {}", err),
+            Self::RequestFailed(err) => write!(f, "niri request failed: {}", err),
+            Self::Io(err) => write!(f, "niri socket error: {}", err),
+        }
+    }
+}
+
+impl Error for NiriError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NiriError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
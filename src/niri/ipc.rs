@@ -0,0 +1,69 @@
+use std::env::var;
+use std::path::{Path, PathBuf};
+
+use async_std::io::prelude::BufReadExt;
+use async_std::io::{BufReader, Lines, WriteExt};
+use async_std::os::unix::net::UnixStream;
+use async_std::stream::StreamExt;
+use serde_json::Value;
+
+use super::error::NiriError;
+
+// niri's IPC is one JSON request per line, answered with a single line holding either {"Ok": reply} or
+// {"Err": message}.
+pub struct NiriIpc {}
+
+impl NiriIpc {
+    // Socket of the niri instance the bar was started in
+    pub fn socket() -> Result<PathBuf, NiriError> {
+        var("NIRI_SOCKET")
+            .map(PathBuf::from)
+            .map_err(|_| NiriError::MissingSocket)
+    }
+
+    pub async fn request(socket: &Path, request: &Value) -> Result<Value, NiriError> {
+        let mut stream = UnixStream::connect(socket).await?;
+        Self::send(&mut stream, request).await?;
+        let mut lines = BufReader::new(stream).lines();
+        Self::read_reply(&mut lines).await
+    }
+
+    // Returns the lines of the event stream, each of which is a single event.
+    pub async fn event_stream(socket: &Path) -> Result<Lines<BufReader<UnixStream>>, NiriError> {
+        let mut stream = UnixStream::connect(socket).await?;
+        Self::send(&mut stream, &Value::from("EventStream")).await?;
+        let mut lines = BufReader::new(stream).lines();
+        Self::read_reply(&mut lines).await?;
+        Ok(lines)
+    }
+
+    async fn send(stream: &mut UnixStream, request: &Value) -> Result<(), NiriError> {
+        stream
+            .write_all(format!("{}\n", request).as_bytes())
+            .await?;
+        Ok(())
+    }
+
+    async fn read_reply(lines: &mut Lines<BufReader<UnixStream>>) -> Result<Value, NiriError> {
+        let line = match lines.next().await {
+            Some(line) => line?,
+            None => {
+                return Err(NiriError::InvalidResponse(
+                    "connection closed before the reply".to_owned(),
+                ));
+            }
+        };
+        let reply = serde_json::from_str::<Value>(&line)
+            .map_err(|err| NiriError::InvalidResponse(err.to_string()))?;
+
+        if let Some(reply) = reply.get("Ok") {
+            return Ok(reply.clone());
+        }
+        match reply.get("Err") {
+            Some(err) => Err(NiriError::RequestFailed(
+                err.as_str().unwrap_or_default().to_owned(),
+            )),
+            None => Err(NiriError::InvalidResponse(line)),
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod compositor;
+pub mod error;
+mod ipc;
+mod models;
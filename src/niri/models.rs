@@ -0,0 +1,86 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct NiriWorkspace {
+    pub id: u64,
+    // Position of the workspace on its output, starting at 1
+    pub idx: u8,
+    pub name: Option<String>,
+    pub output: Option<String>,
+    // Shown on its output
+    pub is_active: bool,
+    pub is_focused: bool,
+    pub is_urgent: bool,
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct NiriWindowLayout {
+    // Column and position in the column, starting at 1, None for floating windows
+    pub pos_in_scrolling_layout: Option<(i32, i32)>,
+}
+
+// Id of a window and its new layout
+pub type NiriLayoutChange = (u64, NiriWindowLayout);
+
+#[derive(Deserialize, Clone, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(default)]
+pub struct NiriTimestamp {
+    pub secs: u64,
+    pub nanos: u32,
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct NiriWindow {
+    pub id: u64,
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    pub workspace_id: Option<u64>,
+    pub is_focused: bool,
+    pub is_urgent: bool,
+    pub layout: NiriWindowLayout,
+    // Only reported by newer versions of niri
+    pub focus_timestamp: Option<NiriTimestamp>,
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct NiriOutput {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub serial: Option<String>,
+    // Position and scale of the output, None when it is disabled
+    pub logical: Option<Value>,
+}
+
+impl NiriOutput {
+    // Matches the "make model serial" description Hyprland reports.
+    pub fn description(&self) -> String {
+        [Some(&self.make), Some(&self.model), self.serial.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty() && *part != "Unknown")
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// The events of niri's event stream which the bar uses, the rest fail to parse and are ignored.
+#[derive(Deserialize, Debug)]
+pub enum NiriEvent {
+    WorkspacesChanged { workspaces: Vec<NiriWorkspace> },
+    WorkspaceActivated { id: u64, focused: bool },
+    WindowsChanged { windows: Vec<NiriWindow> },
+    WindowOpenedOrChanged { window: NiriWindow },
+    WindowClosed { id: u64 },
+    WindowLayoutsChanged { changes: Vec<NiriLayoutChange> },
+    WindowFocusChanged { id: Option<u64> },
+    WindowUrgencyChanged { id: u64, urgent: bool },
+    WorkspaceUrgencyChanged { id: u64, urgent: bool },
+    ConfigLoaded { failed: bool },
+}
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use gtk4::gdk::Display;
+use gtk4::{self as gtk, CssProvider, CssSection, glib};
+use log::{info, trace, warn};
+
+use crate::config::Config;
+
+// Built in theme, installed below the user's stylesheet so any of its rules can be overridden.
+const DEFAULT_STYLE: &str = include_str!("style.css");
+
+thread_local! {
+    // GTK objects may only be used from the main thread so the providers live in a thread local.
+    static THEME_PROVIDER: CssProvider = CssProvider::new();
+    static USER_PROVIDER: CssProvider = CssProvider::new();
+}
+
+// Returns the path of the user's stylesheet, usually ~/.config/twbar/style.css
+pub fn stylesheet_path() -> Option<PathBuf> {
+    Some(Config::config_dir()?.join("style.css"))
+}
+
+// Adds the built in theme and the user's stylesheet to the display. Must be called from the main thread.
+pub fn install(display: &Display) {
+    THEME_PROVIDER.with(|provider| {
+        provider.connect_parsing_error(log_parsing_error);
+        provider.load_from_string(DEFAULT_STYLE);
+        gtk::style_context_add_provider_for_display(
+            display,
+            provider,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+    });
+    USER_PROVIDER.with(|provider| {
+        provider.connect_parsing_error(log_parsing_error);
+        gtk::style_context_add_provider_for_display(
+            display,
+            provider,
+            // One above the user priority so twbar's stylesheet wins over rules from the global gtk.css.
+            gtk::STYLE_PROVIDER_PRIORITY_USER + 1,
+        );
+    });
+    reload();
+}
+
+// Reloads the user's stylesheet, the built in theme applies on its own if there isn't one.
+pub fn reload() {
+    USER_PROVIDER.with(
+        |provider| match stylesheet_path().filter(|path| path.exists()) {
+            Some(path) => {
+                info!("Loading stylesheet from {}", path.display());
+                provider.load_from_path(path);
+            }
+            None => {
+                trace!("No user stylesheet found");
+                provider.load_from_string("");
+            }
+        },
+    );
+}
+
+fn log_parsing_error(_provider: &CssProvider, section: &CssSection, error: &glib::Error) {
+    warn!("CSS error at {}: {}", section, error);
+}
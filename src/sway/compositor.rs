@@ -0,0 +1,547 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+
+use async_broadcast::{InactiveReceiver, Receiver, Sender, broadcast};
+use async_std::sync::{Arc, Weak};
+use async_std::task;
+use futures::future::BoxFuture;
+use gio::glib::clone::Downgrade;
+use log::{error, info, warn};
+
+use super::error::SwayError;
+use super::ipc::{
+    EVENT_MODE, EVENT_OUTPUT, EVENT_WINDOW, EVENT_WORKSPACE, GET_OUTPUTS, GET_TREE, RUN_COMMAND,
+    SUBSCRIBE, SwayIpc,
+};
+use super::models::{SwayChangeEvent, SwayCommandResult, SwayNode, SwayOutput};
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
+use crate::compositor::{
+    Action, Compositor, CompositorEvent, ConnectionState, MonitorInfo, WindowInfo, WorkspaceInfo,
+};
+
+// Delays between attempts to reconnect to sway, doubling after each failure
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct SwayState {
+    workspaces: Vec<WorkspaceInfo>,
+    windows: Vec<WindowInfo>,
+    active_workspace: Option<i32>,
+    active_window: String,
+    // Ids of the output nodes by output name
+    outputs: HashMap<String, i32>,
+}
+
+impl SwayState {
+    fn from_tree(tree: &SwayNode) -> Self {
+        let mut state = Self::default();
+        state.walk(tree, None, 0, None);
+        state
+    }
+
+    fn walk(
+        &mut self,
+        node: &SwayNode,
+        parent: Option<&SwayNode>,
+        mut monitor_id: i32,
+        mut workspace: Option<usize>,
+    ) {
+        match node.node_type.as_str() {
+            // Holds the scratchpad, which isn't shown on any monitor.
+            "output" if node.name.as_deref() == Some("__i3") => return,
+            "output" => {
+                monitor_id = node.id as i32;
+                if let Some(name) = &node.name {
+                    self.outputs.insert(name.clone(), monitor_id);
+                }
+            }
+            "workspace" => {
+                // The tree is walked in focus order but sway keeps the workspaces of an output in order.
+                let position = parent
+                    .and_then(|output| output.nodes.iter().position(|n| n.id == node.id))
+                    .unwrap_or_default() as i32;
+                workspace = Some(self.workspaces.len());
+                self.workspaces.push(WorkspaceInfo {
+                    id: workspace_id(node),
+                    name: node.name.clone().unwrap_or_default(),
+                    monitor_id,
+                    position,
+                    windows: Some(0),
+                    has_fullscreen: false,
+                    urgent: node.urgent,
+                });
+            }
+            _ if node.is_window() => {
+                let class = node.class();
+                let title = node.name.clone().unwrap_or_default();
+                let workspace_info = workspace.map(|index| &mut self.workspaces[index]);
+                let workspace_id = workspace_info.map(|workspace_info| {
+                    workspace_info.windows = workspace_info.windows.map(|windows| windows + 1);
+                    workspace_info.has_fullscreen |= node.fullscreen_mode != 0;
+                    workspace_info.id
+                });
+                self.windows.push(WindowInfo {
+                    address: node.id.to_string(),
+                    title: title.clone(),
+                    class: class.clone(),
+                    initial_title: title,
+                    initial_class: class,
+                    workspace_id,
+                    monitor_id,
+                    at: (node.rect.x, node.rect.y),
+                    // Walking the tree in focus order visits the focused window first.
+                    focus_history_id: self.windows.len() as i32,
+                    urgent: node.urgent,
+                });
+            }
+            _ => {}
+        }
+
+        if node.focused {
+            self.active_workspace = workspace.map(|index| self.workspaces[index].id);
+            if node.is_window() {
+                self.active_window = node.id.to_string();
+            }
+        }
+
+        for child in node.children_by_focus() {
+            self.walk(child, Some(node), monitor_id, workspace);
+        }
+    }
+
+    // Only sway has the ids in GET_OUTPUTS, so the outputs are matched to the tree's output nodes by name for i3.
+    fn monitors(&self, outputs: &[SwayOutput]) -> Vec<MonitorInfo> {
+        outputs
+            .iter()
+            .filter(|output| output.active)
+            .filter_map(|output| {
+                let id = *self.outputs.get(&output.name)?;
+                let active_workspace_id = self
+                    .workspaces
+                    .iter()
+                    .find(|w| {
+                        w.monitor_id == id && Some(&w.name) == output.current_workspace.as_ref()
+                    })
+                    .map(|w| w.id)
+                    .unwrap_or_default();
+                Some(MonitorInfo {
+                    id,
+                    name: output.name.clone(),
+                    description: output.description(),
+                    make: output.make.clone(),
+                    model: output.model.clone(),
+                    serial: output.serial.clone(),
+                    active_workspace_id,
+                    focused: output.focused,
+                })
+            })
+            .collect()
+    }
+}
+
+// sway workspaces are addressed by name and several can share a number, e.g. "1" and "1:web", so they're told apart
+// by their container id.
+fn workspace_id(node: &SwayNode) -> i32 {
+    node.id as i32
+}
+
+// Compositor backend for sway, and i3 which speaks the same protocol
+pub struct SwayCompositor {
+    connection_state: Arc<LatestEventValue<ConnectionState>>,
+    workspaces: Arc<LatestEventValue<Vec<WorkspaceInfo>>>,
+    windows: Arc<LatestEventValue<Vec<WindowInfo>>>,
+    monitors: Arc<LatestEventValue<Vec<MonitorInfo>>>,
+    active_workspace: Arc<LatestEventValue<i32>>,
+    active_window: Arc<LatestEventValue<String>>,
+    event_sender: Sender<CompositorEvent>,
+    event_receiver: InactiveReceiver<CompositorEvent>,
+}
+
+impl SwayCompositor {
+    pub async fn new() -> Arc<Self> {
+        let (mut event_sender, event_receiver) = broadcast(64);
+        event_sender.set_await_active(false);
+        event_sender.set_overflow(true);
+
+        let instance = Arc::new(Self {
+            connection_state: Arc::new(LatestEventValue::new()),
+            workspaces: Arc::new(LatestEventValue::new()),
+            windows: Arc::new(LatestEventValue::new()),
+            monitors: Arc::new(LatestEventValue::new()),
+            active_workspace: Arc::new(LatestEventValue::new()),
+            active_window: Arc::new(LatestEventValue::new()),
+            event_sender,
+            event_receiver: event_receiver.deactivate(),
+        });
+
+        let instance_weak = instance.downgrade();
+        task::spawn(async move {
+            let mut retry_delay = MIN_RETRY_DELAY;
+            loop {
+                match Self::subscribe().await {
+                    Ok(mut ipc) => {
+                        info!("Connected to sway");
+                        retry_delay = MIN_RETRY_DELAY;
+                        match instance_weak.upgrade() {
+                            Some(instance) => {
+                                instance
+                                    .connection_state
+                                    .update(ConnectionState::Connected)
+                                    .await;
+                                instance.force_refresh().await;
+                            }
+                            None => return,
+                        }
+
+                        while let Ok((message_type, payload)) = ipc.receive().await {
+                            match instance_weak.upgrade() {
+                                Some(instance) => {
+                                    instance.handle_event(message_type, &payload).await
+                                }
+                                None => return,
+                            }
+                        }
+
+                        warn!("Lost connection to sway, reconnecting");
+                        Self::set_disconnected(&instance_weak).await;
+                    }
+                    Err(err) => {
+                        warn!(
+                            "Failed to connect to sway, retrying in {:?}: {}",
+                            retry_delay, err
+                        );
+                        Self::set_disconnected(&instance_weak).await;
+                    }
+                }
+
+                if instance_weak.upgrade().is_none() {
+                    return;
+                }
+                task::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+            }
+        });
+
+        instance
+    }
+
+    async fn subscribe() -> Result<SwayIpc, SwayError> {
+        let mut ipc = SwayIpc::connect().await?;
+        ipc.send(SUBSCRIBE, r#"["workspace","window","output","mode"]"#)
+            .await?;
+        let (_, reply) = ipc.receive().await?;
+        let result = serde_json::from_str::<SwayCommandResult>(&reply)
+            .map_err(|err| SwayError::InvalidResponse(err.to_string()))?;
+        if !result.success {
+            return Err(SwayError::CommandFailed(
+                "subscribing to events was rejected".to_owned(),
+            ));
+        }
+        Ok(ipc)
+    }
+
+    async fn set_disconnected(instance_weak: &Weak<Self>) {
+        if let Some(instance) = instance_weak.upgrade() {
+            instance
+                .connection_state
+                .update_fn(|state| {
+                    (*state != ConnectionState::Disconnected)
+                        .then_some(ConnectionState::Disconnected)
+                })
+                .await;
+        }
+    }
+
+    async fn handle_event(&self, message_type: u32, payload: &str) {
+        match message_type {
+            EVENT_MODE => {
+                let Ok(mode) = serde_json::from_str::<SwayChangeEvent>(payload) else {
+                    error!("Failed to parse mode event: {}", payload);
+                    return;
+                };
+                // The default bindings are reported as a mode called "default".
+                let mode = if mode.change == "default" {
+                    String::new()
+                } else {
+                    mode.change
+                };
+                // Sending fails when nobody is listening, which is fine.
+                self.event_sender
+                    .broadcast_direct(CompositorEvent::BindingMode(mode))
+                    .await
+                    .ok();
+            }
+            EVENT_WORKSPACE => {
+                let is_reload = serde_json::from_str::<SwayChangeEvent>(payload)
+                    .is_ok_and(|workspace| workspace.change == "reload");
+                if is_reload {
+                    self.event_sender
+                        .broadcast_direct(CompositorEvent::ConfigReloaded)
+                        .await
+                        .ok();
+                }
+                self.force_refresh().await;
+            }
+            EVENT_WINDOW | EVENT_OUTPUT => self.force_refresh().await,
+            _ => {}
+        }
+    }
+
+    // Rebuilds every model from the tree and the outputs, sway's events don't carry enough to patch them.
+    async fn force_refresh(&self) {
+        let (tree, outputs) = match Self::query().await {
+            Ok(result) => result,
+            Err(err) => {
+                error!("Failed to get the sway tree: {}", err);
+                return;
+            }
+        };
+
+        let state = SwayState::from_tree(&tree);
+        let monitors = state.monitors(&outputs);
+        // An empty output can have focus instead of a window or workspace.
+        let active_workspace = state.active_workspace.or_else(|| {
+            monitors
+                .iter()
+                .find(|m| m.focused)
+                .map(|m| m.active_workspace_id)
+        });
+
+        self.monitors.update(monitors).await;
+        self.workspaces.update(state.workspaces).await;
+        self.windows.update(state.windows).await;
+        if let Some(active_workspace) = active_workspace {
+            self.active_workspace.update(active_workspace).await;
+        }
+        self.active_window.update(state.active_window).await;
+    }
+
+    async fn query() -> Result<(SwayNode, Vec<SwayOutput>), SwayError> {
+        let tree = SwayIpc::request(GET_TREE, "").await?;
+        let tree = serde_json::from_str::<SwayNode>(&tree)
+            .map_err(|err| SwayError::InvalidResponse(err.to_string()))?;
+        let outputs = SwayIpc::request(GET_OUTPUTS, "").await?;
+        let outputs = serde_json::from_str::<Vec<SwayOutput>>(&outputs)
+            .map_err(|err| SwayError::InvalidResponse(err.to_string()))?;
+        Ok((tree, outputs))
+    }
+
+    async fn run_command(command: &str) -> Result<(), SwayError> {
+        let reply = SwayIpc::request(RUN_COMMAND, command).await?;
+        let results = serde_json::from_str::<Vec<SwayCommandResult>>(&reply)
+            .map_err(|err| SwayError::InvalidResponse(err.to_string()))?;
+        match results.into_iter().find(|result| !result.success) {
+            Some(result) => Err(SwayError::CommandFailed(format!(
+                "{}: {}",
+                command,
+                result.error.unwrap_or_default()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Compositor for SwayCompositor {
+    fn workspaces(&self) -> LatestEventValueListener<Vec<WorkspaceInfo>> {
+        LatestEventValueListener::new(self.workspaces.clone())
+    }
+
+    fn windows(&self) -> LatestEventValueListener<Vec<WindowInfo>> {
+        LatestEventValueListener::new(self.windows.clone())
+    }
+
+    fn monitors(&self) -> LatestEventValueListener<Vec<MonitorInfo>> {
+        LatestEventValueListener::new(self.monitors.clone())
+    }
+
+    fn active_workspace(&self) -> LatestEventValueListener<i32> {
+        LatestEventValueListener::new(self.active_workspace.clone())
+    }
+
+    fn active_window(&self) -> LatestEventValueListener<String> {
+        LatestEventValueListener::new(self.active_window.clone())
+    }
+
+    fn connection_state(&self) -> LatestEventValueListener<ConnectionState> {
+        LatestEventValueListener::new(self.connection_state.clone())
+    }
+
+    fn events(&self) -> Receiver<CompositorEvent> {
+        self.event_receiver.activate_cloned()
+    }
+
+    fn supports(&self, action: &Action) -> bool {
+        !matches!(
+            action,
+            Action::ToggleMinimizeWindow(_) | Action::ToggleMaximizeWindow(_)
+        )
+    }
+
+    fn dispatch(&self, action: Action) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>> {
+        Box::pin(async move {
+            let command = match action {
+                Action::FocusWindow(address) => format!("[con_id={}] focus", address),
+                Action::CloseWindow(address) => format!("[con_id={}] kill", address),
+                Action::ToggleMinimizeWindow(_) | Action::ToggleMaximizeWindow(_) => {
+                    return Err(format!("{:?} is not supported on sway", action).into());
+                }
+                Action::FocusWorkspace(id) => {
+                    let workspaces = self.workspaces.current_value.lock().await;
+                    match workspaces.1.iter().find(|w| w.id == id) {
+                        Some(workspace) => format!(
+                            "workspace \"{}\"",
+                            workspace.name.replace('\\', "\\\\").replace('"', "\\\"")
+                        ),
+                        None => return Err(format!("Unknown workspace {}", id).into()),
+                    }
+                }
+            };
+            Self::run_command(&command).await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two outputs, with a workspace and window in the scratchpad, an unnamed split container and workspaces "1" and
+    // "1:web" which share their number.
+    const TREE: &str = r#"{
+        "id": 1, "type": "root", "name": "root", "focus": [3, 20, 2],
+        "nodes": [
+            {"id": 2, "type": "output", "name": "__i3", "nodes": [
+                {"id": 6, "type": "workspace", "name": "__i3_scratch", "num": -1, "floating_nodes": [
+                    {"id": 50, "type": "floating_con", "name": "scratch", "app_id": "foot", "pid": 50}
+                ]}
+            ]},
+            {"id": 3, "type": "output", "name": "eDP-1", "focus": [5, 4], "nodes": [
+                {"id": 4, "type": "workspace", "name": "1", "num": 1, "urgent": true, "nodes": [
+                    {"id": 13, "type": "con", "name": null, "focus": [12], "nodes": [
+                        {"id": 12, "type": "con", "name": "irc", "app_id": "hexchat", "pid": 12, "urgent": true,
+                         "rect": {"x": 5, "y": 6}}
+                    ]}
+                ]},
+                {"id": 5, "type": "workspace", "name": "1:web", "num": 1, "focus": [11, 10],
+                 "nodes": [
+                    {"id": 10, "type": "con", "name": "shell", "app_id": "foot", "pid": 10}
+                 ],
+                 "floating_nodes": [
+                    {"id": 11, "type": "floating_con", "name": "Mozilla Firefox", "focused": true,
+                     "fullscreen_mode": 1, "window_properties": {"class": "firefox"}}
+                 ]}
+            ]},
+            {"id": 20, "type": "output", "name": "HDMI-A-1", "nodes": [
+                {"id": 21, "type": "workspace", "name": "mail", "num": -1}
+            ]}
+        ]
+    }"#;
+
+    #[test]
+    fn from_tree_lists_workspaces_and_windows() {
+        let tree = serde_json::from_str::<SwayNode>(TREE).unwrap();
+        let state = SwayState::from_tree(&tree);
+
+        let workspaces: Vec<_> = state
+            .workspaces
+            .iter()
+            .map(|w| {
+                (
+                    w.id,
+                    w.name.as_str(),
+                    w.monitor_id,
+                    w.position,
+                    w.windows,
+                    w.has_fullscreen,
+                    w.urgent,
+                )
+            })
+            .collect();
+        assert_eq!(
+            workspaces,
+            [
+                (5, "1:web", 3, 1, Some(2), true, false),
+                (4, "1", 3, 0, Some(1), false, true),
+                (21, "mail", 20, 0, Some(0), false, false),
+            ]
+        );
+
+        let windows: Vec<_> = state
+            .windows
+            .iter()
+            .map(|w| {
+                (
+                    w.address.as_str(),
+                    w.title.as_str(),
+                    w.class.as_str(),
+                    w.workspace_id,
+                    w.monitor_id,
+                    w.focus_history_id,
+                    w.urgent,
+                )
+            })
+            .collect();
+        assert_eq!(
+            windows,
+            [
+                ("11", "Mozilla Firefox", "firefox", Some(5), 3, 0, false),
+                ("10", "shell", "foot", Some(5), 3, 1, false),
+                ("12", "irc", "hexchat", Some(4), 3, 2, true),
+            ]
+        );
+        assert_eq!(state.windows[2].at, (5, 6));
+
+        assert_eq!(state.active_workspace, Some(5));
+        assert_eq!(state.active_window, "11");
+    }
+
+    #[test]
+    fn from_tree_without_focus() {
+        let tree = serde_json::from_str::<SwayNode>(
+            r#"{"id": 1, "type": "root", "nodes": [
+                {"id": 3, "type": "output", "name": "eDP-1", "nodes": [
+                    {"id": 4, "type": "workspace", "name": "1", "num": 1}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        let state = SwayState::from_tree(&tree);
+
+        assert_eq!(state.workspaces.len(), 1);
+        assert!(state.windows.is_empty());
+        assert_eq!(state.active_workspace, None);
+        assert_eq!(state.active_window, "");
+    }
+
+    #[test]
+    fn monitors_use_the_output_nodes() {
+        let tree = serde_json::from_str::<SwayNode>(TREE).unwrap();
+        let state = SwayState::from_tree(&tree);
+        // i3's GET_OUTPUTS reply, which has no ids
+        let outputs = serde_json::from_str::<Vec<SwayOutput>>(
+            r#"[
+                {"name": "xroot-0", "active": false},
+                {"name": "eDP-1", "active": true, "focused": true, "current_workspace": "1:web"},
+                {"name": "HDMI-A-1", "active": true, "current_workspace": "mail"},
+                {"name": "DP-1", "active": true, "current_workspace": "2"}
+            ]"#,
+        )
+        .unwrap();
+
+        let monitors: Vec<_> = state
+            .monitors(&outputs)
+            .into_iter()
+            .map(|m| (m.id, m.name, m.active_workspace_id, m.focused))
+            .collect();
+        assert_eq!(
+            monitors,
+            [
+                (3, "eDP-1".to_owned(), 5, true),
+                (20, "HDMI-A-1".to_owned(), 21, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use async_std::io;
+
+#[derive(Debug)]
+pub enum SwayError {
+    // Neither SWAYSOCK nor I3SOCK is set
+    MissingSocket,
+    InvalidResponse(String),
+    CommandFailed(String),
+    Io(io::Error),
+}
+
+impl Display for SwayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSocket => write!(f, "SWAYSOCK is not set"),
+            Self::InvalidResponse(err) => write!(f, "Invalid response from sway: {}", err),
+            Self::CommandFailed(err) => write!(f, "sway command failed: {}", err),
+            Self::Io(err) => write!(f, "sway socket error: {}", err),
+        }
+    }
+}
+
+impl Error for SwayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SwayError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
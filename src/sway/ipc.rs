@@ -0,0 +1,196 @@
+use std::env::var;
+
+use async_std::io::{ReadExt, WriteExt};
+use async_std::os::unix::net::UnixStream;
+
+use super::error::SwayError;
+
+// Message types of the sway IPC protocol, which is shared with i3
+pub const RUN_COMMAND: u32 = 0;
+pub const SUBSCRIBE: u32 = 2;
+pub const GET_OUTPUTS: u32 = 3;
+pub const GET_TREE: u32 = 4;
+
+// Events have the high bit of the message type set
+pub const EVENT_WORKSPACE: u32 = 0x8000_0000;
+pub const EVENT_OUTPUT: u32 = 0x8000_0001;
+pub const EVENT_MODE: u32 = 0x8000_0002;
+pub const EVENT_WINDOW: u32 = 0x8000_0003;
+
+const MAGIC: &[u8] = b"i3-ipc";
+const HEADER_SIZE: usize = MAGIC.len() + 8;
+// Anything bigger than this is a corrupt header rather than a real tree
+const MAX_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+// A connection to the sway IPC socket. Every message is the magic string followed by the payload length and
+// message type in native byte order and then the JSON payload.
+pub struct SwayIpc {
+    stream: UnixStream,
+}
+
+impl SwayIpc {
+    pub async fn connect() -> Result<Self, SwayError> {
+        let path = var("SWAYSOCK")
+            .or_else(|_| var("I3SOCK"))
+            .map_err(|_| SwayError::MissingSocket)?;
+        Ok(Self {
+            stream: UnixStream::connect(path).await?,
+        })
+    }
+
+    // Sends a single message on a new connection and returns the reply's payload.
+    pub async fn request(message_type: u32, payload: &str) -> Result<String, SwayError> {
+        let mut ipc = Self::connect().await?;
+        ipc.send(message_type, payload).await?;
+        let (reply_type, reply) = ipc.receive().await?;
+        if reply_type != message_type {
+            return Err(SwayError::InvalidResponse(format!(
+                "expected a reply of type {} but got {}",
+                message_type, reply_type
+            )));
+        }
+        Ok(reply)
+    }
+
+    pub async fn send(&mut self, message_type: u32, payload: &str) -> Result<(), SwayError> {
+        let mut message = Vec::with_capacity(HEADER_SIZE + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        self.stream.write_all(&message).await?;
+        Ok(())
+    }
+
+    // Returns the type and payload of the next message, which is either a reply or an event.
+    pub async fn receive(&mut self) -> Result<(u32, String), SwayError> {
+        let mut header = [0; HEADER_SIZE];
+        self.stream.read_exact(&mut header).await?;
+        if !header.starts_with(MAGIC) {
+            return Err(SwayError::InvalidResponse(
+                "missing i3-ipc magic".to_owned(),
+            ));
+        }
+
+        let length = u32::from_ne_bytes(header[6..10].try_into().unwrap()) as usize;
+        let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+        if length > MAX_PAYLOAD_SIZE {
+            return Err(SwayError::InvalidResponse(format!(
+                "payload of {} bytes is too large",
+                length
+            )));
+        }
+
+        let mut payload = vec![0; length];
+        self.stream.read_exact(&mut payload).await?;
+        let payload = String::from_utf8(payload)
+            .map_err(|err| SwayError::InvalidResponse(err.to_string()))?;
+        Ok((message_type, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Connects an IPC client to the other end of a socket standing in for sway.
+    fn fake_sway() -> (SwayIpc, UnixStream) {
+        let (client, sway) = UnixStream::pair().unwrap();
+        (SwayIpc { stream: client }, sway)
+    }
+
+    fn frame(message_type: u32, payload: &[u8]) -> Vec<u8> {
+        let mut message = MAGIC.to_vec();
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload);
+        message
+    }
+
+    #[async_std::test]
+    async fn send_frames_the_payload() {
+        let (mut ipc, mut sway) = fake_sway();
+        ipc.send(RUN_COMMAND, "workspace 1").await.unwrap();
+        drop(ipc);
+
+        let mut sent = Vec::new();
+        sway.read_to_end(&mut sent).await.unwrap();
+        assert_eq!(sent, frame(RUN_COMMAND, b"workspace 1"));
+    }
+
+    #[async_std::test]
+    async fn receive_reads_one_message_at_a_time() {
+        let (mut ipc, mut sway) = fake_sway();
+        let mut messages = frame(GET_TREE, br#"{"id":1}"#);
+        messages.extend(frame(EVENT_WINDOW, b""));
+        messages.extend(frame(EVENT_MODE, br#"{"change":"resize"}"#));
+        sway.write_all(&messages).await.unwrap();
+
+        assert_eq!(
+            ipc.receive().await.unwrap(),
+            (GET_TREE, r#"{"id":1}"#.to_owned())
+        );
+        assert_eq!(ipc.receive().await.unwrap(), (EVENT_WINDOW, String::new()));
+        assert_eq!(
+            ipc.receive().await.unwrap(),
+            (EVENT_MODE, r#"{"change":"resize"}"#.to_owned())
+        );
+    }
+
+    #[async_std::test]
+    async fn receive_waits_for_split_messages() {
+        let (mut ipc, mut sway) = fake_sway();
+        let message = frame(EVENT_WORKSPACE, br#"{"change":"focus"}"#);
+        let (first, rest) = message.split_at(3);
+        let rest = rest.to_vec();
+        sway.write_all(first).await.unwrap();
+        let writer = async_std::task::spawn(async move {
+            async_std::task::sleep(std::time::Duration::from_millis(20)).await;
+            sway.write_all(&rest).await.unwrap();
+            sway
+        });
+
+        assert_eq!(
+            ipc.receive().await.unwrap(),
+            (EVENT_WORKSPACE, r#"{"change":"focus"}"#.to_owned())
+        );
+        writer.await;
+    }
+
+    #[async_std::test]
+    async fn receive_rejects_bad_messages() {
+        let (mut ipc, mut sway) = fake_sway();
+        let mut message = frame(GET_TREE, b"{}");
+        message[0] = b'x';
+        sway.write_all(&message).await.unwrap();
+        assert!(matches!(
+            ipc.receive().await,
+            Err(SwayError::InvalidResponse(_))
+        ));
+
+        let (mut ipc, mut sway) = fake_sway();
+        let mut message = frame(GET_TREE, b"");
+        message[6..10].copy_from_slice(&(MAX_PAYLOAD_SIZE as u32 + 1).to_ne_bytes());
+        sway.write_all(&message).await.unwrap();
+        assert!(matches!(
+            ipc.receive().await,
+            Err(SwayError::InvalidResponse(_))
+        ));
+
+        let (mut ipc, mut sway) = fake_sway();
+        sway.write_all(&frame(GET_TREE, b"\xff\xfe")).await.unwrap();
+        assert!(matches!(
+            ipc.receive().await,
+            Err(SwayError::InvalidResponse(_))
+        ));
+    }
+
+    #[async_std::test]
+    async fn receive_fails_when_sway_goes_away() {
+        let (mut ipc, mut sway) = fake_sway();
+        let message = frame(GET_TREE, b"{}");
+        sway.write_all(&message[..message.len() - 1]).await.unwrap();
+        drop(sway);
+        assert!(matches!(ipc.receive().await, Err(SwayError::Io(_))));
+    }
+}
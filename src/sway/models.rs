@@ -0,0 +1,110 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SwayOutput {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub serial: String,
+    pub active: bool,
+    pub focused: bool,
+    pub current_workspace: Option<String>,
+}
+
+impl SwayOutput {
+    // Matches the "make model serial" description Hyprland reports.
+    pub fn description(&self) -> String {
+        [&self.make, &self.model, &self.serial]
+            .into_iter()
+            .filter(|part| !part.is_empty() && *part != "Unknown")
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SwayRect {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SwayWindowProperties {
+    pub class: Option<String>,
+}
+
+// A node of the GET_TREE reply: the root, an output, a workspace or a container
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SwayNode {
+    pub id: i64,
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub node_type: String,
+    // Workspace number, -1 for workspaces without a number
+    pub num: Option<i32>,
+    pub focused: bool,
+    // Set on workspaces holding a window which wants attention
+    pub urgent: bool,
+    // Ids of the children, most recently focused first
+    pub focus: Vec<i64>,
+    pub rect: SwayRect,
+    // 0 when not fullscreen, 1 for workspace fullscreen and 2 for global fullscreen
+    pub fullscreen_mode: u8,
+    // Only set on sway for Wayland windows
+    pub app_id: Option<String>,
+    // Only set for X11 windows
+    pub window_properties: Option<SwayWindowProperties>,
+    pub pid: Option<i64>,
+    pub nodes: Vec<SwayNode>,
+    pub floating_nodes: Vec<SwayNode>,
+}
+
+impl SwayNode {
+    pub fn is_window(&self) -> bool {
+        (self.node_type == "con" || self.node_type == "floating_con")
+            && (self.pid.is_some() || self.window_properties.is_some())
+    }
+
+    pub fn class(&self) -> String {
+        self.app_id
+            .clone()
+            .filter(|app_id| !app_id.is_empty())
+            .or_else(|| self.window_properties.as_ref()?.class.clone())
+            .unwrap_or_default()
+    }
+
+    // Children in the order they were focused, followed by any which never were.
+    pub fn children_by_focus(&self) -> Vec<&SwayNode> {
+        let mut children: Vec<&SwayNode> = self
+            .nodes
+            .iter()
+            .chain(self.floating_nodes.iter())
+            .collect();
+        children.sort_by_key(|child| {
+            self.focus
+                .iter()
+                .position(|id| *id == child.id)
+                .unwrap_or(usize::MAX)
+        });
+        children
+    }
+}
+
+// Payload of workspace and mode events, only the kind of change is used
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SwayChangeEvent {
+    pub change: String,
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct SwayCommandResult {
+    pub success: bool,
+    pub error: Option<String>,
+}
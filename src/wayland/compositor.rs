@@ -0,0 +1,834 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use async_broadcast::{InactiveReceiver, Receiver, RecvError, broadcast};
+use async_std::sync::{Arc, Mutex};
+use async_std::task;
+use futures::future::BoxFuture;
+use wayland_client::backend::ObjectId;
+use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1;
+use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::State;
+
+use super::manager::{
+    ExtWorkspace, ExtWorkspaceGroup, ExtWorkspacesState, OutputEvent, WaylandManager,
+    WaylandOutput, WaylandWindowEvent, WorkspaceEvent, ZWlrForeignTopLevel,
+};
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
+use crate::compositor::{
+    Action, Compositor, CompositorEvent, ConnectionState, MonitorInfo, WindowInfo, WorkspaceInfo,
+};
+
+// Windows, workspaces and outputs as reported over the Wayland connection
+#[derive(Default)]
+struct WaylandState {
+    windows: HashMap<ObjectId, ZWlrForeignTopLevel>,
+    workspaces: ExtWorkspacesState,
+    outputs: HashMap<ObjectId, WaylandOutput>,
+    // Most recently activated first
+    focus_history: Vec<ObjectId>,
+}
+
+impl WaylandState {
+    fn apply_window_event(&mut self, event: WaylandWindowEvent) {
+        match event {
+            WaylandWindowEvent::NewWindowWlr(window)
+            | WaylandWindowEvent::UpdatedWindowWlr(window) => {
+                if window.has_state(State::Activated) {
+                    self.focus_history.retain(|id| *id != window.id);
+                    self.focus_history.insert(0, window.id.clone());
+                } else if !self.focus_history.contains(&window.id) {
+                    self.focus_history.push(window.id.clone());
+                }
+                self.windows.insert(window.id.clone(), window);
+            }
+            WaylandWindowEvent::RemovedWindowWlr(window) => {
+                self.focus_history.retain(|id| *id != window.id);
+                self.windows.remove(&window.id);
+            }
+            // ext_foreign_toplevel_list_v1 has no outputs or requests, so only the wlr protocol is used.
+            _ => {}
+        }
+    }
+
+    fn monitor_id(&self, output: &ObjectId) -> Option<i32> {
+        self.outputs
+            .get(output)
+            .map(|output| output.global_name as i32)
+    }
+
+    // A group shown on several outputs is treated as belonging to the first one.
+    fn group_monitor_id(&self, group: &ExtWorkspaceGroup) -> i32 {
+        group
+            .outputs
+            .iter()
+            .find_map(|output| self.monitor_id(output))
+            .unwrap_or(-1)
+    }
+
+    fn group_workspaces<'a>(
+        &'a self,
+        group: &'a ExtWorkspaceGroup,
+    ) -> impl Iterator<Item = &'a ExtWorkspace> {
+        group.workspaces.iter().filter_map(|id| {
+            self.workspaces
+                .workspaces
+                .iter()
+                .find(|workspace| workspace.id == *id)
+        })
+    }
+
+    fn active_workspace_on(&self, monitor_id: i32) -> Option<i32> {
+        self.workspaces
+            .groups
+            .iter()
+            .filter(|group| self.group_monitor_id(group) == monitor_id)
+            .flat_map(|group| self.group_workspaces(group))
+            .find(|workspace| {
+                workspace
+                    .state
+                    .contains(ext_workspace_handle_v1::State::Active)
+            })
+            .map(|workspace| workspace.id.protocol_id() as i32)
+    }
+
+    // There is no focused output in the protocols, so the active workspace of the focused window's output is used.
+    fn active_workspace(&self) -> Option<i32> {
+        let focused_monitor = self
+            .window_infos()
+            .into_iter()
+            .find(|window| window.focus_history_id == 0)
+            .map(|window| window.monitor_id);
+
+        focused_monitor
+            .and_then(|monitor_id| self.active_workspace_on(monitor_id))
+            .or_else(|| {
+                self.workspaces
+                    .workspaces
+                    .iter()
+                    .find(|workspace| {
+                        workspace
+                            .state
+                            .contains(ext_workspace_handle_v1::State::Active)
+                    })
+                    .map(|workspace| workspace.id.protocol_id() as i32)
+            })
+    }
+
+    fn monitors(&self) -> Vec<MonitorInfo> {
+        let mut monitors: Vec<MonitorInfo> = self
+            .outputs
+            .values()
+            .filter(|output| !output.name.is_empty())
+            .map(|output| MonitorInfo {
+                id: output.global_name as i32,
+                name: output.name.clone(),
+                description: output.description.clone(),
+                make: output.make.clone(),
+                model: output.model.clone(),
+                active_workspace_id: self
+                    .active_workspace_on(output.global_name as i32)
+                    .unwrap_or_default(),
+                ..Default::default()
+            })
+            .collect();
+        monitors.sort_by_key(|monitor| monitor.id);
+        monitors
+    }
+
+    fn workspace_infos(&self) -> Vec<WorkspaceInfo> {
+        self.workspaces
+            .groups
+            .iter()
+            .flat_map(|group| {
+                let monitor_id = self.group_monitor_id(group);
+                self.group_workspaces(group)
+                    .enumerate()
+                    .filter(|(_, workspace)| {
+                        !workspace
+                            .state
+                            .contains(ext_workspace_handle_v1::State::Hidden)
+                    })
+                    .map(move |(index, workspace)| WorkspaceInfo {
+                        id: workspace.id.protocol_id() as i32,
+                        name: workspace.name.clone(),
+                        monitor_id,
+                        // Compositors laying their workspaces out in a grid give the column first.
+                        position: workspace
+                            .coordinates
+                            .first()
+                            .map(|position| *position as i32)
+                            .unwrap_or(index as i32),
+                        // Foreign toplevels don't say which workspace they are on.
+                        windows: None,
+                        has_fullscreen: false,
+                        urgent: workspace
+                            .state
+                            .contains(ext_workspace_handle_v1::State::Urgent),
+                    })
+            })
+            .collect()
+    }
+
+    fn window_infos(&self) -> Vec<WindowInfo> {
+        self.windows
+            .values()
+            .map(|window| {
+                let address = window.id.protocol_id().to_string();
+                WindowInfo {
+                    address,
+                    title: window.title.clone(),
+                    class: window.app_id.clone(),
+                    initial_title: window.title.clone(),
+                    initial_class: window.app_id.clone(),
+                    // Foreign toplevels don't say which workspace they are on.
+                    workspace_id: None,
+                    // A window spanning several outputs is shown in the taskbar of the first one.
+                    monitor_id: window
+                        .output
+                        .iter()
+                        .filter_map(|output| self.monitor_id(output))
+                        .min()
+                        .unwrap_or(-1),
+                    // There are no positions, keep the windows in the order they were opened.
+                    at: (0, window.id.protocol_id() as i32),
+                    focus_history_id: self
+                        .focus_history
+                        .iter()
+                        .position(|id| *id == window.id)
+                        .unwrap_or(self.focus_history.len())
+                        as i32,
+                    // Foreign toplevels have no urgent state.
+                    urgent: false,
+                }
+            })
+            .collect()
+    }
+
+    fn active_window(&self) -> String {
+        self.windows
+            .values()
+            .find(|window| window.has_state(State::Activated))
+            .map(|window| window.id.protocol_id().to_string())
+            .unwrap_or_default()
+    }
+}
+
+// Compositor backend for any compositor implementing wlr-foreign-toplevel-management, such as the wlroots based
+// ones. Workspaces come from ext-workspace when the compositor supports it.
+pub struct WaylandCompositor {
+    manager: Arc<WaylandManager>,
+    state: Mutex<WaylandState>,
+    workspaces: Arc<LatestEventValue<Vec<WorkspaceInfo>>>,
+    windows: Arc<LatestEventValue<Vec<WindowInfo>>>,
+    monitors: Arc<LatestEventValue<Vec<MonitorInfo>>>,
+    active_workspace: Arc<LatestEventValue<i32>>,
+    active_window: Arc<LatestEventValue<String>>,
+    event_receiver: InactiveReceiver<CompositorEvent>,
+}
+
+impl WaylandCompositor {
+    pub async fn new() -> Arc<Self> {
+        // No compositor events are known to the Wayland protocols, the channel only exists for listeners.
+        let (_, event_receiver) = broadcast(1);
+
+        let instance = Arc::new(Self {
+            manager: WaylandManager::instance().await,
+            state: Mutex::new(WaylandState::default()),
+            workspaces: Arc::new(LatestEventValue::new()),
+            windows: Arc::new(LatestEventValue::new()),
+            monitors: Arc::new(LatestEventValue::new()),
+            active_workspace: Arc::new(LatestEventValue::new()),
+            active_window: Arc::new(LatestEventValue::new()),
+            event_receiver: event_receiver.deactivate(),
+        });
+        {
+            let instance = instance.clone();
+            task::spawn(async move {
+                let (windows, mut window_events) = instance.manager.create_window_listener().await;
+                {
+                    let mut state = instance.state.lock().await;
+                    for window in windows {
+                        state.apply_window_event(window);
+                    }
+                }
+                instance.publish().await;
+
+                loop {
+                    match window_events.recv().await {
+                        Ok(event) => instance.state.lock().await.apply_window_event(event),
+                        Err(RecvError::Overflowed(_)) => {
+                            // Start over from the manager's state.
+                            let (windows, events) = instance.manager.create_window_listener().await;
+                            window_events = events;
+                            let mut state = instance.state.lock().await;
+                            state.windows.clear();
+                            state.focus_history.clear();
+                            for window in windows {
+                                state.apply_window_event(window);
+                            }
+                        }
+                        Err(RecvError::Closed) => return,
+                    }
+                    instance.publish().await;
+                }
+            });
+        }
+        {
+            let instance = instance.clone();
+            task::spawn(async move {
+                let (outputs, mut output_events) = instance.manager.create_output_listener().await;
+                instance.set_outputs(outputs).await;
+
+                loop {
+                    match output_events.recv().await {
+                        Ok(OutputEvent::WaylandOutputsUpdated(outputs)) => {
+                            instance.set_outputs(outputs).await
+                        }
+                        Ok(_) => {}
+                        Err(RecvError::Overflowed(_)) => {
+                            let (outputs, events) = instance.manager.create_output_listener().await;
+                            output_events = events;
+                            instance.set_outputs(outputs).await;
+                        }
+                        Err(RecvError::Closed) => return,
+                    }
+                }
+            });
+        }
+
+        {
+            let instance = instance.clone();
+            task::spawn(async move {
+                let (workspaces, mut workspace_events) =
+                    instance.manager.create_workspace_listener().await;
+                instance.state.lock().await.workspaces = workspaces;
+                instance.publish().await;
+
+                loop {
+                    match workspace_events.recv().await {
+                        Ok(WorkspaceEvent::WorkspacesUpdated(workspaces)) => {
+                            instance.state.lock().await.workspaces = workspaces
+                        }
+                        Err(RecvError::Overflowed(_)) => {
+                            let (workspaces, events) =
+                                instance.manager.create_workspace_listener().await;
+                            workspace_events = events;
+                            instance.state.lock().await.workspaces = workspaces;
+                        }
+                        Err(RecvError::Closed) => return,
+                    }
+                    instance.publish().await;
+                }
+            });
+        }
+
+        instance
+    }
+
+    async fn set_outputs(&self, outputs: Vec<WaylandOutput>) {
+        self.state.lock().await.outputs = outputs
+            .into_iter()
+            .map(|output| (output.id.clone(), output))
+            .collect();
+        self.publish().await;
+    }
+
+    // Windows and workspaces refer to the outputs and the active workspace follows the focused window, so
+    // everything is published together.
+    async fn publish(&self) {
+        let (monitors, workspaces, windows, active_workspace, active_window) = {
+            let state = self.state.lock().await;
+            (
+                state.monitors(),
+                state.workspace_infos(),
+                state.window_infos(),
+                state.active_workspace(),
+                state.active_window(),
+            )
+        };
+        self.monitors.update(monitors).await;
+        self.workspaces.update(workspaces).await;
+        self.windows.update(windows).await;
+        if let Some(active_workspace) = active_workspace {
+            self.active_workspace.update(active_workspace).await;
+        }
+        self.active_window.update(active_window).await;
+    }
+
+    async fn find_workspace(&self, id: i32) -> Result<ExtWorkspace, Box<dyn Error + Send + Sync>> {
+        self.state
+            .lock()
+            .await
+            .workspaces
+            .workspaces
+            .iter()
+            .find(|workspace| workspace.id.protocol_id() as i32 == id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown workspace {}", id).into())
+    }
+
+    async fn find_window(
+        &self,
+        address: &str,
+    ) -> Result<ZWlrForeignTopLevel, Box<dyn Error + Send + Sync>> {
+        self.state
+            .lock()
+            .await
+            .windows
+            .values()
+            .find(|window| window.id.protocol_id().to_string() == address)
+            .cloned()
+            .ok_or_else(|| format!("Unknown window {}", address).into())
+    }
+}
+
+impl Compositor for WaylandCompositor {
+    fn workspaces(&self) -> LatestEventValueListener<Vec<WorkspaceInfo>> {
+        LatestEventValueListener::new(self.workspaces.clone())
+    }
+
+    fn windows(&self) -> LatestEventValueListener<Vec<WindowInfo>> {
+        LatestEventValueListener::new(self.windows.clone())
+    }
+
+    fn monitors(&self) -> LatestEventValueListener<Vec<MonitorInfo>> {
+        LatestEventValueListener::new(self.monitors.clone())
+    }
+
+    fn active_workspace(&self) -> LatestEventValueListener<i32> {
+        LatestEventValueListener::new(self.active_workspace.clone())
+    }
+
+    fn active_window(&self) -> LatestEventValueListener<String> {
+        LatestEventValueListener::new(self.active_window.clone())
+    }
+
+    fn connection_state(&self) -> LatestEventValueListener<ConnectionState> {
+        self.manager.get_connection_state_emitter()
+    }
+
+    fn events(&self) -> Receiver<CompositorEvent> {
+        self.event_receiver.activate_cloned()
+    }
+
+    fn dispatch(&self, action: Action) -> BoxFuture<'_, Result<(), Box<dyn Error + Send + Sync>>> {
+        Box::pin(async move {
+            match action {
+                Action::FocusWindow(address) => {
+                    let window = self.find_window(&address).await?;
+                    self.manager.activate_window(&window).await
+                }
+                Action::CloseWindow(address) => {
+                    let window = self.find_window(&address).await?;
+                    self.manager.close_window(&window)
+                }
+                Action::ToggleMinimizeWindow(address) => {
+                    let window = self.find_window(&address).await?;
+                    self.manager
+                        .set_minimized(&window, !window.has_state(State::Minimized))
+                }
+                Action::ToggleMaximizeWindow(address) => {
+                    let window = self.find_window(&address).await?;
+                    self.manager
+                        .set_maximized(&window, !window.has_state(State::Maximized))
+                }
+                Action::FocusWorkspace(id) => {
+                    let workspace = self.find_workspace(id).await?;
+                    self.manager.activate_workspace(&workspace).await
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use wayland_client::protocol::wl_callback::{self, WlCallback};
+    use wayland_client::{Connection, Dispatch, EventQueue, Proxy, QueueHandle};
+    use wayland_protocols::ext::workspace::v1::client::ext_workspace_handle_v1::{
+        ExtWorkspaceHandleV1, WorkspaceCapabilities,
+    };
+    use wayland_protocols_wlr::foreign_toplevel::v1::client::zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1;
+
+    use super::*;
+
+    // Hands out object ids from the client side of a socket nobody answers, nothing is flushed so the requests
+    // creating them are never sent.
+    struct Objects {
+        connection: Connection,
+        queue: EventQueue<Objects>,
+        _compositor: UnixStream,
+    }
+
+    impl Dispatch<WlCallback, ()> for Objects {
+        fn event(
+            _state: &mut Self,
+            _proxy: &WlCallback,
+            _event: wl_callback::Event,
+            _data: &(),
+            _conn: &Connection,
+            _qhandle: &QueueHandle<Self>,
+        ) {
+        }
+    }
+
+    impl Objects {
+        fn new() -> Self {
+            let (client, compositor) = UnixStream::pair().unwrap();
+            let connection = Connection::from_socket(client).unwrap();
+            Self {
+                queue: connection.new_event_queue(),
+                connection,
+                _compositor: compositor,
+            }
+        }
+
+        fn id(&self) -> ObjectId {
+            let qh = self.queue.handle();
+            self.connection.display().sync(&qh, ()).id()
+        }
+
+        fn inert<P: Proxy>(&self) -> P {
+            P::inert(self.connection.backend().downgrade())
+        }
+
+        fn output(&self, global_name: u32, name: &str) -> WaylandOutput {
+            WaylandOutput {
+                id: self.id(),
+                global_name,
+                name: name.to_owned(),
+                description: format!("{} description", name),
+                make: "Acme".to_owned(),
+                model: "Panel".to_owned(),
+            }
+        }
+
+        fn window(
+            &self,
+            title: &str,
+            outputs: &[&WaylandOutput],
+            activated: bool,
+        ) -> ZWlrForeignTopLevel {
+            let mut window = ZWlrForeignTopLevel::new(&self.inert::<ZwlrForeignToplevelHandleV1>());
+            window.id = self.id();
+            window.title = title.to_owned();
+            window.app_id = format!("{}.desktop", title);
+            window.output = outputs.iter().map(|output| output.id.clone()).collect();
+            if activated {
+                window.state = (State::Activated as u32).to_ne_bytes().to_vec();
+            }
+            window
+        }
+
+        fn workspace(
+            &self,
+            name: &str,
+            coordinates: &[u32],
+            state: ext_workspace_handle_v1::State,
+        ) -> ExtWorkspace {
+            ExtWorkspace {
+                id: self.id(),
+                handle: self.inert::<ExtWorkspaceHandleV1>(),
+                name: name.to_owned(),
+                coordinates: coordinates.to_vec(),
+                state,
+                capabilities: WorkspaceCapabilities::Activate,
+            }
+        }
+
+        fn group(
+            &self,
+            outputs: &[&WaylandOutput],
+            workspaces: &[&ExtWorkspace],
+        ) -> ExtWorkspaceGroup {
+            ExtWorkspaceGroup {
+                id: self.id(),
+                outputs: outputs.iter().map(|output| output.id.clone()).collect(),
+                workspaces: workspaces
+                    .iter()
+                    .map(|workspace| workspace.id.clone())
+                    .collect(),
+            }
+        }
+    }
+
+    fn with_outputs(outputs: &[&WaylandOutput]) -> WaylandState {
+        WaylandState {
+            outputs: outputs
+                .iter()
+                .map(|output| (output.id.clone(), (*output).clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    // Title, monitor and focus history of each window, ordered by title
+    fn windows(state: &WaylandState) -> Vec<(String, i32, i32)> {
+        let mut windows: Vec<_> = state
+            .window_infos()
+            .into_iter()
+            .map(|w| (w.title, w.monitor_id, w.focus_history_id))
+            .collect();
+        windows.sort();
+        windows
+    }
+
+    #[test]
+    fn focus_order_follows_activation() {
+        let objects = Objects::new();
+        let output = objects.output(40, "DP-1");
+        let mut state = with_outputs(&[&output]);
+        let editor = objects.window("editor", &[&output], true);
+        let shell = objects.window("shell", &[&output], false);
+        let browser = objects.window("browser", &[&output], false);
+        for window in [&editor, &shell, &browser] {
+            state.apply_window_event(WaylandWindowEvent::NewWindowWlr(window.clone()));
+        }
+        assert_eq!(
+            windows(&state),
+            [
+                ("browser".to_owned(), 40, 2),
+                ("editor".to_owned(), 40, 0),
+                ("shell".to_owned(), 40, 1),
+            ]
+        );
+        assert_eq!(state.active_window(), editor.id.protocol_id().to_string());
+
+        let mut activated_browser = browser.clone();
+        activated_browser.state = (State::Activated as u32).to_ne_bytes().to_vec();
+        let mut deactivated_editor = editor.clone();
+        deactivated_editor.state.clear();
+        state.apply_window_event(WaylandWindowEvent::UpdatedWindowWlr(deactivated_editor));
+        state.apply_window_event(WaylandWindowEvent::UpdatedWindowWlr(activated_browser));
+        assert_eq!(
+            windows(&state),
+            [
+                ("browser".to_owned(), 40, 0),
+                ("editor".to_owned(), 40, 1),
+                ("shell".to_owned(), 40, 2),
+            ]
+        );
+        assert_eq!(state.active_window(), browser.id.protocol_id().to_string());
+
+        state.apply_window_event(WaylandWindowEvent::RemovedWindowWlr(editor));
+        assert_eq!(
+            windows(&state),
+            [("browser".to_owned(), 40, 0), ("shell".to_owned(), 40, 1)]
+        );
+        assert_eq!(state.focus_history.len(), 2);
+    }
+
+    #[test]
+    fn windows_are_shown_on_their_first_output() {
+        let objects = Objects::new();
+        let left = objects.output(40, "DP-1");
+        let right = objects.output(41, "DP-2");
+        let unbound = objects.output(42, "DP-3");
+        let mut state = with_outputs(&[&left, &right]);
+        for window in [
+            objects.window("both", &[&right, &left], false),
+            objects.window("right", &[&right], false),
+            objects.window("unbound", &[&unbound], false),
+            objects.window("nowhere", &[], false),
+        ] {
+            state.apply_window_event(WaylandWindowEvent::NewWindowWlr(window));
+        }
+
+        assert_eq!(
+            windows(&state),
+            [
+                ("both".to_owned(), 40, 0),
+                ("nowhere".to_owned(), -1, 3),
+                ("right".to_owned(), 41, 1),
+                ("unbound".to_owned(), -1, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn windows_are_on_every_workspace() {
+        let objects = Objects::new();
+        let output = objects.output(40, "DP-1");
+        let mut state = with_outputs(&[&output]);
+        let editor = objects.window("editor", &[&output], true);
+        state.apply_window_event(WaylandWindowEvent::NewWindowWlr(editor));
+
+        let windows = state.window_infos();
+        assert_eq!(windows[0].workspace_id, None);
+        // The per monitor active window filters on the monitor's active workspace.
+        assert!(windows[0].is_on_workspace(7));
+        assert!(windows[0].is_on_workspace(0));
+    }
+
+    // Two groups of workspaces, one on DP-1 and one shown on DP-2 and an output this client doesn't know of
+    struct Workspaces {
+        objects: Objects,
+        left: WaylandOutput,
+        right: WaylandOutput,
+        unbound: WaylandOutput,
+        state: WaylandState,
+    }
+
+    impl Workspaces {
+        fn new() -> Self {
+            let objects = Objects::new();
+            let left = objects.output(40, "DP-1");
+            let right = objects.output(41, "DP-2");
+            let unbound = objects.output(42, "DP-3");
+            let mut state = with_outputs(&[&left, &right]);
+
+            let one = objects.workspace("one", &[], ext_workspace_handle_v1::State::Active);
+            let two = objects.workspace("two", &[], ext_workspace_handle_v1::State::Hidden);
+            let three = objects.workspace("three", &[], ext_workspace_handle_v1::State::Urgent);
+            let web = objects.workspace("web", &[3, 1], ext_workspace_handle_v1::State::Active);
+            let mail = objects.workspace("mail", &[1, 0], ext_workspace_handle_v1::State::empty());
+            state.workspaces.groups = vec![
+                objects.group(&[&left], &[&one, &two, &three]),
+                objects.group(&[&unbound, &right], &[&web, &mail]),
+            ];
+            state.workspaces.workspaces = vec![one, two, three, web, mail];
+
+            Self {
+                objects,
+                left,
+                right,
+                unbound,
+                state,
+            }
+        }
+
+        fn id(&self, name: &str) -> i32 {
+            self.state
+                .workspaces
+                .workspaces
+                .iter()
+                .find(|workspace| workspace.name == name)
+                .map(|workspace| workspace.id.protocol_id() as i32)
+                .unwrap()
+        }
+
+        fn focus(&mut self, output: &WaylandOutput) {
+            let window = self.objects.window("focused", &[output], true);
+            self.state
+                .apply_window_event(WaylandWindowEvent::NewWindowWlr(window));
+        }
+    }
+
+    #[test]
+    fn workspaces_follow_their_group() {
+        let workspaces = Workspaces::new();
+        let infos: Vec<_> = workspaces
+            .state
+            .workspace_infos()
+            .into_iter()
+            .map(|w| (w.id, w.name, w.monitor_id, w.position, w.windows, w.urgent))
+            .collect();
+
+        // Hidden workspaces are left out but still count towards the position of the ones after them.
+        assert_eq!(
+            infos,
+            [
+                (workspaces.id("one"), "one".to_owned(), 40, 0, None, false),
+                (
+                    workspaces.id("three"),
+                    "three".to_owned(),
+                    40,
+                    2,
+                    None,
+                    true
+                ),
+                (workspaces.id("web"), "web".to_owned(), 41, 3, None, false),
+                (workspaces.id("mail"), "mail".to_owned(), 41, 1, None, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_are_on_their_first_known_output() {
+        let workspaces = Workspaces::new();
+        let state = &workspaces.state;
+        let monitors: Vec<_> = state
+            .workspaces
+            .groups
+            .iter()
+            .map(|group| state.group_monitor_id(group))
+            .collect();
+        assert_eq!(monitors, [40, 41]);
+
+        let nowhere = workspaces.objects.group(&[&workspaces.unbound], &[]);
+        assert_eq!(state.group_monitor_id(&nowhere), -1);
+    }
+
+    #[test]
+    fn active_workspace_per_monitor() {
+        let workspaces = Workspaces::new();
+        let state = &workspaces.state;
+        assert_eq!(state.active_workspace_on(40), Some(workspaces.id("one")));
+        assert_eq!(state.active_workspace_on(41), Some(workspaces.id("web")));
+        assert_eq!(state.active_workspace_on(42), None);
+
+        let monitors: Vec<_> = state
+            .monitors()
+            .into_iter()
+            .map(|m| (m.id, m.name, m.active_workspace_id))
+            .collect();
+        assert_eq!(
+            monitors,
+            [
+                (40, "DP-1".to_owned(), workspaces.id("one")),
+                (41, "DP-2".to_owned(), workspaces.id("web")),
+            ]
+        );
+    }
+
+    #[test]
+    fn active_workspace_follows_the_focused_window() {
+        // Without a focused window the first active workspace is used.
+        let mut workspaces = Workspaces::new();
+        assert_eq!(
+            workspaces.state.active_workspace(),
+            Some(workspaces.id("one"))
+        );
+
+        let right = workspaces.right.clone();
+        workspaces.focus(&right);
+        assert_eq!(
+            workspaces.state.active_workspace(),
+            Some(workspaces.id("web"))
+        );
+
+        let left = workspaces.left.clone();
+        workspaces.focus(&left);
+        assert_eq!(
+            workspaces.state.active_workspace(),
+            Some(workspaces.id("one"))
+        );
+
+        // A window on an output without workspaces falls back to the first active workspace as well.
+        let mut workspaces = Workspaces::new();
+        let unbound = workspaces.unbound.clone();
+        workspaces.focus(&unbound);
+        assert_eq!(
+            workspaces.state.active_workspace(),
+            Some(workspaces.id("one"))
+        );
+    }
+
+    #[test]
+    fn workspace_requests_need_the_capability() {
+        let workspaces = Workspaces::new();
+        let one = &workspaces.state.workspaces.workspaces[0];
+        assert!(
+            one.check_capability(WorkspaceCapabilities::Activate, "activated")
+                .is_ok()
+        );
+        assert_eq!(
+            one.check_capability(WorkspaceCapabilities::Remove, "removed")
+                .unwrap_err()
+                .to_string(),
+            "Workspace one can't be removed"
+        );
+    }
+}
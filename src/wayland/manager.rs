@@ -0,0 +1,1091 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    ops::DerefMut,
+    thread,
+};
+
+use async_broadcast::{InactiveReceiver, Receiver, Sender, broadcast};
+use async_std::{
+    sync::{Arc, Mutex, RwLock, Weak},
+    task,
+};
+use log::{debug, error, info, trace};
+use wayland_client::WEnum;
+use wayland_client::{
+    Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle,
+    backend::ObjectId,
+    event_created_child,
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry,
+        wl_seat::WlSeat,
+    },
+};
+use wayland_protocols::ext::foreign_toplevel_list::v1::client::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use wayland_protocols::ext::workspace::v1::client::{
+    ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
+    ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
+    ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+};
+use wayland_protocols_wlr::{
+    foreign_toplevel::v1::client::{
+        zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+        zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+    },
+    output_management::v1::client::{
+        zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+        zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+        zwlr_output_mode_v1::ZwlrOutputModeV1,
+    },
+};
+
+use crate::compositor::ConnectionState;
+use crate::compositor::latest_value::{LatestEventValue, LatestEventValueListener};
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum WaylandWindowEvent {
+    NewWindowExt(ExtForeignToplevel),
+    NewWindowWlr(ZWlrForeignTopLevel),
+    UpdatedWindowExt(ExtForeignToplevel),
+    UpdatedWindowWlr(ZWlrForeignTopLevel),
+    RemovedWindowExt(ExtForeignToplevel),
+    RemovedWindowWlr(ZWlrForeignTopLevel),
+}
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub enum OutputEvent {
+    OutputsUpdated(Vec<Output>),
+    WaylandOutputsUpdated(Vec<WaylandOutput>),
+}
+
+#[derive(Clone)]
+pub enum WorkspaceEvent {
+    WorkspacesUpdated(ExtWorkspacesState),
+}
+
+// A workspace from the ext-workspace protocol
+#[derive(Clone)]
+pub struct ExtWorkspace {
+    // Raw wayland proxy ID
+    pub id: ObjectId,
+    // Used to send requests for the workspace
+    pub handle: ExtWorkspaceHandleV1,
+    pub name: String,
+    // Position of the workspace in its group, in as many dimensions as the compositor lays them out in
+    pub coordinates: Vec<u32>,
+    pub state: ext_workspace_handle_v1::State,
+    pub capabilities: ext_workspace_handle_v1::WorkspaceCapabilities,
+}
+
+impl ExtWorkspace {
+    fn new(handle: &ExtWorkspaceHandleV1) -> Self {
+        Self {
+            id: handle.id(),
+            handle: handle.clone(),
+            name: String::new(),
+            coordinates: Vec::new(),
+            state: ext_workspace_handle_v1::State::empty(),
+            capabilities: ext_workspace_handle_v1::WorkspaceCapabilities::empty(),
+        }
+    }
+
+    // Fails when the compositor doesn't allow the request on this workspace, request is how it reads in the error,
+    // e.g. "activated".
+    pub fn check_capability(
+        &self,
+        capability: ext_workspace_handle_v1::WorkspaceCapabilities,
+        request: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.capabilities.contains(capability) {
+            Ok(())
+        } else {
+            Err(format!("Workspace {} can't be {}", self.name, request).into())
+        }
+    }
+}
+
+// Workspaces grouped together, usually all the workspaces of one output
+#[derive(Clone)]
+pub struct ExtWorkspaceGroup {
+    // Raw wayland proxy ID
+    pub id: ObjectId,
+    // wl_outputs the group's workspaces are shown on
+    pub outputs: Vec<ObjectId>,
+    // In the order they were added to the group
+    pub workspaces: Vec<ObjectId>,
+}
+
+#[derive(Clone, Default)]
+pub struct ExtWorkspacesState {
+    pub groups: Vec<ExtWorkspaceGroup>,
+    pub workspaces: Vec<ExtWorkspace>,
+}
+
+// A wl_output bound by this client, which foreign toplevels refer to when entering or leaving an output
+#[derive(Clone, Debug)]
+pub struct WaylandOutput {
+    pub id: ObjectId,
+    // Name of the wl_output global, stays the same for as long as the output is connected
+    pub global_name: u32,
+    // Connector name, e.g. DP-1
+    pub name: String,
+    pub description: String,
+    pub make: String,
+    pub model: String,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Default)]
+pub struct Output {
+    pub wl_output_id: u32,
+    pub name: String,
+    pub description: String,
+    pub size: [i32; 2],
+    pub mode: String,
+    pub enabled: bool,
+    pub current_mode: String,
+    pub position: [i32; 2],
+    pub transform: String,
+    pub scale: f64,
+    pub make: String,
+    pub model: String,
+    pub serial_number: String,
+    pub adaptive_sync: bool,
+}
+
+#[allow(dead_code)]
+pub struct WaylandManager {
+    // None when there is no Wayland display to connect to
+    connection: Option<Connection>,
+    connection_state: Arc<LatestEventValue<ConnectionState>>,
+    seat: Arc<RwLock<Option<WlSeat>>>,
+    workspace_manager: Arc<RwLock<Option<ExtWorkspaceManagerV1>>>,
+    workspace_event_receiver: InactiveReceiver<WorkspaceEvent>,
+    workspaces_state: Arc<RwLock<ExtWorkspacesState>>,
+    window_event_receiver: InactiveReceiver<WaylandWindowEvent>,
+    windows_state: Arc<RwLock<WaylandWindowsState>>,
+    output_event_receiver: InactiveReceiver<OutputEvent>,
+    outputs_state: Arc<RwLock<HashMap<ObjectId, Output>>>,
+    wayland_outputs: Arc<RwLock<HashMap<ObjectId, WaylandOutput>>>,
+}
+
+#[allow(dead_code)]
+impl WaylandManager {
+    pub async fn instance() -> Arc<Self> {
+        static INSTANCE: Mutex<Weak<WaylandManager>> = Mutex::new(Weak::new());
+
+        let mut mutex_guard = INSTANCE.lock().await;
+        match mutex_guard.upgrade() {
+            Some(instance) => instance,
+            None => {
+                let instance = Self::new();
+                *mutex_guard = Arc::downgrade(&instance);
+                instance
+            }
+        }
+    }
+
+    fn new() -> Arc<Self> {
+        let (mut window_event_sender, window_event_receiver) = broadcast(1024);
+        window_event_sender.set_overflow(true);
+        window_event_sender.set_await_active(false);
+
+        let windows_state = Arc::new(RwLock::new(WaylandWindowsState {
+            ext_windows: HashMap::new(),
+            zwlr_windows: HashMap::new(),
+        }));
+
+        let (mut output_event_sender, output_event_receiver) = broadcast(1024);
+        output_event_sender.set_overflow(true);
+        output_event_sender.set_await_active(false);
+
+        let outputs_state = Arc::new(RwLock::new(HashMap::new()));
+        let wayland_outputs = Arc::new(RwLock::new(HashMap::new()));
+        let seat = Arc::new(RwLock::new(None));
+        let connection_state = Arc::new(LatestEventValue::new());
+        let connection = match Connection::connect_to_env() {
+            Ok(connection) => Some(connection),
+            Err(err) => {
+                error!("Failed to connect to the Wayland display: {}", err);
+                None
+            }
+        };
+
+        let (mut workspace_event_sender, workspace_event_receiver) = broadcast(64);
+        workspace_event_sender.set_overflow(true);
+        workspace_event_sender.set_await_active(false);
+        let workspace_manager = Arc::new(RwLock::new(None));
+        let workspaces_state = Arc::new(RwLock::new(ExtWorkspacesState::default()));
+
+        let new_instance = Arc::new(Self {
+            connection: connection.clone(),
+            connection_state: connection_state.clone(),
+            seat: seat.clone(),
+            workspace_manager: workspace_manager.clone(),
+            workspace_event_receiver: workspace_event_receiver.deactivate(),
+            workspaces_state: workspaces_state.clone(),
+            window_event_receiver: window_event_receiver.deactivate(),
+            windows_state: windows_state.clone(),
+            output_event_receiver: output_event_receiver.deactivate(),
+            outputs_state: outputs_state.clone(),
+            wayland_outputs: wayland_outputs.clone(),
+        });
+
+        let Some(connection) = connection else {
+            task::block_on(connection_state.update(ConnectionState::Disconnected));
+            return new_instance;
+        };
+
+        // Dispatching blocks until the compositor sends something, so it gets a thread of its own rather than
+        // holding one of the async executor's.
+        thread::spawn(move || {
+            let mut queue = connection.new_event_queue();
+            connection.display().get_registry(&queue.handle(), ());
+
+            let mut state = WaylandDispatchReceiver {
+                seat,
+                workspace_manager,
+                workspace_event_sender,
+                workspaces_state,
+                pending_workspaces: ExtWorkspacesState::default(),
+                window_event_sender,
+                windows_state,
+                pending_zwlr_windows: HashMap::new(),
+                output_event_sender,
+                outputs_state,
+                wayland_outputs,
+            };
+
+            if let Err(err) = Self::dispatch_events(&mut queue, &mut state, &connection_state) {
+                error!("Lost the Wayland connection: {}", err);
+            }
+            task::block_on(connection_state.update(ConnectionState::Disconnected));
+        });
+
+        new_instance
+    }
+
+    // Only returns once the connection fails.
+    fn dispatch_events(
+        queue: &mut EventQueue<WaylandDispatchReceiver>,
+        state: &mut WaylandDispatchReceiver,
+        connection_state: &LatestEventValue<ConnectionState>,
+    ) -> Result<(), DispatchError> {
+        // The globals are bound by the first roundtrip.
+        queue.roundtrip(state)?;
+        info!("Connected to the Wayland display");
+        task::block_on(connection_state.update(ConnectionState::Connected));
+
+        loop {
+            queue.blocking_dispatch(state)?;
+        }
+    }
+
+    pub fn get_connection_state_emitter(&self) -> LatestEventValueListener<ConnectionState> {
+        LatestEventValueListener::new(self.connection_state.clone())
+    }
+
+    // Returns the currently known window states as a vec of WaylandWindowEvents (just NewWindow* events) and a receiver for future events.
+    pub async fn create_window_listener(
+        &self,
+    ) -> (Vec<WaylandWindowEvent>, Receiver<WaylandWindowEvent>) {
+        let windows_state = self.windows_state.read().await;
+        let mut events: Vec<WaylandWindowEvent> = windows_state
+            .ext_windows
+            .values()
+            .map(|w| WaylandWindowEvent::NewWindowExt(w.clone()))
+            .collect();
+        events.extend(
+            windows_state
+                .zwlr_windows
+                .values()
+                .map(|w| WaylandWindowEvent::NewWindowWlr(w.clone())),
+        );
+        (events, self.window_event_receiver.activate_cloned())
+    }
+
+    // Returns the currently bound wl_outputs and a receiver for future changes to them.
+    pub async fn create_output_listener(&self) -> (Vec<WaylandOutput>, Receiver<OutputEvent>) {
+        let wayland_outputs = self.wayland_outputs.read().await;
+        (
+            wayland_outputs.values().cloned().collect(),
+            self.output_event_receiver.activate_cloned(),
+        )
+    }
+
+    pub async fn activate_window(
+        &self,
+        window: &ZWlrForeignTopLevel,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self.seat.read().await.as_ref() {
+            Some(seat) => window.handle.activate(seat),
+            None => return Err("The compositor has no seat to activate the window with".into()),
+        }
+        self.flush()
+    }
+
+    pub fn close_window(
+        &self,
+        window: &ZWlrForeignTopLevel,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        window.handle.close();
+        self.flush()
+    }
+
+    pub fn set_minimized(
+        &self,
+        window: &ZWlrForeignTopLevel,
+        minimized: bool,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if minimized {
+            window.handle.set_minimized();
+        } else {
+            window.handle.unset_minimized();
+        }
+        self.flush()
+    }
+
+    pub fn set_maximized(
+        &self,
+        window: &ZWlrForeignTopLevel,
+        maximized: bool,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if maximized {
+            window.handle.set_maximized();
+        } else {
+            window.handle.unset_maximized();
+        }
+        self.flush()
+    }
+
+    // Returns the workspaces as of the compositor's last done event and a receiver for future changes to them.
+    pub async fn create_workspace_listener(
+        &self,
+    ) -> (ExtWorkspacesState, Receiver<WorkspaceEvent>) {
+        let workspaces_state = self.workspaces_state.read().await;
+        (
+            workspaces_state.clone(),
+            self.workspace_event_receiver.activate_cloned(),
+        )
+    }
+
+    pub async fn activate_workspace(
+        &self,
+        workspace: &ExtWorkspace,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        workspace.check_capability(
+            ext_workspace_handle_v1::WorkspaceCapabilities::Activate,
+            "activated",
+        )?;
+        workspace.handle.activate();
+        self.commit_workspaces().await
+    }
+
+    pub async fn deactivate_workspace(
+        &self,
+        workspace: &ExtWorkspace,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        workspace.check_capability(
+            ext_workspace_handle_v1::WorkspaceCapabilities::Deactivate,
+            "deactivated",
+        )?;
+        workspace.handle.deactivate();
+        self.commit_workspaces().await
+    }
+
+    pub async fn remove_workspace(
+        &self,
+        workspace: &ExtWorkspace,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        workspace.check_capability(
+            ext_workspace_handle_v1::WorkspaceCapabilities::Remove,
+            "removed",
+        )?;
+        workspace.handle.remove();
+        self.commit_workspaces().await
+    }
+
+    // Workspace requests are only applied by the compositor once they are committed.
+    async fn commit_workspaces(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self.workspace_manager.read().await.as_ref() {
+            Some(workspace_manager) => workspace_manager.commit(),
+            None => return Err("The compositor doesn't support ext-workspace".into()),
+        }
+        self.flush()
+    }
+
+    // The dispatch loop only flushes once the compositor sends something, so requests are flushed right away.
+    fn flush(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match &self.connection {
+            Some(connection) => connection.flush()?,
+            None => return Err("Not connected to a Wayland display".into()),
+        }
+        Ok(())
+    }
+}
+
+// Represents a window from the ExtForeignTopLevel protocol
+#[derive(Clone)]
+pub struct ExtForeignToplevel {
+    // Raw wayland proxy ID
+    pub id: ObjectId,
+    pub title: String,
+    pub app_id: String,
+    // Identifier from the protocol message
+    pub identifier: String,
+}
+
+impl ExtForeignToplevel {
+    pub fn new(id: ObjectId) -> Self {
+        Self {
+            id,
+            title: String::default(),
+            app_id: String::default(),
+            identifier: String::default(),
+        }
+    }
+}
+
+// Represents a window from the ZWlrForeignTopLevel protocol
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct ZWlrForeignTopLevel {
+    // Raw wayland proxy ID
+    pub id: ObjectId,
+    // Used to send requests for the window
+    pub handle: ZwlrForeignToplevelHandleV1,
+    pub title: String,
+    pub app_id: String,
+    // wl_outputs the window is shown on
+    pub output: HashSet<ObjectId>,
+    // Array of zwlr_foreign_toplevel_handle_v1 states in native byte order
+    pub state: Vec<u8>,
+    pub parent_id: Option<ObjectId>,
+}
+
+impl ZWlrForeignTopLevel {
+    pub fn new(handle: &ZwlrForeignToplevelHandleV1) -> Self {
+        Self {
+            id: handle.id(),
+            handle: handle.clone(),
+            title: String::default(),
+            app_id: String::default(),
+            output: HashSet::new(),
+            state: Vec::new(),
+            parent_id: None,
+        }
+    }
+
+    pub fn has_state(&self, state: zwlr_foreign_toplevel_handle_v1::State) -> bool {
+        self.state
+            .chunks_exact(4)
+            .any(|value| u32::from_ne_bytes(value.try_into().unwrap()) == state as u32)
+    }
+}
+
+struct WaylandDispatchReceiver {
+    seat: Arc<RwLock<Option<WlSeat>>>,
+    workspace_manager: Arc<RwLock<Option<ExtWorkspaceManagerV1>>>,
+    workspace_event_sender: Sender<WorkspaceEvent>,
+    // Last state sent to listeners, updated on the manager's done event
+    workspaces_state: Arc<RwLock<ExtWorkspacesState>>,
+    // Workspaces with the changes since the last done event
+    pending_workspaces: ExtWorkspacesState,
+    windows_state: Arc<RwLock<WaylandWindowsState>>,
+    window_event_sender: Sender<WaylandWindowEvent>,
+    // Windows with changes which aren't applied until the compositor sends done, and whether they are new
+    pending_zwlr_windows: HashMap<ObjectId, (ZWlrForeignTopLevel, bool)>,
+    outputs_state: Arc<RwLock<HashMap<ObjectId, Output>>>,
+    output_event_sender: Sender<OutputEvent>,
+    wayland_outputs: Arc<RwLock<HashMap<ObjectId, WaylandOutput>>>,
+}
+
+struct WaylandWindowsState {
+    ext_windows: HashMap<ObjectId, ExtForeignToplevel>,
+    zwlr_windows: HashMap<ObjectId, ZWlrForeignTopLevel>,
+}
+
+impl Dispatch<ExtForeignToplevelListV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ExtForeignToplevelListV1,
+        event: <ExtForeignToplevelListV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_foreign_toplevel_list_v1::Event::Toplevel { toplevel: _ } => {}
+            // task::block_on( async {
+            //     state.wayland_windows_state.write().await.ext_windows.insert(toplevel.id(), ExtForeignToplevel::new(toplevel.id()));
+            // }),
+            ext_foreign_toplevel_list_v1::Event::Finished => {}
+            _ => {}
+        }
+    }
+
+    event_created_child!(WaylandDispatchReceiver, ExtForeignToplevelListV1, [
+        _ => ( ExtForeignToplevelHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ExtForeignToplevelHandleV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        handle: &ExtForeignToplevelHandleV1,
+        event: <ExtForeignToplevelHandleV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        // Window, is_new, is_deleted
+        static WINDOW_STATE: Mutex<(Option<ExtForeignToplevel>, bool, bool)> =
+            Mutex::new((None, false, false));
+        debug!("Ext window event received");
+
+        task::block_on(async {
+            let mut window_state = WINDOW_STATE.lock().await;
+            let (window_opt, is_new, is_deleted) = window_state.deref_mut();
+
+            if window_opt.is_none() {
+                *window_opt = state
+                    .windows_state
+                    .read()
+                    .await
+                    .ext_windows
+                    .get(&handle.id())
+                    .cloned();
+                *is_new = window_opt.is_none();
+                *is_deleted = false;
+                if window_opt.is_none() {
+                    *window_opt = Some(ExtForeignToplevel::new(handle.id()));
+                }
+            }
+
+            let window = window_opt.as_mut().unwrap();
+            let mut is_done = false;
+
+            match event {
+                ext_foreign_toplevel_handle_v1::Event::Title { title } => window.title = title,
+                ext_foreign_toplevel_handle_v1::Event::AppId { app_id } => window.app_id = app_id,
+                ext_foreign_toplevel_handle_v1::Event::Identifier { identifier } => {
+                    window.identifier = identifier
+                }
+                ext_foreign_toplevel_handle_v1::Event::Closed => *is_deleted = true,
+                ext_foreign_toplevel_handle_v1::Event::Done => is_done = true,
+                _ => {}
+            }
+
+            // Hyprland has a bug where it will not send done except for new windows.
+            if !*is_new || is_done {
+                let event = {
+                    if *is_new {
+                        WaylandWindowEvent::NewWindowExt(window.clone())
+                    } else if *is_deleted {
+                        WaylandWindowEvent::RemovedWindowExt(window.clone())
+                    } else {
+                        WaylandWindowEvent::UpdatedWindowExt(window.clone())
+                    }
+                };
+                // To avoid race conditions, we lock the writer before sending to the broadcaster.
+                // When creating a new receiver for the broadcast, we lock the state for reading, copy everything out and create a new receiver, then unlock it.
+                // In combination this effectively means that we can ensure no messages are missed.
+                let mut writer = state.windows_state.write().await;
+                state
+                    .window_event_sender
+                    .broadcast_direct(event)
+                    .await
+                    .unwrap_or_default();
+                writer.ext_windows.insert(handle.id(), window.clone());
+                *window_opt = None;
+            }
+        });
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrForeignToplevelManagerV1,
+        event: <ZwlrForeignToplevelManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel: _ } => {}
+            zwlr_foreign_toplevel_manager_v1::Event::Finished => {
+                debug!("Top level manager finished")
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(WaylandDispatchReceiver, ZwlrForeignToplevelManagerV1, [
+        _ => ( ZwlrForeignToplevelHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: <ZwlrForeignToplevelHandleV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        task::block_on(async {
+            if !state.pending_zwlr_windows.contains_key(&handle.id()) {
+                let window = state
+                    .windows_state
+                    .read()
+                    .await
+                    .zwlr_windows
+                    .get(&handle.id())
+                    .cloned();
+                let is_new = window.is_none();
+                let window = window.unwrap_or_else(|| ZWlrForeignTopLevel::new(handle));
+                state
+                    .pending_zwlr_windows
+                    .insert(handle.id(), (window, is_new));
+            }
+
+            let (window, is_new) = state.pending_zwlr_windows.get_mut(&handle.id()).unwrap();
+
+            match event {
+                zwlr_foreign_toplevel_handle_v1::Event::Title { title } => window.title = title,
+                zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => window.app_id = app_id,
+                zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => {
+                    window.output.insert(output.id());
+                }
+                zwlr_foreign_toplevel_handle_v1::Event::OutputLeave { output } => {
+                    window.output.remove(&output.id());
+                }
+                zwlr_foreign_toplevel_handle_v1::Event::Parent { parent } => {
+                    window.parent_id = parent.map(|p| p.id())
+                }
+                zwlr_foreign_toplevel_handle_v1::Event::State { state } => window.state = state,
+                zwlr_foreign_toplevel_handle_v1::Event::Done => {
+                    let (window, is_new) = state.pending_zwlr_windows.remove(&handle.id()).unwrap();
+                    let event = if is_new {
+                        WaylandWindowEvent::NewWindowWlr(window.clone())
+                    } else {
+                        WaylandWindowEvent::UpdatedWindowWlr(window.clone())
+                    };
+                    // To avoid race conditions, we lock the writer before sending to the broadcaster.
+                    // When creating a new receiver for the broadcast, we lock the state for reading, copy everything out and create a new receiver, then unlock it.
+                    // In combination this effectively means that we can ensure no messages are missed.
+                    let mut writer = state.windows_state.write().await;
+                    state
+                        .window_event_sender
+                        .broadcast_direct(event)
+                        .await
+                        .unwrap_or_default();
+                    writer.zwlr_windows.insert(handle.id(), window);
+                }
+                // No more events are sent for the handle after closed, so there is no done to wait for.
+                zwlr_foreign_toplevel_handle_v1::Event::Closed => {
+                    trace!("{0}: Closed", handle.id());
+                    let is_new = *is_new;
+                    let (window, _) = state.pending_zwlr_windows.remove(&handle.id()).unwrap();
+                    if !is_new {
+                        let mut writer = state.windows_state.write().await;
+                        state
+                            .window_event_sender
+                            .broadcast_direct(WaylandWindowEvent::RemovedWindowWlr(window))
+                            .await
+                            .unwrap_or_default();
+                        writer.zwlr_windows.remove(&handle.id());
+                    }
+                    handle.destroy();
+                }
+                _ => {}
+            }
+        });
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _connection: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => {
+                trace!("New global: {0}: {1} version={2}", name, interface, version);
+                match interface.as_str() {
+                    "zwlr_foreign_toplevel_manager_v1" => {
+                        registry.bind::<ZwlrForeignToplevelManagerV1, _, _>(
+                            name,
+                            version.min(3),
+                            qh,
+                            (),
+                        );
+                    }
+                    "ext_foreign_toplevel_list_v1" => {
+                        registry.bind::<ExtForeignToplevelListV1, _, _>(
+                            name,
+                            version.min(1),
+                            qh,
+                            (),
+                        );
+                    }
+                    "zwlr_output_manager_v1" => {
+                        // Version 4 adds the adaptive sync state.
+                        registry.bind::<ZwlrOutputManagerV1, _, _>(name, version.min(4), qh, ());
+                    }
+                    "wl_output" => {
+                        // Version 4 adds the connector name.
+                        let output = registry.bind::<WlOutput, _, _>(name, version.min(4), qh, ());
+                        task::block_on(async {
+                            state.wayland_outputs.write().await.insert(
+                                output.id(),
+                                WaylandOutput {
+                                    id: output.id(),
+                                    global_name: name,
+                                    name: String::new(),
+                                    description: String::new(),
+                                    make: String::new(),
+                                    model: String::new(),
+                                },
+                            );
+                        });
+                    }
+                    "ext_workspace_manager_v1" => {
+                        let workspace_manager =
+                            registry.bind::<ExtWorkspaceManagerV1, _, _>(name, 1, qh, ());
+                        task::block_on(async {
+                            state
+                                .workspace_manager
+                                .write()
+                                .await
+                                .replace(workspace_manager);
+                        });
+                    }
+                    "wl_seat" => {
+                        let seat = registry.bind::<WlSeat, _, _>(name, version.min(7), qh, ());
+                        // The first seat is the one the bar's own input goes through.
+                        task::block_on(async {
+                            state.seat.write().await.get_or_insert(seat);
+                        });
+                    }
+                    &_ => {}
+                }
+            }
+            wl_registry::Event::GlobalRemove { name } => {
+                trace!("Global removed {0}", name);
+                task::block_on(async {
+                    let mut wayland_outputs = state.wayland_outputs.write().await;
+                    let len = wayland_outputs.len();
+                    wayland_outputs.retain(|_, output| output.global_name != name);
+                    if wayland_outputs.len() != len {
+                        state
+                            .output_event_sender
+                            .broadcast_direct(OutputEvent::WaylandOutputsUpdated(
+                                wayland_outputs.values().cloned().collect(),
+                            ))
+                            .await
+                            .ok();
+                    }
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlOutput, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        proxy: &WlOutput,
+        event: <WlOutput as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        trace!("WLOuput ID: {:?}, event: {:?}", proxy, event);
+
+        task::block_on(async {
+            let mut wayland_outputs = state.wayland_outputs.write().await;
+            let Some(output) = wayland_outputs.get_mut(&proxy.id()) else {
+                return;
+            };
+
+            match event {
+                wl_output::Event::Geometry { make, model, .. } => {
+                    output.make = make;
+                    output.model = model;
+                }
+                wl_output::Event::Name { name } => output.name = name,
+                wl_output::Event::Description { description } => output.description = description,
+                wl_output::Event::Done => {
+                    state
+                        .output_event_sender
+                        .broadcast_direct(OutputEvent::WaylandOutputsUpdated(
+                            wayland_outputs.values().cloned().collect(),
+                        ))
+                        .await
+                        .ok();
+                }
+                _ => {}
+            }
+        });
+    }
+}
+
+impl Dispatch<WlSeat, ()> for WaylandDispatchReceiver {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlSeat,
+        _event: <WlSeat as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrOutputManagerV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZwlrOutputManagerV1,
+        event: <ZwlrOutputManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        task::block_on(async {
+            if let zwlr_output_manager_v1::Event::Done { serial: _ } = event {
+                state
+                    .output_event_sender
+                    .broadcast_direct(OutputEvent::OutputsUpdated(
+                        state.outputs_state.read().await.values().cloned().collect(),
+                    ))
+                    .await
+                    .ok();
+            }
+        });
+    }
+
+    event_created_child!(WaylandDispatchReceiver, ZwlrOutputManagerV1, [
+      _ => ( ZwlrOutputHeadV1, ())
+    ]);
+}
+
+impl Dispatch<ZwlrOutputHeadV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrOutputHeadV1,
+        event: <ZwlrOutputHeadV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        task::block_on(async {
+            let mut outputs_write = state.outputs_state.write().await;
+            let mut output = outputs_write.get_mut(&proxy.id());
+            if output.is_none() {
+                outputs_write.insert(proxy.id(), Output::default());
+                output = outputs_write.get_mut(&proxy.id());
+            }
+            let output = output.as_mut().unwrap();
+
+            match event {
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Name { name } => output.name = name,
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Description { description } => output.description = description,
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::PhysicalSize { width, height } => output.size = [width, height],
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Mode { mode: _mode } => {},
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Enabled { enabled } => output.enabled = enabled != 0,
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::CurrentMode { mode: _mode } => {},
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Position { x, y } => output.position = [x, y],
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Transform { transform: _transform } => {},
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Scale { scale } => output.scale = scale,
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Make { make } => output.make = make,
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Model { model } => output.model = model,
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::SerialNumber { serial_number } => output.serial_number = serial_number,
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::AdaptiveSync { state } => output.adaptive_sync = state.into_result().map(|s| s == zwlr_output_head_v1::AdaptiveSyncState::Enabled).unwrap_or(false),
+                wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event::Finished => {
+                    outputs_write.remove(&proxy.id());
+                    proxy.release();
+                },
+                _ => {}
+            }
+        });
+    }
+
+    event_created_child!(WaylandDispatchReceiver, ZwlrOutputHeadV1, [
+      _ => ( ZwlrOutputModeV1, ())
+    ]);
+}
+
+impl Dispatch<ZwlrOutputModeV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrOutputModeV1,
+        _event: <ZwlrOutputModeV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtWorkspaceManagerV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        proxy: &ExtWorkspaceManagerV1,
+        event: <ExtWorkspaceManagerV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                state.pending_workspaces.groups.push(ExtWorkspaceGroup {
+                    id: workspace_group.id(),
+                    outputs: Vec::new(),
+                    workspaces: Vec::new(),
+                })
+            }
+            ext_workspace_manager_v1::Event::Workspace { workspace } => state
+                .pending_workspaces
+                .workspaces
+                .push(ExtWorkspace::new(&workspace)),
+            // Changes are sent as a batch which is only complete once done is sent.
+            ext_workspace_manager_v1::Event::Done => task::block_on(async {
+                let mut writer = state.workspaces_state.write().await;
+                state
+                    .workspace_event_sender
+                    .broadcast_direct(WorkspaceEvent::WorkspacesUpdated(
+                        state.pending_workspaces.clone(),
+                    ))
+                    .await
+                    .ok();
+                *writer = state.pending_workspaces.clone();
+            }),
+            ext_workspace_manager_v1::Event::Finished => {
+                debug!("Workspace manager finished");
+                task::block_on(async {
+                    state.workspace_manager.write().await.take();
+                });
+                proxy.stop();
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(WaylandDispatchReceiver, ExtWorkspaceManagerV1, [
+        ext_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (ExtWorkspaceGroupHandleV1, ()),
+        ext_workspace_manager_v1::EVT_WORKSPACE_OPCODE => (ExtWorkspaceHandleV1, ())
+    ]);
+}
+
+impl Dispatch<ExtWorkspaceGroupHandleV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        proxy: &ExtWorkspaceGroupHandleV1,
+        event: <ExtWorkspaceGroupHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let groups = &mut state.pending_workspaces.groups;
+        if let ext_workspace_group_handle_v1::Event::Removed = event {
+            groups.retain(|group| group.id != proxy.id());
+            proxy.destroy();
+            return;
+        }
+        let Some(group) = groups.iter_mut().find(|group| group.id == proxy.id()) else {
+            return;
+        };
+
+        match event {
+            ext_workspace_group_handle_v1::Event::OutputEnter { output } => {
+                group.outputs.push(output.id())
+            }
+            ext_workspace_group_handle_v1::Event::OutputLeave { output } => {
+                group.outputs.retain(|id| *id != output.id())
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceEnter { workspace } => {
+                group.workspaces.push(workspace.id())
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceLeave { workspace } => {
+                group.workspaces.retain(|id| *id != workspace.id())
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceHandleV1, ()> for WaylandDispatchReceiver {
+    fn event(
+        state: &mut Self,
+        proxy: &ExtWorkspaceHandleV1,
+        event: <ExtWorkspaceHandleV1 as Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let pending_workspaces = &mut state.pending_workspaces;
+        if let ext_workspace_handle_v1::Event::Removed = event {
+            pending_workspaces
+                .workspaces
+                .retain(|workspace| workspace.id != proxy.id());
+            for group in pending_workspaces.groups.iter_mut() {
+                group.workspaces.retain(|id| *id != proxy.id());
+            }
+            proxy.destroy();
+            return;
+        }
+        let Some(workspace) = pending_workspaces
+            .workspaces
+            .iter_mut()
+            .find(|workspace| workspace.id == proxy.id())
+        else {
+            return;
+        };
+
+        match event {
+            ext_workspace_handle_v1::Event::Name { name } => workspace.name = name,
+            ext_workspace_handle_v1::Event::Coordinates { coordinates } => {
+                workspace.coordinates = coordinates
+                    .chunks_exact(4)
+                    .map(|value| u32::from_ne_bytes(value.try_into().unwrap()))
+                    .collect()
+            }
+            // Bits from newer protocol versions are dropped.
+            ext_workspace_handle_v1::Event::State { state } => {
+                workspace.state = match state {
+                    WEnum::Value(state) => state,
+                    WEnum::Unknown(bits) => {
+                        ext_workspace_handle_v1::State::from_bits_truncate(bits)
+                    }
+                }
+            }
+            ext_workspace_handle_v1::Event::Capabilities { capabilities } => {
+                workspace.capabilities = match capabilities {
+                    WEnum::Value(capabilities) => capabilities,
+                    WEnum::Unknown(bits) => {
+                        ext_workspace_handle_v1::WorkspaceCapabilities::from_bits_truncate(bits)
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
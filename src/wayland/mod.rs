@@ -0,0 +1,2 @@
+pub mod compositor;
+pub mod manager;
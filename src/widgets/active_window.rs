@@ -0,0 +1,322 @@
+use std::cell::{Cell, OnceCell, RefCell};
+
+use gio::glib::clone;
+use gio::prelude::*;
+use gtk4::glib::{Object, Properties};
+use gtk4::pango::EllipsizeMode;
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{Accessible, Buildable, ConstraintTarget, Image, Label, Orientable, Widget, glib};
+
+use crate::compositor::{self, WindowInfo};
+use crate::xdg_applications::XdgApplicationsCache;
+
+pub const DEFAULT_FORMAT: &str = "{title}";
+
+// Object holding the state
+#[derive(Properties, Default)]
+#[properties(wrapper_type = ActiveWindow)]
+pub struct ActiveWindowImpl {
+    #[property(get, construct_only)]
+    monitor_id: OnceCell<i32>,
+    // Text shown for the window, {class}, {title} and {initial_title} are replaced with the window's details
+    #[property(get, construct_only)]
+    format: RefCell<String>,
+    // Longest the text can get before it is ellipsized, -1 for no limit
+    #[property(get, construct_only)]
+    max_width_chars: Cell<i32>,
+    #[property(get, construct_only)]
+    show_icon: Cell<bool>,
+    // Show the last focused window on this monitor instead of the focused window on any monitor
+    #[property(get, construct_only)]
+    per_monitor: Cell<bool>,
+    windows: RefCell<Vec<WindowInfo>>,
+    // None until the compositor reports the focused window, the focus history is used until then
+    active_address: RefCell<Option<String>>,
+    // Last window focused on this monitor
+    monitor_address: RefCell<String>,
+    active_workspace_id: Cell<Option<i32>>,
+    // Class the icon was looked up for
+    icon_class: RefCell<String>,
+    label: Label,
+    icon: Image,
+}
+
+impl ActiveWindowImpl {
+    fn update(&self) {
+        let windows = self.windows.borrow();
+        let active_window = match self.active_address.borrow().as_ref() {
+            Some(address) => windows.iter().find(|w| w.address == *address),
+            None => windows.iter().find(|w| w.focus_history_id == 0),
+        };
+
+        let window = if self.per_monitor.get() {
+            let monitor_id = *self.monitor_id.get().unwrap();
+            if let Some(active_window) = active_window
+                && active_window.monitor_id == monitor_id
+            {
+                self.monitor_address.replace(active_window.address.clone());
+            }
+
+            let active_workspace_id = self.active_workspace_id.get();
+            let visible_windows = windows.iter().filter(|w| {
+                w.monitor_id == monitor_id
+                    && active_workspace_id.is_none_or(|id| w.is_on_workspace(id))
+            });
+            let monitor_address = self.monitor_address.borrow();
+            visible_windows
+                .clone()
+                .find(|w| w.address == *monitor_address)
+                .or_else(|| visible_windows.min_by_key(|w| w.focus_history_id))
+        } else {
+            active_window
+        };
+
+        self.show(window);
+    }
+
+    fn show(&self, window: Option<&WindowInfo>) {
+        let Some(window) = window else {
+            self.obj().add_css_class("empty");
+            self.label.set_text("");
+            self.label.set_tooltip_text(None);
+            self.icon.set_visible(false);
+            self.icon_class.replace(String::new());
+            return;
+        };
+
+        self.obj().remove_css_class("empty");
+        let text = format_text(&self.format.borrow(), window);
+        self.label.set_text(&text);
+        self.label.set_tooltip_text(Some(&window.title));
+
+        if !self.show_icon.get() || *self.icon_class.borrow() == window.class {
+            return;
+        }
+        self.icon_class.replace(window.class.clone());
+
+        let window = window.clone();
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = me)]
+            self.obj(),
+            async move {
+                let cache = XdgApplicationsCache::get_instance().await;
+                let icon = cache
+                    .get_application_by_class(&window.initial_class)
+                    .or_else(|| cache.get_application_by_class(&window.class))
+                    .and_then(|app_info| app_info.string("Icon"));
+
+                let imp = me.imp();
+                // Another window may have been focused while looking up the icon.
+                if *imp.icon_class.borrow() != window.class {
+                    return;
+                }
+                match icon {
+                    Some(icon) => {
+                        imp.icon.set_icon_name(Some(icon.as_str()));
+                        imp.icon.set_visible(true);
+                    }
+                    None => imp.icon.set_visible(false),
+                }
+            }
+        ));
+    }
+}
+
+// The central trait for subclassing a GObject
+#[glib::object_subclass]
+impl ObjectSubclass for ActiveWindowImpl {
+    const NAME: &'static str = "TwBarActiveWindow";
+    type Type = ActiveWindow;
+    type ParentType = gtk4::Box;
+}
+
+// Trait shared by all GObjects
+#[glib::derived_properties]
+impl ObjectImpl for ActiveWindowImpl {
+    fn constructed(&self) {
+        self.parent_constructed();
+
+        self.obj().add_css_class("active_window");
+        self.obj().add_css_class("empty");
+        self.obj().set_spacing(8);
+
+        self.icon.set_visible(false);
+        self.obj().append(&self.icon);
+        self.label.set_ellipsize(EllipsizeMode::End);
+        self.label.set_max_width_chars(self.max_width_chars.get());
+        self.obj().append(&self.label);
+
+        let self_ref = self.downgrade();
+
+        glib::spawn_future_local(clone!(
+            #[strong]
+            self_ref,
+            async move {
+                let compositor = compositor::instance().await;
+                let mut windows_state = compositor.windows();
+
+                loop {
+                    let windows = windows_state.next().await;
+
+                    match self_ref.upgrade() {
+                        Some(me) => {
+                            me.windows.replace(windows);
+                            me.update();
+                        }
+                        None => return,
+                    };
+                }
+            }
+        ));
+
+        if self.per_monitor.get() {
+            glib::spawn_future_local(clone!(
+                #[strong]
+                self_ref,
+                async move {
+                    let compositor = compositor::instance().await;
+                    let mut monitors_state = compositor.monitors();
+
+                    loop {
+                        let monitors = monitors_state.next().await;
+
+                        match self_ref.upgrade() {
+                            Some(me) => {
+                                let monitor_id = *me.monitor_id.get().unwrap();
+                                let workspace_id = monitors
+                                    .iter()
+                                    .find(|m| m.id == monitor_id)
+                                    .map(|m| m.active_workspace_id);
+                                if me.active_workspace_id.replace(workspace_id) != workspace_id {
+                                    me.update();
+                                }
+                            }
+                            None => return,
+                        };
+                    }
+                }
+            ));
+        }
+
+        glib::spawn_future_local(clone!(
+            #[strong]
+            self_ref,
+            async move {
+                let compositor = compositor::instance().await;
+                let mut active_window = compositor.active_window();
+
+                loop {
+                    let address = active_window.next().await;
+
+                    match self_ref.upgrade() {
+                        Some(me) => {
+                            me.active_address.replace(Some(address));
+                            me.update();
+                        }
+                        None => return,
+                    };
+                }
+            }
+        ));
+    }
+}
+
+// Trait shared by all widgets
+impl WidgetImpl for ActiveWindowImpl {}
+
+// Trait shared by all boxes
+impl BoxImpl for ActiveWindowImpl {}
+
+// Self encapsulated label showing the focused window's icon and title
+glib::wrapper! {
+    pub struct ActiveWindow(ObjectSubclass<ActiveWindowImpl>)
+        @extends gtk4::Box, Widget,
+        @implements Accessible, Buildable, ConstraintTarget, Orientable;
+}
+
+impl ActiveWindow {
+    pub fn new(
+        monitor_id: i32,
+        format: &str,
+        max_width_chars: i32,
+        show_icon: bool,
+        per_monitor: bool,
+    ) -> Self {
+        Object::builder()
+            .property("monitor-id", monitor_id)
+            .property("format", format)
+            .property("max-width-chars", max_width_chars)
+            .property("show-icon", show_icon)
+            .property("per-monitor", per_monitor)
+            .build()
+    }
+}
+
+// Replaces the placeholders in one pass over the format, so placeholders in a window's title or class are kept as is.
+fn format_text(format: &str, window: &WindowInfo) -> String {
+    let placeholders = [
+        ("{class}", &window.class),
+        ("{initial_title}", &window.initial_title),
+        ("{title}", &window.title),
+    ];
+
+    let mut text = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match placeholders.iter().find(|(name, _)| rest.starts_with(name)) {
+            Some((name, value)) => {
+                text.push_str(value);
+                rest = &rest[name.len()..];
+            }
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(class: &str, title: &str, initial_title: &str) -> WindowInfo {
+        WindowInfo {
+            class: class.to_owned(),
+            title: title.to_owned(),
+            initial_title: initial_title.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn placeholders() {
+        let window = window("firefox", "GitHub - Mozilla Firefox", "Mozilla Firefox");
+        assert_eq!(
+            format_text(DEFAULT_FORMAT, &window),
+            "GitHub - Mozilla Firefox"
+        );
+        assert_eq!(
+            format_text("{class}: {title} ({initial_title})", &window),
+            "firefox: GitHub - Mozilla Firefox (Mozilla Firefox)"
+        );
+        assert_eq!(
+            format_text("{{title}} {unknown} {", &window),
+            "{GitHub - Mozilla Firefox} {unknown} {"
+        );
+    }
+
+    #[test]
+    fn placeholders_in_the_window_are_kept() {
+        let window = window("{title}", "echo {initial_title} {class}", "kitty");
+        assert_eq!(
+            format_text("{class} {title}", &window),
+            "{title} echo {initial_title} {class}"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+use gtk4::glib::{Object, Properties, SourceId, clone};
+use gtk4::prelude::*;
+use gtk4::subclass::prelude::*;
+use gtk4::{
+    Accessible, Buildable, ConstraintTarget, EventControllerMotion, Orientable, Orientation,
+    Revealer, RevealerTransitionType, Widget, glib,
+};
+
+// Thickness of the strip left along the screen edge while the bar is hidden
+const STRIP_SIZE: i32 = 3;
+const TRANSITION_DURATION: u32 = 200;
+
+// Object holding the state
+#[derive(Properties, Default)]
+#[properties(wrapper_type = AutoHide)]
+pub struct AutoHideImpl {
+    // Milliseconds to wait after the pointer leaves before hiding
+    #[property(get, construct_only)]
+    hide_delay: Cell<u32>,
+    revealer: Revealer,
+    hovered: Cell<bool>,
+    // Kept revealed regardless of the pointer, e.g. while a submap is active
+    pinned: Cell<bool>,
+    hide_source: RefCell<Option<SourceId>>,
+}
+
+// The central trait for subclassing a GObject
+#[glib::object_subclass]
+impl ObjectSubclass for AutoHideImpl {
+    const NAME: &'static str = "TwBarAutoHide";
+    type Type = AutoHide;
+    type ParentType = gtk4::Box;
+}
+
+// Trait shared by all GObjects
+#[glib::derived_properties]
+impl ObjectImpl for AutoHideImpl {
+    fn constructed(&self) {
+        self.parent_constructed();
+
+        self.obj().add_css_class("auto_hide");
+        self.revealer.set_transition_duration(TRANSITION_DURATION);
+        self.revealer.set_hexpand(true);
+        self.revealer.set_vexpand(true);
+        self.obj().append(&self.revealer);
+
+        let motion_controller = EventControllerMotion::new();
+        motion_controller.connect_enter(clone!(
+            #[weak(rename_to = me)]
+            self,
+            move |_, _, _| {
+                me.hovered.set(true);
+                me.obj().reveal();
+            }
+        ));
+        motion_controller.connect_leave(clone!(
+            #[weak(rename_to = me)]
+            self,
+            move |_| {
+                me.hovered.set(false);
+                me.obj().schedule_hide();
+            }
+        ));
+        self.obj().add_controller(motion_controller);
+
+        self.revealer.connect_child_revealed_notify(clone!(
+            #[weak(rename_to = me)]
+            self,
+            move |revealer| {
+                // GTK windows don't shrink on their own, ask for the smallest size so only the strip is left.
+                if !revealer.is_child_revealed()
+                    && let Some(window) = me.obj().root().and_downcast::<gtk4::Window>()
+                {
+                    window.set_default_size(1, 1);
+                }
+            }
+        ));
+    }
+
+    fn dispose(&self) {
+        if let Some(source) = self.hide_source.take() {
+            source.remove();
+        }
+    }
+}
+
+// Trait shared by all widgets
+impl WidgetImpl for AutoHideImpl {}
+
+// Trait shared by all boxes
+impl BoxImpl for AutoHideImpl {}
+
+// Collapses the bar to a thin strip along its screen edge, sliding it out while the pointer is over the strip.
+glib::wrapper! {
+    pub struct AutoHide(ObjectSubclass<AutoHideImpl>)
+        @extends gtk4::Box, Widget,
+        @implements Accessible, Buildable, ConstraintTarget, Orientable;
+}
+
+impl AutoHide {
+    // The transition type sets which way the bar slides out, e.g. SlideDown for a bar along the top edge.
+    pub fn new(
+        child: &impl IsA<Widget>,
+        transition_type: RevealerTransitionType,
+        hide_delay: u32,
+    ) -> Self {
+        let auto_hide: Self = Object::builder().property("hide-delay", hide_delay).build();
+
+        let imp = auto_hide.imp();
+        imp.revealer.set_transition_type(transition_type);
+        imp.revealer.set_child(Some(child));
+        match transition_type {
+            RevealerTransitionType::SlideLeft | RevealerTransitionType::SlideRight => {
+                auto_hide.set_orientation(Orientation::Horizontal);
+                auto_hide.set_size_request(STRIP_SIZE, -1);
+            }
+            _ => {
+                auto_hide.set_orientation(Orientation::Vertical);
+                auto_hide.set_size_request(-1, STRIP_SIZE);
+            }
+        }
+
+        auto_hide
+    }
+
+    pub fn reveal(&self) {
+        self.cancel_hide();
+        self.imp().revealer.set_reveal_child(true);
+    }
+
+    // Hides the bar after the delay unless the pointer is over it or it is pinned by then.
+    pub fn schedule_hide(&self) {
+        self.cancel_hide();
+        let source = glib::timeout_add_local_once(
+            Duration::from_millis(self.hide_delay().into()),
+            clone!(
+                #[weak(rename_to = me)]
+                self,
+                move || {
+                    let imp = me.imp();
+                    imp.hide_source.take();
+                    if !imp.hovered.get() && !imp.pinned.get() {
+                        imp.revealer.set_reveal_child(false);
+                    }
+                }
+            ),
+        );
+        self.imp().hide_source.replace(Some(source));
+    }
+
+    // Shows the bar briefly, as if the pointer had touched the screen edge.
+    pub fn peek(&self) {
+        self.reveal();
+        if !self.imp().hovered.get() {
+            self.schedule_hide();
+        }
+    }
+
+    pub fn set_pinned(&self, pinned: bool) {
+        self.imp().pinned.set(pinned);
+        if pinned {
+            self.reveal();
+        } else {
+            self.schedule_hide();
+        }
+    }
+
+    fn cancel_hide(&self) {
+        if let Some(source) = self.imp().hide_source.take() {
+            source.remove();
+        }
+    }
+}
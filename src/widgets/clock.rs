@@ -1,16 +1,33 @@
 use async_std::task::sleep;
+use std::cell::RefCell;
+use std::fmt::Write;
 use std::time::Duration;
 
 use chrono::Local;
 use gio::glib::clone;
-use gtk4::glib::Object;
+use gtk4::glib::{Object, Properties};
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 use gtk4::{Accessible, Buildable, ConstraintTarget, Orientable, Widget, glib};
 
+const DEFAULT_FORMAT: &str = "%b %e %Y %l:%M %p";
+
 // Object holding the state
-#[derive(Default)]
-pub struct ClockImpl {}
+#[derive(Properties)]
+#[properties(wrapper_type = Clock)]
+pub struct ClockImpl {
+    // strftime style format string used to render the time
+    #[property(get, construct_only)]
+    format: RefCell<String>,
+}
+
+impl Default for ClockImpl {
+    fn default() -> Self {
+        Self {
+            format: RefCell::new(DEFAULT_FORMAT.to_owned()),
+        }
+    }
+}
 
 // The central trait for subclassing a GObject
 #[glib::object_subclass]
@@ -21,6 +38,7 @@ impl ObjectSubclass for ClockImpl {
 }
 
 // Trait shared by all GObjects
+#[glib::derived_properties]
 impl ObjectImpl for ClockImpl {
     fn constructed(&self) {
         self.parent_constructed();
@@ -31,13 +49,20 @@ impl ObjectImpl for ClockImpl {
         self.obj().add_css_class("clock");
         self.obj().set_spacing(0);
 
+        let format = self.format.borrow().clone();
         glib::spawn_future_local(clone!(
             #[weak]
             label,
             async move {
                 loop {
                     let now = Local::now();
-                    label.set_text(&format!("{}", now.format("%b %e %Y %l:%M %p")));
+                    let mut text = String::new();
+                    // chrono reports invalid format strings as a formatting error rather than when parsing them.
+                    if write!(text, "{}", now.format(&format)).is_err() {
+                        log::error!("Invalid clock format: {}", format);
+                        text = format!("{}", now.format(DEFAULT_FORMAT));
+                    }
+                    label.set_text(&text);
                     sleep(Duration::from_secs(10)).await;
                 }
             }
@@ -66,6 +91,10 @@ impl Default for Clock {
 
 impl Clock {
     pub fn new() -> Self {
-        Object::builder().build()
+        Self::with_format(DEFAULT_FORMAT)
+    }
+
+    pub fn with_format(format: &str) -> Self {
+        Object::builder().property("format", format).build()
     }
 }
@@ -9,12 +9,16 @@ use gtk4::subclass::prelude::*;
 use gtk4::{
     glib, Accessible, Align, Buildable, ConstraintTarget, GestureClick, Label, Orientable, Widget,
 };
+use serde::Deserialize;
 
-#[derive(glib::Boxed, Default, Clone, Debug)]
+#[derive(glib::Boxed, Default, Clone, Debug, PartialEq, Deserialize)]
 #[boxed_type(name = "ButtonCommandType")]
+#[serde(deny_unknown_fields)]
 pub struct ButtonCommand {
     pub command: String,
+    #[serde(default)]
     pub args: Vec<String>,
+    #[serde(default)]
     pub allow_failure: bool,
 }
 
@@ -56,6 +60,7 @@ impl ObjectImpl for CommandButtonImpl {
         label.set_halign(Align::Center);
         self.obj().append(&label);
         self.obj().set_halign(Align::Center);
+        self.obj().add_css_class("command_button");
 
         let commands = self.obj().commands().borrow().clone();
         event_controller.connect_released(move |_box, _, _, _| {
@@ -1,3 +1,5 @@
+pub mod active_window;
+pub mod auto_hide;
 pub mod battery_info;
 pub mod clock;
 pub mod command_button;
@@ -7,11 +7,11 @@ use gio::prelude::*;
 use gtk4::glib::{Object, Properties};
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
-use gtk4::{Accessible, Buildable, ConstraintTarget, Orientable, Widget, glib};
+use gtk4::{Accessible, Buildable, ConstraintTarget, Orientable, Orientation, Widget, glib};
 use log::trace;
 
-use crate::hyprland::events::{HyprlandEvent, HyprlandEvents};
-use crate::hyprland::windows::{HyprlandWindow, HyprlandWindows};
+use crate::compositor::diff::ListDiff;
+use crate::compositor::{self, WindowInfo};
 
 use super::taskbar_button::TaskbarButton;
 
@@ -22,17 +22,88 @@ pub struct TaskbarImpl {
     #[property(get, construct_only)]
     monitor_id: OnceCell<i32>,
     selected_address: RefCell<String>,
-    windows: RefCell<Vec<HyprlandWindow>>,
+    windows: RefCell<Vec<WindowInfo>>,
 }
 
 impl TaskbarImpl {
+    fn shows(&self, window: &WindowInfo) -> bool {
+        window.monitor_id == *self.monitor_id.get().unwrap()
+    }
+
+    // Order of the buttons
+    fn sort_key(window: &WindowInfo) -> (Option<i32>, (i32, i32)) {
+        (window.workspace_id, window.at)
+    }
+
+    fn buttons(&self) -> impl Iterator<Item = TaskbarButton> {
+        let mut child = self.obj().first_child();
+        std::iter::from_fn(move || {
+            let button = child.take()?;
+            child = button.next_sibling();
+            Some(button.downcast::<TaskbarButton>().unwrap())
+        })
+    }
+
+    fn button(&self, address: &str) -> Option<TaskbarButton> {
+        self.buttons().find(|b| b.window_info().address == address)
+    }
+
+    fn apply_changes(&self, changes: &ListDiff<WindowInfo>) {
+        for address in &changes.removed {
+            if let Some(button) = self.button(address) {
+                self.obj().remove(&button);
+            }
+        }
+        for window in changes.changed.iter().chain(&changes.added) {
+            self.sync_window(window);
+        }
+
+        // Windows which moved can leave the buttons out of order, fall back to updating all of them then.
+        if !self
+            .buttons()
+            .map(|b| Self::sort_key(&b.window_info()))
+            .is_sorted()
+        {
+            self.update_buttons();
+        }
+    }
+
+    // Adds, updates or removes the window's button, e.g. when the window moves to another monitor.
+    fn sync_window(&self, window: &WindowInfo) {
+        match (self.button(&window.address), self.shows(window)) {
+            (Some(button), true) => button.set_window_info(window),
+            (Some(button), false) => self.obj().remove(&button),
+            (None, true) => {
+                let new_button =
+                    TaskbarButton::new(window, self.obj().orientation() == Orientation::Vertical);
+                if window.address == *self.selected_address.borrow() {
+                    new_button.add_css_class("active");
+                }
+                let previous = self
+                    .buttons()
+                    .take_while(|b| Self::sort_key(&b.window_info()) <= Self::sort_key(window))
+                    .last();
+                self.obj()
+                    .insert_child_after(&new_button, previous.as_ref());
+            }
+            (None, false) => {}
+        }
+    }
+
+    fn set_selected_address(&self, address: String) {
+        let previous = self.selected_address.replace(address);
+        if let Some(button) = self.button(&previous) {
+            button.remove_css_class("active");
+        }
+        if let Some(button) = self.button(&self.selected_address.borrow()) {
+            button.add_css_class("active");
+        }
+    }
+
     fn update_buttons(&self) {
         let windows = self.windows.borrow();
-        let mut windows: Vec<&HyprlandWindow> = windows
-            .iter()
-            .filter(|w| w.monitor == *self.monitor_id.get().unwrap())
-            .collect();
-        windows.sort_by_key(|w| (w.workspace.id, w.at));
+        let mut windows: Vec<&WindowInfo> = windows.iter().filter(|w| self.shows(w)).collect();
+        windows.sort_by_key(|w| Self::sort_key(w));
 
         trace!("Windows: {:?}", windows);
 
@@ -42,7 +113,7 @@ impl TaskbarImpl {
             let taskbar_button = button.clone().downcast::<TaskbarButton>().unwrap();
             child = button.next_sibling();
 
-            let window_address = taskbar_button.hyprland_window().address;
+            let window_address = taskbar_button.window_info().address;
             if windows.iter().any(|w| w.address == window_address) {
                 if window_address != *self.selected_address.borrow() {
                     taskbar_button.remove_css_class("active");
@@ -64,7 +135,8 @@ impl TaskbarImpl {
                 self.obj().reorder_child_after(button, last_button.as_ref());
                 last_button = Some(button.clone());
             } else {
-                let new_button = TaskbarButton::new(w);
+                let new_button =
+                    TaskbarButton::new(w, self.obj().orientation() == Orientation::Vertical);
                 if w.address == *self.selected_address.borrow() {
                     new_button.add_css_class("active");
                 }
@@ -98,16 +170,16 @@ impl ObjectImpl for TaskbarImpl {
             #[strong]
             self_ref,
             async move {
-                let hyprland_windows = HyprlandWindows::instance().await;
-                let mut windows_state = hyprland_windows.get_windows_update_emitter();
+                let compositor = compositor::instance().await;
+                let mut windows_state = compositor.window_changes();
 
                 loop {
-                    let windows = windows_state.next().await;
+                    let (windows, changes) = windows_state.next().await;
 
                     match self_ref.upgrade() {
                         Some(me) => {
                             me.windows.set(windows);
-                            me.update_buttons();
+                            me.apply_changes(&changes);
                         }
                         None => return,
                     };
@@ -119,22 +191,15 @@ impl ObjectImpl for TaskbarImpl {
             #[strong]
             self_ref,
             async move {
-                let events = HyprlandEvents::instance().await;
-                let mut event_stream = events.get_event_stream().await;
+                let compositor = compositor::instance().await;
+                let mut active_window = compositor.active_window();
 
                 loop {
-                    match event_stream.recv_direct().await {
-                        Ok(HyprlandEvent::ActiveWindowV2(address)) => {
-                            match self_ref.upgrade() {
-                                Some(me) => {
-                                    me.selected_address.set(address);
-                                    me.update_buttons();
-                                }
-                                None => return,
-                            };
-                        }
-                        Ok(_) => {}
-                        _ => return,
+                    let address = active_window.next().await;
+
+                    match self_ref.upgrade() {
+                        Some(me) => me.set_selected_address(address),
+                        None => return,
                     };
                 }
             }
@@ -1,34 +1,44 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
 use gio::glib::clone;
 use gio::glib::property::PropertySet;
 use gio::prelude::*;
-use gtk4::glib::{Object, Properties};
+use gtk4::gdk::{BUTTON_MIDDLE, BUTTON_SECONDARY};
+use gtk4::glib::{Object, Properties, WeakRef};
 use gtk4::subclass::prelude::*;
 use gtk4::{
-    Accessible, Actionable, Buildable, Button, ConstraintTarget, EventControllerMotion, Label,
-    Popover, Widget, glib,
+    Accessible, Actionable, Buildable, Button, ConstraintTarget, EventControllerMotion,
+    GestureClick, Label, Popover, Widget, glib,
 };
 use gtk4::{Orientation, prelude::*};
-use log::trace;
+use log::{error, trace};
 
-use crate::hyprland::commands::HyprlandCommands;
-use crate::hyprland::windows::HyprlandWindow;
+use crate::compositor::{self, Action, Compositor, WindowInfo};
 use crate::xdg_applications::XdgApplicationsCache;
 
 // Object holding the state
 #[derive(Properties, Default)]
 #[properties(wrapper_type = TaskbarButton)]
 pub struct TaskbarButtonImpl {
-    #[property(get, set = Self::set_hyprland_window, construct)]
-    hyprland_window: RefCell<HyprlandWindow>,
+    #[property(get, set = Self::set_window_info, construct)]
+    window_info: RefCell<WindowInfo>,
+    // Only show the application icon, used on vertical bars where there is no room for the name.
+    #[property(get, construct_only)]
+    icon_only: Cell<bool>,
     window_title: RefCell<String>,
+    // The context menu while it is open
+    menu: WeakRef<Popover>,
 }
 
 impl TaskbarButtonImpl {
-    fn set_hyprland_window(&self, current_window: HyprlandWindow) {
-        let previous_window = self.hyprland_window.replace(current_window.clone());
+    fn set_window_info(&self, current_window: WindowInfo) {
+        let previous_window = self.window_info.replace(current_window.clone());
         self.window_title.set(current_window.title);
+        if current_window.urgent {
+            self.obj().add_css_class("urgent");
+        } else {
+            self.obj().remove_css_class("urgent");
+        }
 
         if previous_window.class != current_window.class
             || previous_window.initial_class != current_window.initial_class
@@ -52,8 +62,10 @@ impl TaskbarButtonImpl {
                             let image = gtk4::Image::new();
                             image.set_icon_name(icon.unwrap().as_str().into());
                             button_box.append(&image);
-                            let label = Label::new(app_info.name().as_str().into());
-                            button_box.append(&label);
+                            if !button.icon_only() {
+                                let label = Label::new(app_info.name().as_str().into());
+                                button_box.append(&label);
+                            }
                             button.set_child(Some(&button_box));
                         } else {
                             button.set_label(app_info.name().as_str());
@@ -65,6 +77,65 @@ impl TaskbarButtonImpl {
             ));
         }
     }
+
+    fn dispatch(&self, action: Action) {
+        glib::spawn_future_local(async move {
+            let compositor = compositor::instance().await;
+            if let Err(err) = compositor.dispatch(action.clone()).await {
+                error!("Failed to dispatch {:?}: {}", action, err);
+            }
+        });
+    }
+
+    fn show_menu(&self) {
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = me)]
+            self,
+            async move {
+                let compositor = compositor::instance().await;
+                me.open_menu(compositor.as_ref());
+            }
+        ));
+    }
+
+    // Offers the window actions which the compositor supports.
+    fn open_menu(&self, compositor: &dyn Compositor) {
+        let address = self.window_info.borrow().address.clone();
+        let menu = Popover::new();
+        menu.add_css_class("taskbar_menu");
+        let menu_box = gtk4::Box::new(Orientation::Vertical, 0);
+        for (label, action) in [
+            ("Minimize", Action::ToggleMinimizeWindow(address.clone())),
+            ("Maximize", Action::ToggleMaximizeWindow(address.clone())),
+            ("Close", Action::CloseWindow(address)),
+        ]
+        .into_iter()
+        .filter(|(_, action)| compositor.supports(action))
+        {
+            let item = Button::with_label(label);
+            item.set_has_frame(false);
+            item.connect_clicked(clone!(
+                #[weak(rename_to = me)]
+                self,
+                #[weak]
+                menu,
+                move |_| {
+                    menu.popdown();
+                    me.dispatch(action.clone());
+                }
+            ));
+            menu_box.append(&item);
+        }
+        menu.set_child(Some(&menu_box));
+        menu.set_parent(self.obj().upcast_ref::<Widget>());
+        self.menu.set(Some(&menu));
+        // The menu is built again each time it is opened.
+        menu.connect_closed(|menu| {
+            let menu = menu.clone();
+            glib::idle_add_local_once(move || menu.unparent());
+        });
+        menu.popup();
+    }
 }
 
 // The central trait for subclassing a GObject
@@ -112,9 +183,33 @@ impl ObjectImpl for TaskbarButtonImpl {
             }
         ));
         self.obj().add_controller(event_controller);
+
+        // Middle click closes the window and right click opens a menu with the other window actions.
+        let click_controller = GestureClick::new();
+        click_controller.set_button(0);
+        click_controller.connect_released(clone!(
+            #[weak(rename_to = me)]
+            self,
+            move |gesture, _, _, _| match gesture.current_button() {
+                BUTTON_MIDDLE => {
+                    let address = me.window_info.borrow().address.clone();
+                    me.dispatch(Action::CloseWindow(address));
+                }
+                BUTTON_SECONDARY => me.show_menu(),
+                _ => {}
+            }
+        ));
+        self.obj().add_controller(click_controller);
         // Unparent to avoid the warning about a destroyed widget having children.
         self.obj().connect_destroy(move |_| popup.unparent());
     }
+
+    fn dispose(&self) {
+        // The button can go away while its menu is open.
+        if let Some(menu) = self.menu.upgrade() {
+            menu.unparent();
+        }
+    }
 }
 
 // Trait shared by all widgets
@@ -123,14 +218,8 @@ impl WidgetImpl for TaskbarButtonImpl {}
 // Trait shared by all buttons
 impl ButtonImpl for TaskbarButtonImpl {
     fn activate(&self) {
-        glib::spawn_future_local(clone!(
-            #[strong(rename_to = obj)]
-            self.obj(),
-            async move {
-                let address = obj.hyprland_window().address;
-                HyprlandCommands::set_active_window(&address).await;
-            }
-        ));
+        let address = self.window_info.borrow().address.clone();
+        self.dispatch(Action::FocusWindow(address));
     }
 
     fn clicked(&self) {
@@ -148,9 +237,10 @@ glib::wrapper! {
 }
 
 impl TaskbarButton {
-    pub fn new(window: &HyprlandWindow) -> Self {
+    pub fn new(window: &WindowInfo, icon_only: bool) -> Self {
         Object::builder()
-            .property("hyprland-window", window)
+            .property("window-info", window)
+            .property("icon-only", icon_only)
             .build()
     }
 }
@@ -5,10 +5,9 @@ use gtk4::glib::{Object, Properties};
 use gtk4::subclass::prelude::*;
 use gtk4::{glib, Accessible, Actionable, Buildable, Button, ConstraintTarget, Widget};
 use gtk4::{prelude::*, Orientation};
-use log::trace;
+use log::{error, trace};
 
-use crate::hyprland::commands::HyprlandCommands;
-use crate::hyprland::workspaces::HyprlandWorkspace;
+use crate::compositor::{self, Action, WorkspaceInfo};
 
 // Object holding the state
 #[derive(Properties, Default)]
@@ -56,7 +55,13 @@ impl ButtonImpl for WorkspaceButtonImpl {
 
         let workspace_id = *self.workspace_id.borrow();
         glib::spawn_future_local(async move {
-            HyprlandCommands::set_active_workspace(workspace_id).await;
+            let compositor = compositor::instance().await;
+            if let Err(err) = compositor
+                .dispatch(Action::FocusWorkspace(workspace_id))
+                .await
+            {
+                error!("Failed to switch to workspace {}: {}", workspace_id, err);
+            }
         });
     }
 
@@ -75,7 +80,7 @@ glib::wrapper! {
 }
 
 impl WorkspaceButton {
-    pub fn new(workspace: &HyprlandWorkspace) -> Self {
+    pub fn new(workspace: &WorkspaceInfo) -> Self {
         Object::builder()
             .property("workspace-id", workspace.id)
             .property("workspace-name", workspace.name.clone())
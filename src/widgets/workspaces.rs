@@ -9,7 +9,8 @@ use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 use gtk4::{Accessible, Buildable, ConstraintTarget, Orientable, Widget, glib};
 
-use crate::hyprland::workspaces::{HyprlandWorkspace, HyprlandWorkspaces};
+use crate::compositor::diff::ListDiff;
+use crate::compositor::{self, WorkspaceInfo};
 use crate::widgets::workspace_button::WorkspaceButton;
 
 // Object holding the state
@@ -19,20 +20,125 @@ pub struct WorkspacesImpl {
     #[property(get, construct_only)]
     monitor_id: OnceCell<i32>,
     selected_workspace_id: RefCell<i32>,
-    workspaces: RefCell<Vec<HyprlandWorkspace>>,
+    workspaces: RefCell<Vec<WorkspaceInfo>>,
 }
 
 impl WorkspacesImpl {
+    fn shows(&self, workspace: &WorkspaceInfo) -> bool {
+        (workspace.windows != Some(0) || workspace.id == *self.selected_workspace_id.borrow())
+            && workspace.monitor_id == *self.monitor_id.get().unwrap()
+    }
+
+    fn position(&self, workspace_id: i32) -> i32 {
+        self.workspaces
+            .borrow()
+            .iter()
+            .find(|w| w.id == workspace_id)
+            .map(|w| w.position)
+            .unwrap_or_default()
+    }
+
+    fn buttons(&self) -> impl Iterator<Item = WorkspaceButton> {
+        let mut child = self.obj().first_child();
+        std::iter::from_fn(move || {
+            let button = child.take()?;
+            child = button.next_sibling();
+            Some(button.downcast::<WorkspaceButton>().unwrap())
+        })
+    }
+
+    fn button(&self, workspace_id: i32) -> Option<WorkspaceButton> {
+        self.buttons().find(|b| b.workspace_id() == workspace_id)
+    }
+
+    fn apply_changes(&self, changes: &ListDiff<WorkspaceInfo>) {
+        for workspace_id in &changes.removed {
+            if let Some(button) = self.button(*workspace_id) {
+                self.obj().remove(&button);
+            }
+        }
+        for workspace in changes.changed.iter().chain(&changes.added) {
+            self.sync_workspace(workspace);
+        }
+        self.ensure_order();
+    }
+
+    // Workspaces which moved can leave the buttons out of order, fall back to updating all of them then.
+    fn ensure_order(&self) {
+        if !self
+            .buttons()
+            .map(|b| self.position(b.workspace_id()))
+            .is_sorted()
+        {
+            self.update_buttons();
+        }
+    }
+
+    // Adds, updates or removes the workspace's button, e.g. when its last window is closed.
+    fn sync_workspace(&self, workspace: &WorkspaceInfo) {
+        let mut button = self.button(workspace.id);
+        // The name can't be changed so renamed workspaces get a new button.
+        if let Some(renamed) = button.take_if(|b| b.workspace_name() != workspace.name) {
+            self.obj().remove(&renamed);
+        }
+
+        match (button, self.shows(workspace)) {
+            (Some(button), true) => self.update_classes(&button, workspace),
+            (Some(button), false) => self.obj().remove(&button),
+            (None, true) => {
+                let new_button = WorkspaceButton::new(workspace);
+                self.update_classes(&new_button, workspace);
+                let previous = self
+                    .buttons()
+                    .take_while(|b| self.position(b.workspace_id()) <= workspace.position)
+                    .last();
+                self.obj()
+                    .insert_child_after(&new_button, previous.as_ref());
+            }
+            (None, false) => {}
+        }
+    }
+
+    fn update_classes(&self, button: &WorkspaceButton, workspace: &WorkspaceInfo) {
+        if workspace.id == *self.selected_workspace_id.borrow() {
+            button.add_css_class("active");
+        } else {
+            button.remove_css_class("active");
+        }
+        if workspace.urgent {
+            button.add_css_class("urgent");
+        } else {
+            button.remove_css_class("urgent");
+        }
+    }
+
+    fn set_selected_workspace_id(&self, workspace_id: i32) {
+        let previous = self.selected_workspace_id.replace(workspace_id);
+        // Empty workspaces are only shown while selected so both buttons may need to be added or removed.
+        for workspace_id in [previous, workspace_id] {
+            let workspace = self
+                .workspaces
+                .borrow()
+                .iter()
+                .find(|w| w.id == workspace_id)
+                .cloned();
+            match workspace {
+                Some(workspace) => self.sync_workspace(&workspace),
+                None => {
+                    if let Some(button) = self.button(workspace_id) {
+                        self.obj().remove(&button);
+                    }
+                }
+            }
+        }
+        self.ensure_order();
+    }
+
     fn update_buttons(&self) {
         let workspaces = self.workspaces.borrow();
-        let mut workspaces: Vec<&HyprlandWorkspace> = workspaces
-            .iter()
-            .filter(|w| {
-                (w.windows > 0 || w.id == *self.selected_workspace_id.borrow())
-                    && w.monitor_id == *self.monitor_id.get().unwrap()
-            })
-            .collect();
-        workspaces.sort_by_key(|w| w.id);
+        let mut workspaces: Vec<&WorkspaceInfo> =
+            workspaces.iter().filter(|w| self.shows(w)).collect();
+        workspaces.sort_by_key(|w| w.position);
 
         let mut buttons = HashMap::new();
         let mut child = self.obj().first_child();
@@ -55,12 +161,12 @@ impl WorkspacesImpl {
         for w in workspaces.iter() {
             // The process is to find the button that belongs here, if no button belongs here add one
             let button = buttons.get(&w.id);
-            if let Some(button) = button {
+            let button = if let Some(button) = button {
                 if w.id == *self.selected_workspace_id.borrow() {
                     button.add_css_class("active");
                 }
                 self.obj().reorder_child_after(button, last_button.as_ref());
-                last_button = Some(button.clone());
+                button.clone()
             } else {
                 let new_button = WorkspaceButton::new(w);
                 if w.id == *self.selected_workspace_id.borrow() {
@@ -68,8 +174,14 @@ impl WorkspacesImpl {
                 }
                 self.obj()
                     .insert_child_after(&new_button, last_button.as_ref());
-                last_button = Some(new_button);
+                new_button
+            };
+            if w.urgent {
+                button.add_css_class("urgent");
+            } else {
+                button.remove_css_class("urgent");
             }
+            last_button = Some(button);
         }
     }
 }
@@ -91,35 +203,44 @@ impl ObjectImpl for WorkspacesImpl {
         self.obj().add_css_class("workspaces");
         self.obj().set_spacing(0);
 
+        let self_ref = self.downgrade();
+
         glib::spawn_future_local(clone!(
-            #[weak(rename_to = me)]
-            self,
+            #[strong]
+            self_ref,
             async move {
-                let hyprland_workspaces = HyprlandWorkspaces::instance().await;
-                let mut workspaces_state = hyprland_workspaces.get_workspaces_state_emitter();
+                let compositor = compositor::instance().await;
+                let mut workspaces_state = compositor.workspace_changes();
 
                 loop {
-                    let workspaces = workspaces_state.next().await;
+                    let (workspaces, changes) = workspaces_state.next().await;
 
-                    me.workspaces.set(workspaces);
-                    me.update_buttons();
+                    match self_ref.upgrade() {
+                        Some(me) => {
+                            me.workspaces.set(workspaces);
+                            me.apply_changes(&changes);
+                        }
+                        None => return,
+                    };
                 }
             }
         ));
 
         glib::spawn_future_local(clone!(
-            #[weak(rename_to = me)]
-            self,
+            #[strong]
+            self_ref,
             async move {
-                let hyprland_workspaces = HyprlandWorkspaces::instance().await;
+                let compositor = compositor::instance().await;
 
-                let mut active_workspace = hyprland_workspaces.get_active_workspace_id_state();
+                let mut active_workspace = compositor.active_workspace();
 
                 loop {
                     let active_workspace = active_workspace.next().await;
-                    me.selected_workspace_id.set(active_workspace);
 
-                    me.update_buttons();
+                    match self_ref.upgrade() {
+                        Some(me) => me.set_selected_workspace_id(active_workspace),
+                        None => return,
+                    };
                 }
             }
         ));